@@ -46,6 +46,116 @@
 //!     .send()
 //!     .await?;
 //! ```
+//!
+//! The `x-cache` and `x-cache-lookup` headers can be left off a single response
+//! in the same way.
+//!
+//! ```no_run
+//! # use reqwest::Client;
+//! # use reqwest_middleware::{ClientBuilder, Result};
+//! # use http_cache_reqwest::{Cache, CacheMode, CACacheManager, HttpCache, HttpCacheOptions, SuppressCacheHeaders};
+//! # #[tokio::main]
+//! # async fn main() -> Result<()> {
+//! # let client = ClientBuilder::new(Client::new())
+//! #     .with(Cache(HttpCache {
+//! #         mode: CacheMode::Default,
+//! #         manager: CACacheManager::default(),
+//! #         options: HttpCacheOptions::default(),
+//! #     }))
+//! #     .build();
+//! client.get("https://developer.mozilla.org/en-US/docs/Web/HTTP/Caching")
+//!     .with_extension(SuppressCacheHeaders)
+//!     .send()
+//!     .await?;
+//! # Ok(())
+//! # }
+//! ```
+//!
+//! The options used to build the cache policy can be replaced for a single request
+//! as well, for example to ignore the anti-cache directives some servers send along
+//! with the non-standard `pre-check` and `post-check` directives.
+//!
+//! ```no_run
+//! # use reqwest::Client;
+//! # use reqwest_middleware::{ClientBuilder, Result};
+//! # use http_cache_reqwest::{Cache, CacheMode, CacheOptions, CacheOptionsOverride, CACacheManager, HttpCache, HttpCacheOptions};
+//! # #[tokio::main]
+//! # async fn main() -> Result<()> {
+//! # let client = ClientBuilder::new(Client::new())
+//! #     .with(Cache(HttpCache {
+//! #         mode: CacheMode::Default,
+//! #         manager: CACacheManager::default(),
+//! #         options: HttpCacheOptions::default(),
+//! #     }))
+//! #     .build();
+//! let options = CacheOptions { ignore_cargo_cult: true, ..Default::default() };
+//! client.get("https://developer.mozilla.org/en-US/docs/Web/HTTP/Caching")
+//!     .with_extension(CacheOptionsOverride(options))
+//!     .send()
+//!     .await?;
+//! # Ok(())
+//! # }
+//! ```
+//!
+//! ## Compressed responses
+//!
+//! With reqwest's `gzip` or `deflate` features enabled, the client decodes the body
+//! after the middleware has handed the request on, removing the `content-encoding`
+//! and `content-length` headers as it does, so the decoded body is what gets cached.
+//! A body that still carries a `content-encoding` header wasn't decoded by the client,
+//! so it's cached and replayed as is along with the header, as it is without those
+//! features or with decoding disabled on the client.
+//!
+//! ## Caching redirects
+//!
+//! Redirects are followed by the reqwest client itself, after the middleware has handed
+//! the request on, so by default the cache only sees the final response. To cache
+//! permanent redirects (301 and 308) disable the client's redirect policy; the cached
+//! redirect is then returned with its `location` header for the caller to follow.
+//!
+//! ```no_run
+//! # use http_cache_reqwest::{Cache, CacheMode, CACacheManager, HttpCache, HttpCacheOptions};
+//! # use reqwest_middleware::ClientBuilder;
+//! let client = ClientBuilder::new(
+//!     reqwest::Client::builder()
+//!         .redirect(reqwest::redirect::Policy::none())
+//!         .build()
+//!         .unwrap(),
+//! )
+//! .with(Cache(HttpCache {
+//!     mode: CacheMode::Default,
+//!     manager: CACacheManager::default(),
+//!     options: HttpCacheOptions::default(),
+//! }))
+//! .build();
+//! ```
+//!
+//! ## Reading the cache status
+//!
+//! Responses carry a [`CacheStatus`] extension describing how the cache handled them.
+//!
+//! ```no_run
+//! # use reqwest::Client;
+//! # use reqwest_middleware::{ClientBuilder, Result};
+//! # use http_cache_reqwest::{Cache, CacheMode, CacheStatus, CACacheManager, HttpCache, HttpCacheOptions};
+//! # #[tokio::main]
+//! # async fn main() -> Result<()> {
+//! # let client = ClientBuilder::new(Client::new())
+//! #     .with(Cache(HttpCache {
+//! #         mode: CacheMode::Default,
+//! #         manager: CACacheManager::default(),
+//! #         options: HttpCacheOptions::default(),
+//! #     }))
+//! #     .build();
+//! let res = client.get("https://developer.mozilla.org/en-US/docs/Web/HTTP/Caching")
+//!     .send()
+//!     .await?;
+//! if let Some(status) = res.extensions().get::<CacheStatus>() {
+//!     println!("{} hit: {}", status.key, status.hit);
+//! }
+//! # Ok(())
+//! # }
+//! ```
 mod error;
 
 use anyhow::anyhow;
@@ -65,16 +175,19 @@ use http::{
     Extensions, HeaderValue, Method,
 };
 use http_cache::{
-    BoxError, HitOrMiss, Middleware, Result, XCACHE, XCACHELOOKUP,
+    BoxError, HitOrMiss, HitStatus, Middleware, Result, XCACHE, XCACHELOOKUP,
 };
 use http_cache_semantics::CachePolicy;
 use reqwest::{Request, Response, ResponseBuilderExt};
-use reqwest_middleware::{Error, Next};
+use reqwest_middleware::{ClientBuilder, Error, Next};
 use url::Url;
 
 pub use http_cache::{
-    CacheManager, CacheMode, CacheOptions, HttpCache, HttpCacheOptions,
-    HttpResponse,
+    CacheDecision, CacheDecisionReason, CacheEvent, CacheEvents, CacheManager,
+    CacheMode, CacheOptions, ClosureError, ClosureErrorPolicy,
+    DeserializeErrorPolicy, HttpCache, HttpCacheOptions,
+    HttpCacheOptionsBuilder, HttpResponse, Refresher, StaleReason,
+    UnrefreshableKey,
 };
 
 #[cfg(feature = "manager-cacache")]
@@ -83,12 +196,114 @@ pub use http_cache::CACacheManager;
 
 #[cfg(feature = "manager-moka")]
 #[cfg_attr(docsrs, doc(cfg(feature = "manager-moka")))]
-pub use http_cache::{MokaCache, MokaCacheBuilder, MokaManager};
+pub use http_cache::{
+    MokaCache, MokaCacheBuilder, MokaManager, MokaRemovalCause,
+};
+
+#[cfg(feature = "manager-memory")]
+#[cfg_attr(docsrs, doc(cfg(feature = "manager-memory")))]
+pub use http_cache::MemoryManager;
+
+#[cfg(feature = "json-body-key")]
+#[cfg_attr(docsrs, doc(cfg(feature = "json-body-key")))]
+pub use http_cache::json_fields_key;
 
 /// Wrapper for [`HttpCache`]
 #[derive(Debug)]
 pub struct Cache<T: CacheManager>(pub HttpCache<T>);
 
+impl<T: CacheManager + Clone> Cache<T> {
+    /// Pre-populates the cache by requesting each url with the client,
+    /// returning a result per url in the same order.
+    ///
+    /// The requests are handled by this cache, so with [`CacheMode::Default`]
+    /// urls that already have a fresh entry don't reach the network.
+    pub async fn warm(
+        &self,
+        client: &reqwest::Client,
+        urls: &[Url],
+    ) -> Vec<std::result::Result<(), Error>> {
+        let client = ClientBuilder::new(client.clone())
+            .with(Cache(self.0.clone()))
+            .build();
+        let mut results = Vec::with_capacity(urls.len());
+        for url in urls {
+            let result = match client.get(url.clone()).send().await {
+                Ok(res) => res.bytes().await.map(|_| ()).map_err(Error::from),
+                Err(e) => Err(e),
+            };
+            results.push(result);
+        }
+        results
+    }
+
+    /// Spawns a task on the tokio runtime that runs the [`Refresher`], revalidating
+    /// its urls by sending requests with the client through this cache, so they're
+    /// fresh when they're next requested. Abort the returned handle to stop refreshing.
+    ///
+    /// Build the refresher with [`HttpCache::refresher`] to refresh urls, or
+    /// [`HttpCache::refresher_for_keys`] to refresh keys like `GET:https://example.com/`.
+    #[cfg(feature = "refresher")]
+    #[cfg_attr(docsrs, doc(cfg(feature = "refresher")))]
+    pub fn spawn_refresher(
+        &self,
+        client: &reqwest::Client,
+        refresher: Refresher,
+    ) -> tokio::task::JoinHandle<()> {
+        let client = ClientBuilder::new(client.clone())
+            .with(Cache(self.0.clone()))
+            .build();
+        let refresher = refresher.with_sleeper(tokio::time::sleep);
+        tokio::spawn(refresher.run(move |url| {
+            let client = client.clone();
+            async move {
+                // A request max-age of 0 makes the cache revalidate
+                // the stored response, even if it's still fresh
+                let res = client
+                    .get(url)
+                    .header(CACHE_CONTROL, "max-age=0")
+                    .send()
+                    .await?;
+                res.bytes().await?;
+                Ok(())
+            }
+        }))
+    }
+}
+
+/// Request extension that leaves the `x-cache` and `x-cache-lookup` headers off the response
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct SuppressCacheHeaders;
+
+/// Request extension that replaces the [`CacheOptions`] used to build the cache policy
+/// for the response, including those set in [`HttpCacheOptions::cache_options`]
+#[derive(Debug, Clone, Copy)]
+pub struct CacheOptionsOverride(pub CacheOptions);
+
+/// Describes how the cache handled a request, inserted into the [`Response`] extensions.
+///
+/// It's reported whether or not the `x-cache` and `x-cache-lookup` headers are added,
+/// see [`HttpCacheOptions::cache_status_headers`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CacheStatus {
+    /// The cache key the request was resolved to
+    pub key: String,
+    /// Whether the response was served from the cache
+    pub hit: bool,
+    /// Whether a cached response was found for the key
+    pub lookup_hit: bool,
+}
+
+impl CacheStatus {
+    fn new(key: String, status: HitStatus) -> Self {
+        Self {
+            key,
+            hit: status.cache == Some(HitOrMiss::HIT),
+            lookup_hit: status.lookup == Some(HitOrMiss::HIT),
+        }
+    }
+}
+
 /// Implements ['Middleware'] for reqwest
 pub(crate) struct ReqwestMiddleware<'a> {
     pub req: Request,
@@ -108,17 +323,29 @@ impl Middleware for ReqwestMiddleware<'_> {
     fn overridden_cache_mode(&self) -> Option<CacheMode> {
         self.extensions.get().cloned()
     }
+    fn suppress_cache_headers(&self) -> bool {
+        self.extensions.get::<SuppressCacheHeaders>().is_some()
+    }
     fn is_method_get_head(&self) -> bool {
         self.req.method() == Method::GET || self.req.method() == Method::HEAD
     }
     fn policy(&self, response: &HttpResponse) -> Result<CachePolicy> {
-        Ok(CachePolicy::new(&self.parts()?, &response.parts()?))
+        match self.extensions.get::<CacheOptionsOverride>() {
+            Some(CacheOptionsOverride(options)) => {
+                self.policy_with_options(response, *options)
+            }
+            None => Ok(CachePolicy::new(&self.parts()?, &response.parts()?)),
+        }
     }
     fn policy_with_options(
         &self,
         response: &HttpResponse,
         options: CacheOptions,
     ) -> Result<CachePolicy> {
+        let options = self
+            .extensions
+            .get::<CacheOptionsOverride>()
+            .map_or(options, |CacheOptionsOverride(options)| *options);
         Ok(CachePolicy::new_options(
             &self.parts()?,
             &response.parts()?,
@@ -152,6 +379,9 @@ impl Middleware for ReqwestMiddleware<'_> {
     fn method(&self) -> Result<String> {
         Ok(self.req.method().as_ref().to_string())
     }
+    fn body(&self) -> Option<&[u8]> {
+        self.req.body().and_then(reqwest::Body::as_bytes)
+    }
     async fn remote_fetch(&mut self) -> Result<HttpResponse> {
         let copied_req = clone_req(&self.req)?;
         let res = match self.next.clone().run(copied_req, self.extensions).await
@@ -222,24 +452,41 @@ impl<T: CacheManager> reqwest_middleware::Middleware for Cache<T> {
             .can_cache_request(&middleware)
             .map_err(|e| Error::Middleware(anyhow!(e)))?
         {
-            let res = self.0.run(middleware).await.map_err(from_box_error)?;
-            let converted = convert_response(res)?;
+            let key = self.0.cache_key(&middleware).map_err(from_box_error)?;
+            let (res, status) = self
+                .0
+                .run_with_status(middleware)
+                .await
+                .map_err(from_box_error)?;
+            let status = CacheStatus::new(key, status);
+            let mut converted = convert_response(res)?;
+            converted.extensions_mut().insert(status);
             Ok(converted)
         } else {
             self.0
                 .run_no_cache(&mut middleware)
                 .await
                 .map_err(from_box_error)?;
+            // The key is unavailable when the cache was bypassed due to a closure error
+            let key = self.0.cache_key(&middleware).unwrap_or_default();
+            let suppress_cache_headers = middleware.suppress_cache_headers();
             let mut res = middleware
                 .next
                 .run(middleware.req, middleware.extensions)
                 .await?;
 
-            let miss =
-                HeaderValue::from_str(HitOrMiss::MISS.to_string().as_ref())
-                    .map_err(bad_header)?;
-            res.headers_mut().insert(XCACHE, miss.clone());
-            res.headers_mut().insert(XCACHELOOKUP, miss);
+            if !suppress_cache_headers {
+                let miss =
+                    HeaderValue::from_str(HitOrMiss::MISS.to_string().as_ref())
+                        .map_err(bad_header)?;
+                res.headers_mut().insert(XCACHE, miss.clone());
+                res.headers_mut().insert(XCACHELOOKUP, miss);
+            }
+            res.extensions_mut().insert(CacheStatus {
+                key,
+                hit: false,
+                lookup_hit: false,
+            });
             Ok(res)
         }
     }
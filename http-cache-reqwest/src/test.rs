@@ -94,6 +94,7 @@ async fn default_mode_with_options() -> Result<()> {
                 cache_mode_fn: None,
                 cache_bust: None,
                 cache_status_headers: true,
+                ..Default::default()
             },
         }))
         .build();
@@ -157,6 +158,7 @@ async fn custom_cache_key() -> Result<()> {
                 cache_mode_fn: None,
                 cache_bust: None,
                 cache_status_headers: true,
+                ..Default::default()
             },
         }))
         .build();
@@ -198,6 +200,7 @@ async fn custom_cache_mode_fn() -> Result<()> {
                 })),
                 cache_bust: None,
                 cache_status_headers: true,
+                ..Default::default()
             },
         }))
         .build();
@@ -239,6 +242,7 @@ async fn override_cache_mode() -> Result<()> {
                 cache_mode_fn: None,
                 cache_bust: None,
                 cache_status_headers: true,
+                ..Default::default()
             },
         }))
         .build();
@@ -280,6 +284,7 @@ async fn no_status_headers() -> Result<()> {
                 cache_mode_fn: None,
                 cache_bust: None,
                 cache_status_headers: false,
+                ..Default::default()
             },
         }))
         .build();
@@ -331,6 +336,7 @@ async fn cache_bust() -> Result<()> {
                     },
                 )),
                 cache_status_headers: true,
+                ..Default::default()
             },
         }))
         .build();
@@ -384,3 +390,2513 @@ async fn delete_after_non_get_head_method_request() -> Result<()> {
 
     Ok(())
 }
+
+#[tokio::test]
+async fn skip_cache_on_cookie() -> Result<()> {
+    let mock_server = MockServer::start().await;
+    let m = build_mock("max-age=86400", TEST_BODY, 200, 1);
+    let _mock_guard = mock_server.register_as_scoped(m).await;
+    let url = format!("{}/", &mock_server.uri());
+    let manager = MokaManager::default();
+
+    // Construct reqwest client with cookie bearing requests skipped
+    let client = ClientBuilder::new(Client::new())
+        .with(Cache(HttpCache {
+            mode: CacheMode::Default,
+            manager: manager.clone(),
+            options: HttpCacheOptions {
+                skip_cache_on_cookie: true,
+                ..Default::default()
+            },
+        }))
+        .build();
+
+    // Remote request with a cookie and a non-public response
+    client.get(url.clone()).header("cookie", "session=1").send().await?;
+
+    // Check no cache object was created
+    let data = manager.get(&format!("{}:{}", GET, &Url::parse(&url)?)).await?;
+    assert!(data.is_none());
+    Ok(())
+}
+
+#[tokio::test]
+async fn skip_cache_on_cookie_public_response() -> Result<()> {
+    let mock_server = MockServer::start().await;
+    let m = build_mock(CACHEABLE_PUBLIC, TEST_BODY, 200, 1);
+    let _mock_guard = mock_server.register_as_scoped(m).await;
+    let url = format!("{}/", &mock_server.uri());
+    let manager = MokaManager::default();
+
+    // Construct reqwest client with cookie bearing requests skipped
+    let client = ClientBuilder::new(Client::new())
+        .with(Cache(HttpCache {
+            mode: CacheMode::Default,
+            manager: manager.clone(),
+            options: HttpCacheOptions {
+                skip_cache_on_cookie: true,
+                ..Default::default()
+            },
+        }))
+        .build();
+
+    // Remote request with a cookie and an explicitly public response
+    client.get(url.clone()).header("cookie", "session=1").send().await?;
+
+    // Try to load cached object
+    let data = manager.get(&format!("{}:{}", GET, &Url::parse(&url)?)).await?;
+    assert!(data.is_some());
+    Ok(())
+}
+
+#[tokio::test]
+async fn immutable_skips_revalidation() -> Result<()> {
+    let mock_server = MockServer::start().await;
+    let m = build_mock("max-age=86400, public, immutable", TEST_BODY, 200, 1);
+    let _mock_guard = mock_server.register_as_scoped(m).await;
+    let url = format!("{}/", &mock_server.uri());
+    let manager = MokaManager::default();
+
+    // Construct reqwest client with cache defaults
+    let client = ClientBuilder::new(Client::new())
+        .with(Cache(HttpCache {
+            mode: CacheMode::Default,
+            manager: manager.clone(),
+            options: HttpCacheOptions::default(),
+        }))
+        .build();
+
+    // Cold pass to load cache
+    client.get(url.clone()).send().await?;
+
+    // A no-cache request would normally revalidate, but immutable wins
+    let res =
+        client.get(url).header("cache-control", "no-cache").send().await?;
+    assert_eq!(res.headers().get(XCACHE).unwrap(), "HIT");
+    assert_eq!(res.bytes().await?, TEST_BODY);
+    Ok(())
+}
+
+#[tokio::test]
+async fn cache_events() -> Result<()> {
+    use futures::{FutureExt, StreamExt};
+
+    let mock_server = MockServer::start().await;
+    let m = build_mock(CACHEABLE_PUBLIC, TEST_BODY, 200, 1);
+    let _mock_guard = mock_server.register_as_scoped(m).await;
+    let url = format!("{}/", &mock_server.uri());
+    let key = format!("{}:{}", GET, &Url::parse(&url)?);
+    let manager = MokaManager::default();
+    let cache = HttpCache {
+        mode: CacheMode::Default,
+        manager: manager.clone(),
+        options: HttpCacheOptions {
+            cache_events: Some(CacheEvents::default()),
+            ..Default::default()
+        },
+    };
+    let mut events = cache.subscribe().unwrap();
+
+    // Construct reqwest client with the event channel enabled
+    let client = ClientBuilder::new(Client::new()).with(Cache(cache)).build();
+
+    // Cold pass to load cache
+    client.get(url.clone()).send().await?;
+    assert_eq!(events.next().await, Some(CacheEvent::Miss(key.clone())));
+    assert_eq!(events.next().await, Some(CacheEvent::Store(key.clone())));
+
+    // Hot pass to make sure the hit is published
+    client.get(url.clone()).send().await?;
+    assert_eq!(events.next().await, Some(CacheEvent::Hit(key.clone())));
+
+    // Post request to make sure the eviction is published
+    client.post(url.clone()).send().await?;
+    assert_eq!(events.next().await, Some(CacheEvent::Evict(key)));
+
+    // Nothing is evicted once there's no cached response
+    client.post(url).send().await?;
+    assert!(events.next().now_or_never().is_none());
+    Ok(())
+}
+
+#[cfg(feature = "json-body-key")]
+#[tokio::test]
+async fn json_body_cache_key() -> Result<()> {
+    use wiremock::matchers::body_string_contains;
+
+    let mock_server = MockServer::start().await;
+    let first = Mock::given(method("POST"))
+        .and(body_string_contains(r#""query":"a""#))
+        .respond_with(ResponseTemplate::new(200).set_body_bytes(TEST_BODY))
+        .expect(1);
+    let second = Mock::given(method("POST"))
+        .and(body_string_contains(r#""query":"b""#))
+        .respond_with(ResponseTemplate::new(200).set_body_bytes(TEST_BODY))
+        .expect(1);
+    let _first_guard = mock_server.register_as_scoped(first).await;
+    let _second_guard = mock_server.register_as_scoped(second).await;
+    let url = format!("{}/graphql", &mock_server.uri());
+    let manager = MokaManager::default();
+
+    // Construct reqwest client keyed on the query field of the body
+    let client = ClientBuilder::new(Client::new())
+        .with(Cache(HttpCache {
+            mode: CacheMode::IgnoreRules,
+            manager: manager.clone(),
+            options: HttpCacheOptions {
+                cache_key_body: Some(json_fields_key(&["/query"])),
+                ..Default::default()
+            },
+        }))
+        .build();
+
+    // Cold pass to load cache
+    client
+        .post(url.clone())
+        .body(r#"{"query":"a","requestId":"1"}"#)
+        .send()
+        .await?;
+
+    // Only the request id differs, so this should be a hit
+    let res = client
+        .post(url.clone())
+        .body(r#"{"query":"a","requestId":"2"}"#)
+        .send()
+        .await?;
+    assert_eq!(res.headers().get(XCACHE).unwrap(), "HIT");
+
+    // A different query should go to the network
+    let res = client
+        .post(url)
+        .body(r#"{"query":"b","requestId":"3"}"#)
+        .send()
+        .await?;
+    assert_eq!(res.headers().get(XCACHE).unwrap(), "MISS");
+    Ok(())
+}
+
+#[tokio::test]
+async fn cached_response_keeps_final_url() -> Result<()> {
+    use wiremock::matchers::path;
+
+    let mock_server = MockServer::start().await;
+    let redirect = Mock::given(method(GET))
+        .and(path("/old"))
+        .respond_with(
+            ResponseTemplate::new(301)
+                .insert_header("location", "/new")
+                .insert_header("cache-control", "no-store"),
+        )
+        .expect(1);
+    let target = Mock::given(method(GET))
+        .and(path("/new"))
+        .respond_with(
+            ResponseTemplate::new(200)
+                .insert_header("cache-control", CACHEABLE_PUBLIC)
+                .set_body_bytes(TEST_BODY),
+        )
+        .expect(1);
+    let _redirect_guard = mock_server.register_as_scoped(redirect).await;
+    let _target_guard = mock_server.register_as_scoped(target).await;
+    let url = format!("{}/old", &mock_server.uri());
+    let final_url = Url::parse(&format!("{}/new", &mock_server.uri()))?;
+    let manager = MokaManager::default();
+
+    // Construct reqwest client with cache defaults
+    let client = ClientBuilder::new(Client::new())
+        .with(Cache(HttpCache {
+            mode: CacheMode::Default,
+            manager: manager.clone(),
+            options: HttpCacheOptions::default(),
+        }))
+        .build();
+
+    // Cold pass follows the redirect and caches the final response
+    let res = client.get(url.clone()).send().await?;
+    assert_eq!(res.url(), &final_url);
+
+    // Hot pass should report the final url rather than the requested one
+    let res = client.get(url).send().await?;
+    assert_eq!(res.headers().get(XCACHE).unwrap(), "HIT");
+    assert_eq!(res.url(), &final_url);
+    Ok(())
+}
+
+#[tokio::test]
+async fn stored_header_allowlist() -> Result<()> {
+    let mock_server = MockServer::start().await;
+    let m = Mock::given(method(GET))
+        .respond_with(
+            ResponseTemplate::new(200)
+                .insert_header("cache-control", CACHEABLE_PUBLIC)
+                .insert_header("content-type", "text/plain")
+                .insert_header("x-internal-token", "secret")
+                .set_body_bytes(TEST_BODY),
+        )
+        .expect(1);
+    let _mock_guard = mock_server.register_as_scoped(m).await;
+    let url = format!("{}/", &mock_server.uri());
+    let manager = MokaManager::default();
+
+    // Construct reqwest client that only stores a few headers
+    let client = ClientBuilder::new(Client::new())
+        .with(Cache(HttpCache {
+            mode: CacheMode::Default,
+            manager: manager.clone(),
+            options: HttpCacheOptions {
+                stored_header_allowlist: Some(vec![
+                    http::header::CACHE_CONTROL,
+                    http::header::CONTENT_TYPE,
+                ]),
+                ..Default::default()
+            },
+        }))
+        .build();
+
+    // Cold pass still returns every header
+    let res = client.get(url.clone()).send().await?;
+    assert!(res.headers().get("x-internal-token").is_some());
+
+    // Only the allowlisted headers were stored
+    let (stored, _) =
+        manager.get(&format!("{}:{}", GET, &Url::parse(&url)?)).await?.unwrap();
+    let mut names: Vec<&str> =
+        stored.headers.keys().map(String::as_str).collect();
+    names.sort_unstable();
+    assert_eq!(names, vec!["cache-control", "content-type"]);
+
+    // Hot pass is served without the dropped header
+    let res = client.get(url).send().await?;
+    assert_eq!(res.headers().get(XCACHE).unwrap(), "HIT");
+    assert!(res.headers().get("x-internal-token").is_none());
+    Ok(())
+}
+
+#[tokio::test]
+async fn single_flight() -> Result<()> {
+    let mock_server = MockServer::start().await;
+    let m = Mock::given(method(GET))
+        .respond_with(
+            ResponseTemplate::new(200)
+                .insert_header("cache-control", CACHEABLE_PUBLIC)
+                .set_body_bytes(TEST_BODY)
+                .set_delay(std::time::Duration::from_millis(200)),
+        )
+        .expect(1);
+    let _mock_guard = mock_server.register_as_scoped(m).await;
+    let url = format!("{}/", &mock_server.uri());
+
+    // Construct reqwest client with single flight enabled
+    let client = ClientBuilder::new(Client::new())
+        .with(Cache(HttpCache {
+            mode: CacheMode::Default,
+            manager: MokaManager::default(),
+            options: HttpCacheOptions {
+                single_flight: true,
+                ..Default::default()
+            },
+        }))
+        .build();
+
+    // Concurrent misses share one upstream request
+    let (first, second) = tokio::join!(
+        client.get(url.clone()).send(),
+        client.get(url.clone()).send()
+    );
+    assert_eq!(first?.bytes().await?, TEST_BODY);
+    assert_eq!(second?.bytes().await?, TEST_BODY);
+    Ok(())
+}
+
+#[tokio::test]
+async fn single_flight_not_stored() -> Result<()> {
+    let mock_server = MockServer::start().await;
+    let m = Mock::given(method(GET))
+        .respond_with(
+            ResponseTemplate::new(200)
+                .insert_header("cache-control", "no-store")
+                .set_body_bytes(TEST_BODY)
+                .set_delay(std::time::Duration::from_millis(200)),
+        )
+        .expect(2);
+    let _mock_guard = mock_server.register_as_scoped(m).await;
+    let url = format!("{}/", &mock_server.uri());
+
+    let client = ClientBuilder::new(Client::new())
+        .with(Cache(HttpCache {
+            mode: CacheMode::Default,
+            manager: MokaManager::default(),
+            options: HttpCacheOptions {
+                single_flight: true,
+                ..Default::default()
+            },
+        }))
+        .build();
+
+    // A response that isn't stored isn't shared, each request fetches its own
+    let (first, second) = tokio::join!(
+        client.get(url.clone()).send(),
+        client.get(url.clone()).send()
+    );
+    assert_eq!(first?.bytes().await?, TEST_BODY);
+    assert_eq!(second?.bytes().await?, TEST_BODY);
+    Ok(())
+}
+
+#[tokio::test]
+async fn single_flight_ignore_rules() -> Result<()> {
+    let mock_server = MockServer::start().await;
+    let m = Mock::given(method(GET))
+        .respond_with(
+            ResponseTemplate::new(200)
+                .insert_header("cache-control", "no-store")
+                .set_body_bytes(TEST_BODY)
+                .set_delay(std::time::Duration::from_millis(200)),
+        )
+        .expect(1);
+    let _mock_guard = mock_server.register_as_scoped(m).await;
+    let url = format!("{}/", &mock_server.uri());
+
+    let client = ClientBuilder::new(Client::new())
+        .with(Cache(HttpCache {
+            mode: CacheMode::IgnoreRules,
+            manager: MokaManager::default(),
+            options: HttpCacheOptions {
+                single_flight: true,
+                ..Default::default()
+            },
+        }))
+        .build();
+
+    // The response is stored despite its policy, so it's shared
+    let (first, second) = tokio::join!(
+        client.get(url.clone()).send(),
+        client.get(url.clone()).send()
+    );
+    assert_eq!(first?.bytes().await?, TEST_BODY);
+    assert_eq!(second?.bytes().await?, TEST_BODY);
+    Ok(())
+}
+
+#[tokio::test]
+async fn single_flight_separate_caches() -> Result<()> {
+    let mock_server = MockServer::start().await;
+    let m = Mock::given(method(GET))
+        .respond_with(
+            ResponseTemplate::new(200)
+                .insert_header("cache-control", CACHEABLE_PUBLIC)
+                .set_body_bytes(TEST_BODY)
+                .set_delay(std::time::Duration::from_millis(200)),
+        )
+        .expect(2);
+    let _mock_guard = mock_server.register_as_scoped(m).await;
+    let url = format!("{}/", &mock_server.uri());
+
+    let client = || {
+        ClientBuilder::new(Client::new())
+            .with(Cache(HttpCache {
+                mode: CacheMode::Default,
+                manager: MokaManager::default(),
+                options: HttpCacheOptions {
+                    single_flight: true,
+                    ..Default::default()
+                },
+            }))
+            .build()
+    };
+    let (first_client, second_client) = (client(), client());
+
+    // Unrelated caches don't wait on each other's fetches
+    let (first, second) = tokio::join!(
+        first_client.get(url.clone()).send(),
+        second_client.get(url.clone()).send()
+    );
+    assert_eq!(first?.bytes().await?, TEST_BODY);
+    assert_eq!(second?.bytes().await?, TEST_BODY);
+    Ok(())
+}
+
+#[tokio::test]
+async fn via_pseudonym() -> Result<()> {
+    let mock_server = MockServer::start().await;
+    let m = Mock::given(method(GET))
+        .respond_with(
+            ResponseTemplate::new(200)
+                .insert_header("cache-control", CACHEABLE_PUBLIC)
+                .insert_header("via", "1.1 upstream")
+                .set_body_bytes(TEST_BODY),
+        )
+        .expect(1);
+    let _mock_guard = mock_server.register_as_scoped(m).await;
+    let url = format!("{}/", &mock_server.uri());
+
+    // Construct reqwest client with a via pseudonym
+    let client = ClientBuilder::new(Client::new())
+        .with(Cache(HttpCache {
+            mode: CacheMode::Default,
+            manager: MokaManager::default(),
+            options: HttpCacheOptions {
+                via_pseudonym: Some("http-cache".to_string()),
+                ..Default::default()
+            },
+        }))
+        .build();
+
+    // Cold pass appends to the upstream via header
+    let res = client.get(url.clone()).send().await?;
+    assert_eq!(
+        res.headers().get("via").unwrap(),
+        "1.1 upstream, 1.1 http-cache"
+    );
+
+    // Hot pass appends it only once
+    let res = client.get(url).send().await?;
+    assert_eq!(res.headers().get(XCACHE).unwrap(), "HIT");
+    assert_eq!(
+        res.headers().get("via").unwrap(),
+        "1.1 upstream, 1.1 http-cache"
+    );
+    Ok(())
+}
+
+#[tokio::test]
+async fn cache_status_extension() -> Result<()> {
+    let mock_server = MockServer::start().await;
+    let m = build_mock(CACHEABLE_PUBLIC, TEST_BODY, 200, 1);
+    let _mock_guard = mock_server.register_as_scoped(m).await;
+    let url = format!("{}/", &mock_server.uri());
+
+    // Construct reqwest client with cache defaults
+    let client = ClientBuilder::new(Client::new())
+        .with(Cache(HttpCache {
+            mode: CacheMode::Default,
+            manager: MokaManager::default(),
+            options: HttpCacheOptions::default(),
+        }))
+        .build();
+    let key = format!("{}:{}", GET, &Url::parse(&url)?);
+
+    // Cold pass to load cache
+    let res = client.get(url.clone()).send().await?;
+    assert_eq!(
+        res.extensions().get::<crate::CacheStatus>(),
+        Some(&crate::CacheStatus {
+            key: key.clone(),
+            hit: false,
+            lookup_hit: false
+        })
+    );
+
+    // Hot pass reports the hit
+    let res = client.get(url).send().await?;
+    assert_eq!(
+        res.extensions().get::<crate::CacheStatus>(),
+        Some(&crate::CacheStatus { key, hit: true, lookup_hit: true })
+    );
+    Ok(())
+}
+
+#[tokio::test]
+async fn cache_status_extension_without_headers() -> Result<()> {
+    let mock_server = MockServer::start().await;
+    let m = build_mock(CACHEABLE_PUBLIC, TEST_BODY, 200, 1);
+    let _mock_guard = mock_server.register_as_scoped(m).await;
+    let url = format!("{}/", &mock_server.uri());
+
+    // Construct reqwest client without the cache status headers
+    let client = ClientBuilder::new(Client::new())
+        .with(Cache(HttpCache {
+            mode: CacheMode::Default,
+            manager: MokaManager::default(),
+            options: HttpCacheOptions {
+                cache_status_headers: false,
+                ..Default::default()
+            },
+        }))
+        .build();
+    let key = format!("{}:{}", GET, &Url::parse(&url)?);
+
+    // Cold pass to load cache
+    client.get(url.clone()).send().await?;
+
+    // Hot pass still reports the hit
+    let res = client.get(url).send().await?;
+    assert!(res.headers().get(XCACHE).is_none());
+    assert_eq!(
+        res.extensions().get::<crate::CacheStatus>(),
+        Some(&crate::CacheStatus { key, hit: true, lookup_hit: true })
+    );
+    Ok(())
+}
+
+#[tokio::test]
+async fn stale_while_revalidate_mode() -> Result<()> {
+    let mock_server = MockServer::start().await;
+    let first = Mock::given(method(GET))
+        .respond_with(
+            ResponseTemplate::new(200)
+                .insert_header("cache-control", "public, max-age=0")
+                .set_body_bytes(TEST_BODY),
+        )
+        .up_to_n_times(1)
+        .expect(1);
+    let refreshed = Mock::given(method(GET))
+        .respond_with(
+            ResponseTemplate::new(200)
+                .insert_header("cache-control", "public, max-age=0")
+                .set_body_bytes(b"refreshed"),
+        )
+        .expect(1);
+    let _first_guard = mock_server.register_as_scoped(first).await;
+    let _refreshed_guard = mock_server.register_as_scoped(refreshed).await;
+    let url = format!("{}/", &mock_server.uri());
+    let manager = MokaManager::default();
+
+    // Construct reqwest client with stale while revalidate mode
+    let client = ClientBuilder::new(Client::new())
+        .with(Cache(HttpCache {
+            mode: CacheMode::StaleWhileRevalidate,
+            manager: manager.clone(),
+            options: HttpCacheOptions::default(),
+        }))
+        .build();
+
+    // Cold pass to load cache
+    client.get(url.clone()).send().await?;
+
+    // Hot pass serves the stale response
+    let res = client.get(url.clone()).send().await?;
+    assert_eq!(res.headers().get(XCACHE).unwrap(), "HIT");
+    assert!(res.headers().get("warning").unwrap().to_str()?.starts_with("110"));
+    assert_eq!(res.bytes().await?, TEST_BODY);
+
+    // The cache was refreshed in the meantime
+    let (stored, _) =
+        manager.get(&format!("{}:{}", GET, &Url::parse(&url)?)).await?.unwrap();
+    assert_eq!(stored.body, b"refreshed");
+    Ok(())
+}
+
+#[tokio::test]
+async fn stale_while_revalidate_refresh_failed() -> Result<()> {
+    use futures::StreamExt;
+    use std::sync::Mutex;
+
+    // Fails every revalidation, as if the backend answering them were down
+    #[derive(Debug, Clone, Default)]
+    struct FailingManager {
+        inner: MokaManager,
+    }
+
+    #[async_trait::async_trait]
+    impl CacheManager for FailingManager {
+        async fn get(
+            &self,
+            cache_key: &str,
+        ) -> Result<Option<(HttpResponse, http_cache_semantics::CachePolicy)>>
+        {
+            self.inner.get(cache_key).await
+        }
+
+        async fn put(
+            &self,
+            cache_key: String,
+            res: HttpResponse,
+            policy: http_cache_semantics::CachePolicy,
+        ) -> Result<HttpResponse> {
+            self.inner.put(cache_key, res, policy).await
+        }
+
+        async fn revalidate(
+            &self,
+            _cache_key: &str,
+            _parts: &http::request::Parts,
+        ) -> Result<Option<RevalidationOutcome>> {
+            Err("backend down".into())
+        }
+
+        async fn delete(&self, cache_key: &str) -> Result<()> {
+            self.inner.delete(cache_key).await
+        }
+    }
+
+    let mock_server = MockServer::start().await;
+    let m = build_mock("public, max-age=0", TEST_BODY, 200, 1);
+    let _mock_guard = mock_server.register_as_scoped(m).await;
+    let url = format!("{}/", &mock_server.uri());
+    let key = format!("{}:{}", GET, &Url::parse(&url)?);
+    let served = Arc::new(Mutex::new(Vec::new()));
+    let recorded = served.clone();
+    let cache = HttpCache {
+        mode: CacheMode::StaleWhileRevalidate,
+        manager: FailingManager::default(),
+        options: HttpCacheOptions {
+            cache_events: Some(CacheEvents::default()),
+            on_stale_served: Some(Arc::new(
+                move |_: &Url, reason: StaleReason| {
+                    recorded.lock().unwrap().push(reason)
+                },
+            )),
+            ..Default::default()
+        },
+    };
+    let mut events = cache.subscribe().unwrap();
+    let client = ClientBuilder::new(Client::new()).with(Cache(cache)).build();
+
+    // Cold pass to load cache
+    client.get(url.clone()).send().await?;
+    assert_eq!(events.next().await, Some(CacheEvent::Miss(key.clone())));
+    assert_eq!(events.next().await, Some(CacheEvent::Store(key.clone())));
+
+    // The refresh fails, the stale response is still served and the failure reported
+    let res = client.get(url).send().await?;
+    assert!(res.headers().get("warning").unwrap().to_str()?.starts_with("110"));
+    assert_eq!(res.bytes().await?, TEST_BODY);
+    assert_eq!(events.next().await, Some(CacheEvent::RefreshFailed(key)));
+    assert_eq!(
+        *served.lock().unwrap(),
+        vec![StaleReason::StaleWhileRevalidate]
+    );
+    Ok(())
+}
+
+#[tokio::test]
+async fn fallible_cache_key() -> Result<()> {
+    let mock_server = MockServer::start().await;
+    let m = build_mock(CACHEABLE_PUBLIC, TEST_BODY, 200, 2);
+    let _mock_guard = mock_server.register_as_scoped(m).await;
+    let url = format!("{}/", &mock_server.uri());
+    let failing_key: TryCacheKey = Arc::new(|req: &http::request::Parts| {
+        Err(format!("no key for {}", req.uri).into())
+    });
+
+    // By default the closure error fails the request
+    let client = ClientBuilder::new(Client::new())
+        .with(Cache(HttpCache {
+            mode: CacheMode::Default,
+            manager: MokaManager::default(),
+            options: HttpCacheOptions {
+                try_cache_key: Some(failing_key.clone()),
+                ..Default::default()
+            },
+        }))
+        .build();
+    let err = client.get(url.clone()).send().await.unwrap_err();
+    assert!(err.to_string().contains("Cache option closure failed"));
+
+    // When bypassing, requests skip the cache and go to the origin
+    let client = ClientBuilder::new(Client::new())
+        .with(Cache(HttpCache {
+            mode: CacheMode::Default,
+            manager: MokaManager::default(),
+            options: HttpCacheOptions {
+                try_cache_key: Some(failing_key),
+                closure_error_policy: ClosureErrorPolicy::Bypass,
+                ..Default::default()
+            },
+        }))
+        .build();
+    for _ in 0..2 {
+        let res = client.get(url.clone()).send().await?;
+        assert_eq!(res.bytes().await?, TEST_BODY);
+    }
+    Ok(())
+}
+
+#[tokio::test]
+async fn request_max_age() -> Result<()> {
+    for cache_control in [CACHEABLE_PUBLIC, "max-age=86400, public, immutable"]
+    {
+        let mock_server = MockServer::start().await;
+        let m = build_mock(cache_control, TEST_BODY, 200, 2);
+        let _mock_guard = mock_server.register_as_scoped(m).await;
+        let url = format!("{}/", &mock_server.uri());
+
+        // Construct reqwest client with cache defaults
+        let client = ClientBuilder::new(Client::new())
+            .with(Cache(HttpCache {
+                mode: CacheMode::Default,
+                manager: MokaManager::default(),
+                options: HttpCacheOptions::default(),
+            }))
+            .build();
+
+        // Cold pass to load cache
+        client.get(url.clone()).send().await?;
+
+        // The stored response is young enough for a max-age of 10 seconds
+        let res = client
+            .get(url.clone())
+            .header("cache-control", "max-age=10")
+            .send()
+            .await?;
+        assert_eq!(res.headers().get(XCACHE).unwrap(), "HIT");
+
+        // A max-age of 0 forces revalidation of the fresh response
+        let res =
+            client.get(url).header("cache-control", "max-age=0").send().await?;
+        assert_eq!(res.headers().get(XCACHE).unwrap(), "MISS");
+        assert_eq!(res.headers().get(XCACHELOOKUP).unwrap(), "HIT");
+    }
+    Ok(())
+}
+
+#[tokio::test]
+async fn cache_decisions() -> Result<()> {
+    use std::sync::Mutex;
+    use wiremock::matchers::path;
+
+    let mock_server = MockServer::start().await;
+    for (route, cache_control) in [
+        ("/no-store", "no-store"),
+        ("/private", "private, max-age=86400"),
+        ("/public", CACHEABLE_PUBLIC),
+    ] {
+        mock_server
+            .register(
+                Mock::given(method(GET)).and(path(route)).respond_with(
+                    ResponseTemplate::new(200)
+                        .insert_header("cache-control", cache_control)
+                        .set_body_bytes(TEST_BODY),
+                ),
+            )
+            .await;
+    }
+    let decisions = Arc::new(Mutex::new(Vec::new()));
+    let recorded = decisions.clone();
+
+    // Construct reqwest client as a shared cache recording cache decisions
+    let client = ClientBuilder::new(Client::new())
+        .with(Cache(HttpCache {
+            mode: CacheMode::Default,
+            manager: MokaManager::default(),
+            options: HttpCacheOptions {
+                shared: true,
+                on_cache_decision: Some(Arc::new(
+                    move |decision: &CacheDecision| {
+                        recorded.lock().unwrap().push(decision.clone())
+                    },
+                )),
+                ..Default::default()
+            },
+        }))
+        .build();
+
+    for route in ["/no-store", "/private", "/public"] {
+        client.get(format!("{}{}", &mock_server.uri(), route)).send().await?;
+    }
+
+    let decisions = decisions.lock().unwrap();
+    assert_eq!(decisions.len(), 3);
+    assert_eq!(decisions[0].reason, CacheDecisionReason::NoStore);
+    assert_eq!(decisions[0].directives, vec!["no-store"]);
+    assert!(!decisions[0].cached);
+    assert_eq!(decisions[1].reason, CacheDecisionReason::Private);
+    assert!(!decisions[1].is_storable);
+    assert!(!decisions[1].cached);
+    assert_eq!(decisions[2].reason, CacheDecisionReason::Storable);
+    assert_eq!(decisions[2].mode, CacheMode::Default);
+    assert_eq!(decisions[2].status, 200);
+    assert!(decisions[2].cached);
+    Ok(())
+}
+
+#[tokio::test]
+async fn cache_decision_on_replacement() -> Result<()> {
+    use std::sync::Mutex;
+
+    let mock_server = MockServer::start().await;
+    let m = Mock::given(method(GET))
+        .respond_with(
+            ResponseTemplate::new(200)
+                .insert_header("cache-control", "public, max-age=0")
+                .set_body_bytes(TEST_BODY),
+        )
+        .expect(2);
+    let _mock_guard = mock_server.register_as_scoped(m).await;
+    let url = format!("{}/", &mock_server.uri());
+    let decisions = Arc::new(Mutex::new(Vec::new()));
+    let recorded = decisions.clone();
+
+    let client = ClientBuilder::new(Client::new())
+        .with(Cache(HttpCache {
+            mode: CacheMode::Default,
+            manager: MokaManager::default(),
+            options: HttpCacheOptions {
+                on_cache_decision: Some(Arc::new(
+                    move |decision: &CacheDecision| {
+                        recorded.lock().unwrap().push(decision.clone())
+                    },
+                )),
+                ..Default::default()
+            },
+        }))
+        .build();
+
+    // The stale response is revalidated and replaced by the new one,
+    // which is decided on like any other response from the origin
+    client.get(url.clone()).send().await?;
+    client.get(url).send().await?;
+
+    let decisions = decisions.lock().unwrap();
+    assert_eq!(decisions.len(), 2);
+    assert!(decisions.iter().all(|decision| decision.cached));
+    Ok(())
+}
+
+#[tokio::test]
+async fn age_header_on_hit() -> Result<()> {
+    let mock_server = MockServer::start().await;
+    let m = Mock::given(method(GET))
+        .respond_with(
+            ResponseTemplate::new(200)
+                .insert_header("cache-control", "public, max-age=200")
+                .insert_header("age", "100")
+                .set_body_bytes(TEST_BODY),
+        )
+        .expect(1);
+    let _mock_guard = mock_server.register_as_scoped(m).await;
+    let url = format!("{}/", &mock_server.uri());
+
+    // Construct reqwest client with cache defaults
+    let client = ClientBuilder::new(Client::new())
+        .with(Cache(HttpCache {
+            mode: CacheMode::Default,
+            manager: MokaManager::default(),
+            options: HttpCacheOptions::default(),
+        }))
+        .build();
+
+    // Cold pass to load cache
+    client.get(url.clone()).send().await?;
+
+    // Hot pass carries the upstream age plus the time spent in this cache
+    let res = client.get(url).send().await?;
+    assert_eq!(res.headers().get(XCACHE).unwrap(), "HIT");
+    let age: u64 = res.headers().get("age").unwrap().to_str()?.parse()?;
+    assert!((100..=101).contains(&age));
+    Ok(())
+}
+
+#[tokio::test]
+async fn respect_request_authorization() -> Result<()> {
+    for (respect, cache_control, should_cache) in [
+        (false, "max-age=86400", true),
+        (true, "max-age=86400", false),
+        (true, "max-age=86400, must-revalidate", true),
+        (true, CACHEABLE_PUBLIC, true),
+    ] {
+        let mock_server = MockServer::start().await;
+        let m = build_mock(cache_control, TEST_BODY, 200, 1);
+        let _mock_guard = mock_server.register_as_scoped(m).await;
+        let url = format!("{}/", &mock_server.uri());
+        let manager = MokaManager::default();
+
+        // Construct reqwest client as a private cache, which the policy
+        // would otherwise allow to store authenticated responses
+        let client = ClientBuilder::new(Client::new())
+            .with(Cache(HttpCache {
+                mode: CacheMode::Default,
+                manager: manager.clone(),
+                options: HttpCacheOptions {
+                    cache_options: Some(CacheOptions {
+                        shared: false,
+                        ..Default::default()
+                    }),
+                    respect_request_authorization: respect,
+                    ..Default::default()
+                },
+            }))
+            .build();
+
+        client
+            .get(url.clone())
+            .header("authorization", "Bearer token")
+            .send()
+            .await?;
+
+        let data =
+            manager.get(&format!("{}:{}", GET, &Url::parse(&url)?)).await?;
+        assert_eq!(data.is_some(), should_cache, "{cache_control}");
+    }
+    Ok(())
+}
+
+#[tokio::test]
+async fn cached_permanent_redirect() -> Result<()> {
+    let mock_server = MockServer::start().await;
+    let m = Mock::given(method(GET))
+        .respond_with(
+            ResponseTemplate::new(308)
+                .insert_header("cache-control", CACHEABLE_PUBLIC)
+                .insert_header("location", "/moved"),
+        )
+        .expect(1);
+    let _mock_guard = mock_server.register_as_scoped(m).await;
+    let url = format!("{}/", &mock_server.uri());
+
+    // Construct reqwest client that doesn't follow redirects itself
+    let client = ClientBuilder::new(
+        Client::builder()
+            .redirect(reqwest::redirect::Policy::none())
+            .build()?,
+    )
+    .with(Cache(HttpCache {
+        mode: CacheMode::Default,
+        manager: MokaManager::default(),
+        options: HttpCacheOptions::default(),
+    }))
+    .build();
+
+    // Cold pass to load cache
+    let res = client.get(url.clone()).send().await?;
+    assert_eq!(res.status(), 308);
+    assert_eq!(res.headers().get(XCACHE).unwrap(), "MISS");
+
+    // Hot pass returns the cached redirect
+    let res = client.get(url).send().await?;
+    assert_eq!(res.status(), 308);
+    assert_eq!(res.headers().get(XCACHE).unwrap(), "HIT");
+    assert_eq!(res.headers().get("location").unwrap(), "/moved");
+    Ok(())
+}
+
+#[tokio::test]
+async fn on_stale_served() -> Result<()> {
+    use std::sync::Mutex;
+
+    let mock_server = MockServer::start().await;
+    let m = build_mock("public, max-age=0, must-revalidate", TEST_BODY, 200, 1)
+        .up_to_n_times(1);
+    let m_500 = build_mock(CACHEABLE_PUBLIC, b"", 500, 1);
+    let _mock_guard = mock_server.register_as_scoped(m).await;
+    let _mock_500_guard = mock_server.register_as_scoped(m_500).await;
+    let url = format!("{}/", &mock_server.uri());
+    let served = Arc::new(Mutex::new(Vec::new()));
+    let recorded = served.clone();
+
+    // Construct reqwest client recording stale responses
+    let client = ClientBuilder::new(Client::new())
+        .with(Cache(HttpCache {
+            mode: CacheMode::Default,
+            manager: MokaManager::default(),
+            options: HttpCacheOptions {
+                on_stale_served: Some(Arc::new(
+                    move |url: &Url, reason: StaleReason| {
+                        recorded.lock().unwrap().push((url.clone(), reason))
+                    },
+                )),
+                ..Default::default()
+            },
+        }))
+        .build();
+
+    // Cold pass to load cache
+    client.get(url.clone()).send().await?;
+    assert!(served.lock().unwrap().is_empty());
+
+    // The origin fails while revalidating, so the stale response is served
+    let res = client.get(url.clone()).send().await?;
+    assert_eq!(res.bytes().await?, TEST_BODY);
+    let served = served.lock().unwrap();
+    assert_eq!(
+        *served,
+        vec![(Url::parse(&url)?, StaleReason::RevalidationFailed)]
+    );
+    Ok(())
+}
+
+#[tokio::test]
+async fn vary_star_not_cached() -> Result<()> {
+    let mock_server = MockServer::start().await;
+    let m = Mock::given(method(GET))
+        .respond_with(
+            ResponseTemplate::new(200)
+                .insert_header("cache-control", CACHEABLE_PUBLIC)
+                .insert_header("vary", "*")
+                .set_body_bytes(TEST_BODY),
+        )
+        .expect(2);
+    let _mock_guard = mock_server.register_as_scoped(m).await;
+    let url = format!("{}/", &mock_server.uri());
+    let manager = MokaManager::default();
+
+    // Construct reqwest client with cache defaults
+    let client = ClientBuilder::new(Client::new())
+        .with(Cache(HttpCache {
+            mode: CacheMode::Default,
+            manager: manager.clone(),
+            options: HttpCacheOptions::default(),
+        }))
+        .build();
+
+    // The response is returned to the caller but never stored
+    for _ in 0..2 {
+        let res = client.get(url.clone()).send().await?;
+        assert_eq!(res.bytes().await?, TEST_BODY);
+        let data =
+            manager.get(&format!("{}:{}", GET, &Url::parse(&url)?)).await?;
+        assert!(data.is_none());
+    }
+    Ok(())
+}
+
+#[tokio::test]
+async fn content_length_on_hit() -> Result<()> {
+    let mock_server = MockServer::start().await;
+    let m = build_mock(CACHEABLE_PUBLIC, TEST_BODY, 200, 1);
+    let _mock_guard = mock_server.register_as_scoped(m).await;
+    let url = format!("{}/", &mock_server.uri());
+    let manager = MokaManager::default();
+
+    // Construct reqwest client with cache defaults
+    let client = ClientBuilder::new(Client::new())
+        .with(Cache(HttpCache {
+            mode: CacheMode::Default,
+            manager: manager.clone(),
+            options: HttpCacheOptions::default(),
+        }))
+        .build();
+
+    // Cold pass to load cache
+    client.get(url.clone()).send().await?;
+
+    // Store a content-length that no longer matches the body
+    let cache_key = format!("{}:{}", GET, &Url::parse(&url)?);
+    let (mut cached, policy) = manager.get(&cache_key).await?.unwrap();
+    cached.headers.insert("content-length".to_string(), "100".to_string());
+    manager.put(cache_key, cached, policy).await?;
+
+    // Hot pass to make sure the served length matches the body
+    let res = client.get(url).send().await?;
+    assert_eq!(res.headers().get("content-length").unwrap(), "4");
+    assert_eq!(res.bytes().await?, TEST_BODY);
+    Ok(())
+}
+
+#[tokio::test]
+async fn suppress_cache_headers() -> Result<()> {
+    let mock_server = MockServer::start().await;
+    let m = build_mock(CACHEABLE_PUBLIC, TEST_BODY, 200, 1);
+    let _mock_guard = mock_server.register_as_scoped(m).await;
+    let url = format!("{}/", &mock_server.uri());
+
+    // Construct reqwest client with cache defaults
+    let client = ClientBuilder::new(Client::new())
+        .with(Cache(HttpCache {
+            mode: CacheMode::Default,
+            manager: MokaManager::default(),
+            options: HttpCacheOptions::default(),
+        }))
+        .build();
+
+    // Cold pass to load cache
+    let res = client
+        .get(url.clone())
+        .with_extension(crate::SuppressCacheHeaders)
+        .send()
+        .await?;
+    assert!(res.headers().get(XCACHE).is_none());
+    assert!(res.headers().get(XCACHELOOKUP).is_none());
+
+    // Hot pass without the extension still reports the hit
+    let res = client.get(url.clone()).send().await?;
+    assert_eq!(res.headers().get(XCACHE).unwrap(), "HIT");
+
+    // Hot pass with the extension leaves the headers off
+    let res = client
+        .get(url)
+        .with_extension(crate::SuppressCacheHeaders)
+        .send()
+        .await?;
+    assert!(res.headers().get(XCACHE).is_none());
+    assert!(res.headers().get(XCACHELOOKUP).is_none());
+    assert_eq!(res.bytes().await?, TEST_BODY);
+    Ok(())
+}
+
+#[tokio::test]
+async fn force_refresh_mode() -> Result<()> {
+    let mock_server = MockServer::start().await;
+    let m = build_mock(CACHEABLE_PUBLIC, TEST_BODY, 200, 2).up_to_n_times(2);
+    let m_slow = Mock::given(method(GET))
+        .respond_with(
+            ResponseTemplate::new(200)
+                .set_delay(std::time::Duration::from_secs(5)),
+        )
+        .expect(1);
+    let _mock_guard = mock_server.register_as_scoped(m).await;
+    let _mock_slow_guard = mock_server.register_as_scoped(m_slow).await;
+    let url = format!("{}/", &mock_server.uri());
+
+    // Construct reqwest client with force refresh mode
+    let client = ClientBuilder::new(Client::new())
+        .with(Cache(HttpCache {
+            mode: CacheMode::ForceRefresh,
+            manager: MokaManager::default(),
+            options: HttpCacheOptions::default(),
+        }))
+        .build();
+
+    // Cold pass to load cache
+    let res = client.get(url.clone()).send().await?;
+    assert_eq!(res.headers().get(XCACHELOOKUP).unwrap(), "MISS");
+
+    // Fresh response is always fetched from the origin
+    let res = client.get(url.clone()).send().await?;
+    assert_eq!(res.headers().get(XCACHELOOKUP).unwrap(), "HIT");
+    assert_eq!(res.headers().get(XCACHE).unwrap(), "MISS");
+
+    // The cached response is returned when the request to the origin times out
+    let res = client
+        .get(url)
+        .timeout(std::time::Duration::from_millis(100))
+        .send()
+        .await?;
+    assert_eq!(res.headers().get(XCACHE).unwrap(), "HIT");
+    assert!(res.headers().get("warning").unwrap().to_str()?.starts_with("111"));
+    assert_eq!(res.bytes().await?, TEST_BODY);
+    Ok(())
+}
+
+#[tokio::test]
+async fn force_refresh_store_error() -> Result<()> {
+    use std::sync::{
+        atomic::{AtomicBool, Ordering},
+        Mutex,
+    };
+
+    // Fails every put once `failing` is set
+    #[derive(Debug, Clone, Default)]
+    struct FailingManager {
+        inner: MokaManager,
+        failing: Arc<AtomicBool>,
+    }
+
+    #[async_trait::async_trait]
+    impl CacheManager for FailingManager {
+        async fn get(
+            &self,
+            cache_key: &str,
+        ) -> Result<Option<(HttpResponse, http_cache_semantics::CachePolicy)>>
+        {
+            self.inner.get(cache_key).await
+        }
+
+        async fn put(
+            &self,
+            cache_key: String,
+            res: HttpResponse,
+            policy: http_cache_semantics::CachePolicy,
+        ) -> Result<HttpResponse> {
+            if self.failing.load(Ordering::SeqCst) {
+                return Err("backend down".into());
+            }
+            self.inner.put(cache_key, res, policy).await
+        }
+
+        async fn delete(&self, cache_key: &str) -> Result<()> {
+            self.inner.delete(cache_key).await
+        }
+    }
+
+    let mock_server = MockServer::start().await;
+    let m = build_mock(CACHEABLE_PUBLIC, TEST_BODY, 200, 2);
+    let _mock_guard = mock_server.register_as_scoped(m).await;
+    let url = format!("{}/", &mock_server.uri());
+    let manager = FailingManager::default();
+    let served = Arc::new(Mutex::new(Vec::new()));
+    let recorded = served.clone();
+
+    let client = ClientBuilder::new(Client::new())
+        .with(Cache(HttpCache {
+            mode: CacheMode::ForceRefresh,
+            manager: manager.clone(),
+            options: HttpCacheOptions {
+                on_stale_served: Some(Arc::new(
+                    move |_: &Url, reason: StaleReason| {
+                        recorded.lock().unwrap().push(reason)
+                    },
+                )),
+                ..Default::default()
+            },
+        }))
+        .build();
+
+    // Cold pass to load cache
+    client.get(url.clone()).send().await?;
+
+    // The origin was reached, so failing to store its response is an error
+    // rather than a reason to serve the stale one
+    manager.failing.store(true, Ordering::SeqCst);
+    let err = client.get(url).send().await.unwrap_err();
+    assert!(err.to_string().contains("backend down"));
+    assert!(served.lock().unwrap().is_empty());
+    Ok(())
+}
+
+#[tokio::test]
+async fn gzip_encoded_upstream() -> Result<()> {
+    use flate2::{write::GzEncoder, Compression};
+    use std::io::Write;
+
+    let mut encoder = GzEncoder::new(Vec::new(), Compression::default());
+    encoder.write_all(TEST_BODY)?;
+    let encoded = encoder.finish()?;
+    let mock_server = MockServer::start().await;
+    let m = Mock::given(method(GET))
+        .respond_with(
+            ResponseTemplate::new(200)
+                .insert_header("cache-control", CACHEABLE_PUBLIC)
+                .insert_header("content-encoding", "gzip")
+                .set_body_bytes(encoded.clone()),
+        )
+        .expect(2);
+    let _mock_guard = mock_server.register_as_scoped(m).await;
+
+    // The client decodes the body, so the decoded body is cached
+    let url = format!("{}/decoded", &mock_server.uri());
+    let client = ClientBuilder::new(Client::builder().gzip(true).build()?)
+        .with(Cache(HttpCache {
+            mode: CacheMode::Default,
+            manager: MokaManager::default(),
+            options: HttpCacheOptions::default(),
+        }))
+        .build();
+    for _ in 0..2 {
+        let res = client.get(url.clone()).send().await?;
+        assert!(res.headers().get("content-encoding").is_none());
+        assert_eq!(res.headers().get("content-length").unwrap(), "4");
+        assert_eq!(res.bytes().await?, TEST_BODY);
+    }
+
+    // Decoding is disabled, so the encoded body is cached as is
+    let url = format!("{}/encoded", &mock_server.uri());
+    let client = ClientBuilder::new(Client::builder().gzip(false).build()?)
+        .with(Cache(HttpCache {
+            mode: CacheMode::Default,
+            manager: MokaManager::default(),
+            options: HttpCacheOptions::default(),
+        }))
+        .build();
+    for _ in 0..2 {
+        let res = client.get(url.clone()).send().await?;
+        assert_eq!(res.headers().get("content-encoding").unwrap(), "gzip");
+        assert_eq!(
+            res.headers().get("content-length").unwrap(),
+            encoded.len().to_string().as_str()
+        );
+        assert_eq!(res.bytes().await?, encoded);
+    }
+    Ok(())
+}
+
+#[tokio::test]
+async fn strip_headers_before_cache() -> Result<()> {
+    let mock_server = MockServer::start().await;
+    let m = Mock::given(method(GET))
+        .respond_with(
+            ResponseTemplate::new(200)
+                .insert_header("cache-control", CACHEABLE_PUBLIC)
+                .insert_header("set-cookie", "session=secret")
+                .insert_header("x-kept", "kept")
+                .set_body_bytes(TEST_BODY),
+        )
+        .expect(1);
+    let _mock_guard = mock_server.register_as_scoped(m).await;
+    let url = format!("{}/", &mock_server.uri());
+    let manager = MokaManager::default();
+
+    // Construct reqwest client with cache defaults
+    let client = ClientBuilder::new(Client::new())
+        .with(Cache(HttpCache {
+            mode: CacheMode::Default,
+            manager: manager.clone(),
+            options: HttpCacheOptions::default(),
+        }))
+        .build();
+
+    // Cold pass to load cache, the caller still receives the cookie
+    let res = client.get(url.clone()).send().await?;
+    assert_eq!(res.headers().get("set-cookie").unwrap(), "session=secret");
+
+    // The cookie isn't stored
+    let data = manager.get(&format!("{}:{}", GET, &Url::parse(&url)?)).await?;
+    assert!(!data.unwrap().0.headers.contains_key("set-cookie"));
+
+    // Hot pass to make sure the cookie isn't served from the cache
+    let res = client.get(url).send().await?;
+    assert_eq!(res.headers().get(XCACHE).unwrap(), "HIT");
+    assert!(res.headers().get("set-cookie").is_none());
+    assert_eq!(res.headers().get("x-kept").unwrap(), "kept");
+    Ok(())
+}
+
+#[tokio::test]
+async fn revalidation_if_modified_since() -> Result<()> {
+    use wiremock::matchers::header_exists;
+
+    const LAST_MODIFIED: &str = "Wed, 21 Oct 2015 07:28:00 GMT";
+    let mock_server = MockServer::start().await;
+    let m_304 = Mock::given(method(GET))
+        .and(header_exists("if-modified-since"))
+        .respond_with(ResponseTemplate::new(304))
+        .expect(1);
+    let m = Mock::given(method(GET))
+        .respond_with(
+            ResponseTemplate::new(200)
+                .insert_header("cache-control", "public, no-cache")
+                .insert_header("last-modified", LAST_MODIFIED)
+                .set_body_bytes(TEST_BODY),
+        )
+        .expect(1);
+    let _mock_304_guard = mock_server.register_as_scoped(m_304).await;
+    let _mock_guard = mock_server.register_as_scoped(m).await;
+    let url = format!("{}/", &mock_server.uri());
+
+    // Construct reqwest client with cache defaults
+    let client = ClientBuilder::new(Client::new())
+        .with(Cache(HttpCache {
+            mode: CacheMode::Default,
+            manager: MokaManager::default(),
+            options: HttpCacheOptions::default(),
+        }))
+        .build();
+
+    // Cold pass to load cache
+    let res = client.get(url.clone()).send().await?;
+    assert_eq!(res.headers().get(XCACHE).unwrap(), "MISS");
+
+    // The conditional request carries if-modified-since and the 304 is served from cache
+    let res = client.get(url).send().await?;
+    assert_eq!(res.headers().get(XCACHE).unwrap(), "HIT");
+    assert_eq!(res.headers().get("last-modified").unwrap(), LAST_MODIFIED);
+    assert_eq!(res.bytes().await?, TEST_BODY);
+    let requests = mock_server.received_requests().await.unwrap();
+    assert!(requests[0].headers.get("if-modified-since").is_none());
+    assert_eq!(
+        requests[1].headers.get("if-modified-since").unwrap(),
+        LAST_MODIFIED
+    );
+    assert!(requests[1].headers.get("if-none-match").is_none());
+    Ok(())
+}
+
+#[tokio::test]
+async fn warm() -> Result<()> {
+    use wiremock::matchers::path;
+
+    let mock_server = MockServer::start().await;
+    for route in ["/first", "/second"] {
+        mock_server
+            .register(
+                Mock::given(method(GET))
+                    .and(path(route))
+                    .respond_with(
+                        ResponseTemplate::new(200)
+                            .insert_header("cache-control", CACHEABLE_PUBLIC)
+                            .set_body_bytes(TEST_BODY),
+                    )
+                    .expect(1),
+            )
+            .await;
+    }
+    let urls = [
+        Url::parse(&format!("{}/first", &mock_server.uri()))?,
+        Url::parse(&format!("{}/second", &mock_server.uri()))?,
+    ];
+    let manager = MokaManager::default();
+    let cache = Cache(HttpCache {
+        mode: CacheMode::Default,
+        manager: manager.clone(),
+        options: HttpCacheOptions::default(),
+    });
+
+    // Warming stores both urls
+    let results = cache.warm(&Client::new(), &urls).await;
+    assert_eq!(results.len(), 2);
+    assert!(results.iter().all(|result| result.is_ok()));
+    for url in &urls {
+        assert!(manager.get(&format!("{}:{}", GET, url)).await?.is_some());
+    }
+
+    // Warming again doesn't refetch fresh entries
+    let results = cache.warm(&Client::new(), &urls).await;
+    assert!(results.iter().all(|result| result.is_ok()));
+
+    // A subsequent request is served from the cache
+    let client = ClientBuilder::new(Client::new()).with(cache).build();
+    let res = client.get(urls[0].clone()).send().await?;
+    assert_eq!(res.headers().get(XCACHE).unwrap(), "HIT");
+    Ok(())
+}
+
+#[tokio::test]
+async fn shared_cache() -> Result<()> {
+    for (shared, should_cache) in [(false, true), (true, false)] {
+        let mock_server = MockServer::start().await;
+        let m = build_mock("private, max-age=86400", TEST_BODY, 200, 1);
+        let _mock_guard = mock_server.register_as_scoped(m).await;
+        let url = format!("{}/", &mock_server.uri());
+        let manager = MokaManager::default();
+
+        // Construct reqwest client as a shared or private cache
+        let client = ClientBuilder::new(Client::new())
+            .with(Cache(HttpCache {
+                mode: CacheMode::Default,
+                manager: manager.clone(),
+                options: HttpCacheOptions { shared, ..Default::default() },
+            }))
+            .build();
+
+        client.get(url.clone()).send().await?;
+
+        let data =
+            manager.get(&format!("{}:{}", GET, &Url::parse(&url)?)).await?;
+        assert_eq!(data.is_some(), should_cache, "shared: {shared}");
+    }
+    Ok(())
+}
+
+#[tokio::test]
+async fn shared_cache_prefers_s_maxage() -> Result<()> {
+    for (shared, expected_hit, fetches) in
+        [(false, "HIT", 1), (true, "MISS", 2)]
+    {
+        let mock_server = MockServer::start().await;
+        let m = build_mock(
+            "public, max-age=86400, s-maxage=0",
+            TEST_BODY,
+            200,
+            fetches,
+        );
+        let _mock_guard = mock_server.register_as_scoped(m).await;
+        let url = format!("{}/", &mock_server.uri());
+
+        // Construct reqwest client as a shared or private cache
+        let client = ClientBuilder::new(Client::new())
+            .with(Cache(HttpCache {
+                mode: CacheMode::Default,
+                manager: MokaManager::default(),
+                options: HttpCacheOptions { shared, ..Default::default() },
+            }))
+            .build();
+
+        client.get(url.clone()).send().await?;
+        let res = client.get(url.clone()).send().await?;
+        assert_eq!(
+            res.headers().get(XCACHE).unwrap(),
+            expected_hit,
+            "shared: {shared}"
+        );
+    }
+    Ok(())
+}
+
+#[tokio::test]
+async fn range_requests() -> Result<()> {
+    use wiremock::matchers::header;
+
+    for (cache_range_requests, partial_fetches) in [(false, 2), (true, 1)] {
+        let mock_server = MockServer::start().await;
+        let partial = Mock::given(method(GET))
+            .and(header("range", "bytes=0-3"))
+            .respond_with(
+                ResponseTemplate::new(206)
+                    .insert_header("cache-control", CACHEABLE_PUBLIC)
+                    .insert_header("content-range", "bytes 0-3/11")
+                    .set_body_bytes(&TEST_BODY[..4]),
+            )
+            .with_priority(1)
+            .expect(partial_fetches);
+        let _partial_guard = mock_server.register_as_scoped(partial).await;
+        let full = build_mock(CACHEABLE_PUBLIC, TEST_BODY, 200, 1);
+        let _full_guard = mock_server.register_as_scoped(full).await;
+        let url = format!("{}/", &mock_server.uri());
+
+        // Construct reqwest client with cache defaults and range caching option
+        let client = ClientBuilder::new(Client::new())
+            .with(Cache(HttpCache {
+                mode: CacheMode::Default,
+                manager: MokaManager::default(),
+                options: HttpCacheOptions {
+                    cache_range_requests,
+                    ..Default::default()
+                },
+            }))
+            .build();
+
+        // Partial responses are only cached when keyed on their range
+        for _ in 0..2 {
+            let res = client
+                .get(url.clone())
+                .header("range", "bytes=0-3")
+                .send()
+                .await?;
+            assert_eq!(res.status(), 206);
+            assert_eq!(res.bytes().await?, &TEST_BODY[..4]);
+        }
+
+        // A full request is never served the cached partial response
+        let res = client.get(url.clone()).send().await?;
+        assert_eq!(res.status(), 200);
+        assert_eq!(res.headers().get(XCACHE).unwrap(), "MISS");
+        assert_eq!(res.bytes().await?, TEST_BODY);
+        let res = client.get(url.clone()).send().await?;
+        assert_eq!(res.headers().get(XCACHE).unwrap(), "HIT");
+        assert_eq!(res.bytes().await?, TEST_BODY);
+    }
+    Ok(())
+}
+
+#[tokio::test]
+async fn verify_body_checksum() -> Result<()> {
+    for (corrupt, expected) in [(false, "HIT"), (true, "MISS")] {
+        let mock_server = MockServer::start().await;
+        let m = build_mock(
+            CACHEABLE_PUBLIC,
+            TEST_BODY,
+            200,
+            if corrupt { 2 } else { 1 },
+        );
+        let _mock_guard = mock_server.register_as_scoped(m).await;
+        let url = format!("{}/", &mock_server.uri());
+        let manager = MokaManager::default();
+
+        // Construct reqwest client with body checksums verified
+        let client = ClientBuilder::new(Client::new())
+            .with(Cache(HttpCache {
+                mode: CacheMode::Default,
+                manager: manager.clone(),
+                options: HttpCacheOptions {
+                    verify_body_checksum: true,
+                    ..Default::default()
+                },
+            }))
+            .build();
+
+        // Cold pass to load cache, the checksum isn't part of the response
+        let res = client.get(url.clone()).send().await?;
+        assert!(res.headers().get("x-http-cache-checksum").is_none());
+
+        // Flip a byte of the stored body
+        if corrupt {
+            let cache_key = format!("{}:{}", GET, &Url::parse(&url)?);
+            let (mut cached, policy) = manager.get(&cache_key).await?.unwrap();
+            cached.body[0] ^= 1;
+            manager.put(cache_key, cached, policy).await?;
+        }
+
+        // Hot pass only serves the stored body if it's intact
+        let res = client.get(url).send().await?;
+        assert_eq!(res.headers().get(XCACHE).unwrap(), expected);
+        assert!(res.headers().get("x-http-cache-checksum").is_none());
+        assert_eq!(res.bytes().await?, TEST_BODY);
+    }
+    Ok(())
+}
+
+#[tokio::test]
+async fn negative_caching() -> Result<()> {
+    let mock_server = MockServer::start().await;
+    let m = build_mock("no-cache", TEST_BODY, 503, 1);
+    let _mock_guard = mock_server.register_as_scoped(m).await;
+    let url = format!("{}/", &mock_server.uri());
+
+    // Construct reqwest client caching 503 responses for 5 seconds
+    let client = ClientBuilder::new(Client::new())
+        .with(Cache(HttpCache {
+            mode: CacheMode::Default,
+            manager: MokaManager::default(),
+            options: HttpCacheOptions {
+                negative_ttl: Some(std::time::Duration::from_secs(5)),
+                negative_status_codes: [503].into(),
+                ..Default::default()
+            },
+        }))
+        .build();
+
+    // Cold pass to load cache
+    let res = client.get(url.clone()).send().await?;
+    assert_eq!(res.status(), 503);
+    assert_eq!(res.headers().get(XCACHE).unwrap(), "MISS");
+
+    // Immediate retry is served the cached error despite its no-cache header
+    let res = client.get(url).send().await?;
+    assert_eq!(res.status(), 503);
+    assert_eq!(res.headers().get(XCACHE).unwrap(), "HIT");
+    assert_eq!(res.bytes().await?, TEST_BODY);
+    Ok(())
+}
+
+#[tokio::test]
+async fn negative_caching_status_codes() -> Result<()> {
+    let mock_server = MockServer::start().await;
+    let m = build_mock(CACHEABLE_PUBLIC, TEST_BODY, 500, 2);
+    let _mock_guard = mock_server.register_as_scoped(m).await;
+    let url = format!("{}/", &mock_server.uri());
+
+    // Construct reqwest client that only negatively caches 404 responses
+    let client = ClientBuilder::new(Client::new())
+        .with(Cache(HttpCache {
+            mode: CacheMode::Default,
+            manager: MokaManager::default(),
+            options: HttpCacheOptions {
+                negative_ttl: Some(std::time::Duration::from_secs(5)),
+                negative_status_codes: [404].into(),
+                ..Default::default()
+            },
+        }))
+        .build();
+
+    // Other error statuses are still fetched every time
+    for _ in 0..2 {
+        let res = client.get(url.clone()).send().await?;
+        assert_eq!(res.status(), 500);
+        assert_eq!(res.headers().get(XCACHE).unwrap(), "MISS");
+    }
+    Ok(())
+}
+
+#[tokio::test]
+async fn raw_deflate_encoded_round_trip() -> Result<()> {
+    use flate2::{write::DeflateEncoder, Compression};
+    use std::io::Write;
+
+    // A raw deflate stream has no zlib header, the client doesn't decode deflate
+    let mut encoder = DeflateEncoder::new(Vec::new(), Compression::default());
+    encoder.write_all(TEST_BODY)?;
+    let encoded = encoder.finish()?;
+    let mock_server = MockServer::start().await;
+    let m = Mock::given(method(GET))
+        .respond_with(
+            ResponseTemplate::new(200)
+                .insert_header("cache-control", CACHEABLE_PUBLIC)
+                .insert_header("content-encoding", "deflate")
+                .set_body_bytes(encoded.clone()),
+        )
+        .expect(1);
+    let _mock_guard = mock_server.register_as_scoped(m).await;
+    let url = format!("{}/", &mock_server.uri());
+
+    // Construct reqwest client with cache defaults
+    let client = ClientBuilder::new(Client::new())
+        .with(Cache(HttpCache {
+            mode: CacheMode::Default,
+            manager: MokaManager::default(),
+            options: HttpCacheOptions::default(),
+        }))
+        .build();
+
+    // The body is still encoded, so its content-encoding is kept
+    for expected in ["MISS", "HIT"] {
+        let res = client.get(url.clone()).send().await?;
+        assert_eq!(res.headers().get(XCACHE).unwrap(), expected);
+        assert_eq!(res.headers().get("content-encoding").unwrap(), "deflate");
+        assert_eq!(res.bytes().await?, encoded);
+    }
+    Ok(())
+}
+
+#[tokio::test]
+async fn brotli_encoded_round_trip() -> Result<()> {
+    // Arbitrary bytes standing in for a brotli stream, the client doesn't decode br
+    let encoded = vec![0x1b, 0x03, 0x00, 0xf8, 0x25, 0x00, 0xa2, 0x90];
+    let mock_server = MockServer::start().await;
+    let m = Mock::given(method(GET))
+        .respond_with(
+            ResponseTemplate::new(200)
+                .insert_header("cache-control", CACHEABLE_PUBLIC)
+                .insert_header("content-encoding", "br")
+                .set_body_bytes(encoded.clone()),
+        )
+        .expect(1);
+    let _mock_guard = mock_server.register_as_scoped(m).await;
+    let url = format!("{}/", &mock_server.uri());
+
+    // Construct reqwest client with cache defaults
+    let client = ClientBuilder::new(Client::new())
+        .with(Cache(HttpCache {
+            mode: CacheMode::Default,
+            manager: MokaManager::default(),
+            options: HttpCacheOptions::default(),
+        }))
+        .build();
+
+    // The encoded body and its content-encoding are replayed unchanged,
+    // so the client can still decode the cached response
+    for expected in ["MISS", "HIT"] {
+        let res = client.get(url.clone()).send().await?;
+        assert_eq!(res.headers().get(XCACHE).unwrap(), expected);
+        assert_eq!(res.headers().get("content-encoding").unwrap(), "br");
+        assert_eq!(res.bytes().await?, encoded);
+    }
+    Ok(())
+}
+
+#[tokio::test]
+async fn cache_options_override() -> Result<()> {
+    use crate::CacheOptionsOverride;
+
+    let mock_server = MockServer::start().await;
+    let m = build_mock(
+        "no-store, no-cache, must-revalidate, post-check=0, pre-check=0",
+        TEST_BODY,
+        200,
+        2,
+    );
+    let _mock_guard = mock_server.register_as_scoped(m).await;
+    let manager = MokaManager::default();
+
+    // Construct reqwest client with cache defaults
+    let client = ClientBuilder::new(Client::new())
+        .with(Cache(HttpCache {
+            mode: CacheMode::Default,
+            manager: manager.clone(),
+            options: HttpCacheOptions::default(),
+        }))
+        .build();
+
+    // The anti-cache directives are followed by default
+    let url = format!("{}/default", &mock_server.uri());
+    client.get(url.clone()).send().await?;
+    let data = manager.get(&format!("{}:{}", GET, &Url::parse(&url)?)).await?;
+    assert!(data.is_none());
+
+    // The override makes the response storable
+    let url = format!("{}/override", &mock_server.uri());
+    let options =
+        CacheOptions { ignore_cargo_cult: true, ..Default::default() };
+    client
+        .get(url.clone())
+        .with_extension(CacheOptionsOverride(options))
+        .send()
+        .await?;
+    let data = manager.get(&format!("{}:{}", GET, &Url::parse(&url)?)).await?;
+    assert!(data.is_some());
+    Ok(())
+}
+
+#[tokio::test]
+async fn cache_options_method() -> Result<()> {
+    let mock_server = MockServer::start().await;
+    let m = Mock::given(method("OPTIONS"))
+        .respond_with(
+            ResponseTemplate::new(200)
+                .insert_header("access-control-allow-origin", "*")
+                .insert_header("access-control-max-age", "600"),
+        )
+        .expect(1);
+    let _mock_guard = mock_server.register_as_scoped(m).await;
+    let url = format!("{}/", &mock_server.uri());
+
+    // Construct reqwest client that caches preflight responses
+    let client = ClientBuilder::new(Client::new())
+        .with(Cache(HttpCache {
+            mode: CacheMode::Default,
+            manager: MokaManager::default(),
+            options: HttpCacheOptions {
+                cache_options_method: true,
+                ..Default::default()
+            },
+        }))
+        .build();
+
+    // The preflight is fresh for its access-control-max-age
+    for expected in ["MISS", "HIT"] {
+        let res = client
+            .request(reqwest::Method::OPTIONS, url.clone())
+            .send()
+            .await?;
+        assert_eq!(res.headers().get(XCACHE).unwrap(), expected);
+        assert_eq!(res.headers().get("access-control-max-age").unwrap(), "600");
+    }
+    Ok(())
+}
+
+#[tokio::test]
+async fn invalidate_path_prefix() -> Result<()> {
+    let mock_server = MockServer::start().await;
+    let m = build_mock(CACHEABLE_PUBLIC, TEST_BODY, 200, 5);
+    let _mock_guard = mock_server.register_as_scoped(m).await;
+    let m_post = Mock::given(method("POST"))
+        .respond_with(ResponseTemplate::new(201))
+        .expect(1);
+    let _mock_guard_post = mock_server.register_as_scoped(m_post).await;
+    let listings = [
+        format!("{}/items", &mock_server.uri()),
+        format!("{}/items?page=2", &mock_server.uri()),
+    ];
+    let other = format!("{}/users", &mock_server.uri());
+
+    // Construct reqwest client that busts every listing on a change to the collection
+    let client = ClientBuilder::new(Client::new())
+        .with(Cache(HttpCache {
+            mode: CacheMode::Default,
+            manager: MokaManager::default(),
+            options: HttpCacheOptions {
+                cache_bust: Some(http_cache::invalidate_path_prefix("/items")),
+                ..Default::default()
+            },
+        }))
+        .build();
+
+    for url in listings.iter().chain([&other]) {
+        client.get(url).send().await?;
+        let res = client.get(url).send().await?;
+        assert_eq!(res.headers().get(XCACHE).unwrap(), "HIT");
+    }
+
+    client.post(&listings[0]).send().await?;
+
+    // Every listing variant is refetched, other paths are still cached
+    for url in &listings {
+        let res = client.get(url).send().await?;
+        assert_eq!(res.headers().get(XCACHE).unwrap(), "MISS");
+    }
+    let res = client.get(&other).send().await?;
+    assert_eq!(res.headers().get(XCACHE).unwrap(), "HIT");
+    Ok(())
+}
+
+#[tokio::test]
+async fn max_fresh_lifetime() -> Result<()> {
+    let mock_server = MockServer::start().await;
+    // Received two hours into its day long lifetime
+    let m = Mock::given(method(GET))
+        .respond_with(
+            ResponseTemplate::new(200)
+                .insert_header("cache-control", "max-age=86400")
+                .insert_header("age", "7200")
+                .set_body_bytes(TEST_BODY),
+        )
+        .expect(2);
+    let _mock_guard = mock_server.register_as_scoped(m).await;
+    let url = format!("{}/", &mock_server.uri());
+
+    // Construct reqwest client that considers nothing fresh for over an hour
+    let client = ClientBuilder::new(Client::new())
+        .with(Cache(HttpCache {
+            mode: CacheMode::Default,
+            manager: MokaManager::default(),
+            options: HttpCacheOptions {
+                max_fresh_lifetime: Some(std::time::Duration::from_secs(3600)),
+                ..Default::default()
+            },
+        }))
+        .build();
+
+    // The cached response is past the clamp, so it's revalidated
+    client.get(url.clone()).send().await?;
+    let res = client.get(url).send().await?;
+    assert_eq!(res.headers().get(XCACHELOOKUP).unwrap(), "HIT");
+    assert_eq!(res.headers().get(XCACHE).unwrap(), "MISS");
+    Ok(())
+}
+
+#[tokio::test]
+async fn max_served_staleness() -> Result<()> {
+    let mock_server = MockServer::start().await;
+    let m = build_mock("max-age=0", TEST_BODY, 200, 2);
+    let _mock_guard = mock_server.register_as_scoped(m).await;
+    let url = format!("{}/", &mock_server.uri());
+
+    // Construct reqwest client that never serves stale responses
+    let client = ClientBuilder::new(Client::new())
+        .with(Cache(HttpCache {
+            mode: CacheMode::StaleWhileRevalidate,
+            manager: MokaManager::default(),
+            options: HttpCacheOptions {
+                max_served_staleness: Some(std::time::Duration::ZERO),
+                ..Default::default()
+            },
+        }))
+        .build();
+
+    // The stale response isn't served, the revalidated one is returned instead
+    client.get(url.clone()).send().await?;
+    let res = client.get(url).send().await?;
+    assert_eq!(res.headers().get(XCACHE).unwrap(), "MISS");
+    assert!(res.headers().get("warning").is_none());
+    Ok(())
+}
+
+#[tokio::test]
+async fn manager_revalidation() -> Result<()> {
+    // Answers every revalidation itself, as if it knew the etag is unchanged
+    #[derive(Debug, Clone, Default)]
+    struct ValidatingManager {
+        inner: MokaManager,
+    }
+
+    #[async_trait::async_trait]
+    impl CacheManager for ValidatingManager {
+        async fn get(
+            &self,
+            cache_key: &str,
+        ) -> Result<Option<(HttpResponse, http_cache_semantics::CachePolicy)>>
+        {
+            self.inner.get(cache_key).await
+        }
+
+        async fn put(
+            &self,
+            cache_key: String,
+            res: HttpResponse,
+            policy: http_cache_semantics::CachePolicy,
+        ) -> Result<HttpResponse> {
+            self.inner.put(cache_key, res, policy).await
+        }
+
+        async fn delete(&self, cache_key: &str) -> Result<()> {
+            self.inner.delete(cache_key).await
+        }
+
+        async fn revalidate(
+            &self,
+            _cache_key: &str,
+            parts: &http::request::Parts,
+        ) -> Result<Option<RevalidationOutcome>> {
+            assert_eq!(parts.headers.get("if-none-match").unwrap(), "\"v1\"");
+            Ok(Some(RevalidationOutcome::NotModified(
+                [("etag".to_string(), "\"v1\"".to_string())].into(),
+            )))
+        }
+    }
+
+    let mock_server = MockServer::start().await;
+    let m = Mock::given(method(GET))
+        .respond_with(
+            ResponseTemplate::new(200)
+                .insert_header("cache-control", "max-age=0")
+                .insert_header("etag", "\"v1\"")
+                .set_body_bytes(TEST_BODY),
+        )
+        .expect(1);
+    let _mock_guard = mock_server.register_as_scoped(m).await;
+    let url = format!("{}/", &mock_server.uri());
+
+    // Construct reqwest client with a manager that answers revalidations
+    let client = ClientBuilder::new(Client::new())
+        .with(Cache(HttpCache {
+            mode: CacheMode::Default,
+            manager: ValidatingManager::default(),
+            options: HttpCacheOptions::default(),
+        }))
+        .build();
+
+    // The stale response is revalidated without the origin being contacted
+    client.get(url.clone()).send().await?;
+    let res = client.get(url).send().await?;
+    assert_eq!(res.headers().get(XCACHE).unwrap(), "HIT");
+    assert_eq!(res.bytes().await?, TEST_BODY);
+    Ok(())
+}
+
+#[tokio::test]
+async fn vary_by_request_headers() -> Result<()> {
+    use wiremock::matchers::header;
+
+    let mock_server = MockServer::start().await;
+    for (accept, body) in
+        [("application/json", "{}"), ("application/xml", "<items/>")]
+    {
+        let m = Mock::given(method(GET))
+            .and(header("accept", accept))
+            .respond_with(
+                ResponseTemplate::new(200)
+                    .insert_header("cache-control", CACHEABLE_PUBLIC)
+                    .set_body_string(body),
+            )
+            .expect(1);
+        mock_server.register(m).await;
+    }
+    let url = format!("{}/", &mock_server.uri());
+
+    // Construct reqwest client that keys responses on the accept header,
+    // which the origin doesn't list in a vary header
+    let client = ClientBuilder::new(Client::new())
+        .with(Cache(HttpCache {
+            mode: CacheMode::Default,
+            manager: MokaManager::default(),
+            options: HttpCacheOptions {
+                vary_by_request_headers: vec![http::header::ACCEPT],
+                ..Default::default()
+            },
+        }))
+        .build();
+
+    // Each representation is cached separately
+    for expected in ["MISS", "HIT"] {
+        for (accept, body) in
+            [("application/json", "{}"), ("application/xml", "<items/>")]
+        {
+            let res =
+                client.get(url.clone()).header("accept", accept).send().await?;
+            assert_eq!(res.headers().get(XCACHE).unwrap(), expected);
+            assert_eq!(res.text().await?, body);
+        }
+    }
+    Ok(())
+}
+
+#[tokio::test]
+async fn qualified_no_cache() -> Result<()> {
+    let mock_server = MockServer::start().await;
+    let m = Mock::given(method(GET))
+        .respond_with(
+            ResponseTemplate::new(200)
+                .insert_header(
+                    "cache-control",
+                    "max-age=3600, no-cache=\"Set-Cookie, X-Session\"",
+                )
+                .insert_header("set-cookie", "session=1")
+                .insert_header("x-session", "1")
+                .insert_header("x-request", "1")
+                .set_body_bytes(TEST_BODY),
+        )
+        .expect(1);
+    let _mock_guard = mock_server.register_as_scoped(m).await;
+    let url = format!("{}/", &mock_server.uri());
+
+    // Construct reqwest client that doesn't strip set-cookie on its own
+    let client = ClientBuilder::new(Client::new())
+        .with(Cache(HttpCache {
+            mode: CacheMode::Default,
+            manager: MokaManager::default(),
+            options: HttpCacheOptions {
+                strip_headers_before_cache: None,
+                ..Default::default()
+            },
+        }))
+        .build();
+
+    // The original response keeps every header
+    let res = client.get(url.clone()).send().await?;
+    assert_eq!(res.headers().get("set-cookie").unwrap(), "session=1");
+    assert_eq!(res.headers().get("x-session").unwrap(), "1");
+
+    // The body is reused without revalidation, minus the named headers
+    let res = client.get(url).send().await?;
+    assert_eq!(res.headers().get(XCACHE).unwrap(), "HIT");
+    assert!(res.headers().get("set-cookie").is_none());
+    assert!(res.headers().get("x-session").is_none());
+    assert_eq!(res.headers().get("x-request").unwrap(), "1");
+    assert_eq!(res.bytes().await?, TEST_BODY);
+    Ok(())
+}
+
+#[cfg(feature = "refresher")]
+#[tokio::test(start_paused = true)]
+async fn spawn_refresher() -> Result<()> {
+    let mock_server = MockServer::start().await;
+    for body in ["first", "second"] {
+        let m = Mock::given(method(GET))
+            .respond_with(
+                ResponseTemplate::new(200)
+                    .insert_header("cache-control", CACHEABLE_PUBLIC)
+                    .set_body_string(body),
+            )
+            .up_to_n_times(1);
+        mock_server.register(m).await;
+    }
+    let url = Url::parse(&format!("{}/", &mock_server.uri()))?;
+    let key = format!("{}:{}", GET, &url);
+    let manager = MokaManager::default();
+    let cache = Cache(HttpCache {
+        mode: CacheMode::Default,
+        manager: manager.clone(),
+        options: HttpCacheOptions::default(),
+    });
+    cache.warm(&Client::new(), &[url]).await;
+    let interval = std::time::Duration::from_secs(60);
+    let refresher = cache.spawn_refresher(
+        &Client::new(),
+        cache.0.refresher_for_keys(std::slice::from_ref(&key), interval)?,
+    );
+
+    // Nothing is refreshed before the interval has passed
+    tokio::time::advance(interval / 2).await;
+    let (res, _) = manager.get(&key).await?.unwrap();
+    assert_eq!(res.body, b"first");
+
+    // The still fresh entry is revalidated once it has
+    tokio::time::advance(interval).await;
+    let deadline =
+        std::time::Instant::now() + std::time::Duration::from_secs(5);
+    loop {
+        let (res, _) = manager.get(&key).await?.unwrap();
+        if res.body == b"second" {
+            break;
+        }
+        assert!(std::time::Instant::now() < deadline, "entry wasn't refreshed");
+        tokio::task::yield_now().await;
+    }
+    refresher.abort();
+    Ok(())
+}
+
+#[tokio::test]
+async fn keep_after_non_get_head_method_request() -> Result<()> {
+    let mock_server = MockServer::start().await;
+    let m = build_mock(CACHEABLE_PUBLIC, TEST_BODY, 200, 1);
+    let _mock_guard = mock_server.register_as_scoped(m).await;
+    let url = format!("{}/", &mock_server.uri());
+    let manager = MokaManager::default();
+
+    // Construct reqwest client for an endpoint taking idempotent posts
+    let client = ClientBuilder::new(Client::new())
+        .with(Cache(HttpCache {
+            mode: CacheMode::Default,
+            manager: manager.clone(),
+            options: HttpCacheOptions {
+                invalidate_on_unsafe_methods: false,
+                ..Default::default()
+            },
+        }))
+        .build();
+
+    // Cold pass to load cache
+    client.get(url.clone()).send().await?;
+
+    // The post leaves the cached object at the same resource in place
+    client.post(url.clone()).body("query").send().await?;
+    let data = manager.get(&format!("{}:{}", GET, &Url::parse(&url)?)).await?;
+    assert!(data.is_some());
+    let res = client.get(url).send().await?;
+    assert_eq!(res.headers().get(XCACHE).unwrap(), "HIT");
+    Ok(())
+}
+
+#[tokio::test]
+async fn follow_content_location() -> Result<()> {
+    use wiremock::matchers::path;
+
+    let mock_server = MockServer::start().await;
+    let m = Mock::given(method(GET))
+        .and(path("/latest"))
+        .respond_with(
+            ResponseTemplate::new(200)
+                .insert_header("cache-control", CACHEABLE_PUBLIC)
+                .insert_header("content-location", "/v/5")
+                .set_body_bytes(TEST_BODY),
+        )
+        .expect(1);
+    mock_server.register(m).await;
+    let m = Mock::given(method(GET))
+        .and(path("/v/5"))
+        .respond_with(ResponseTemplate::new(500))
+        .expect(0);
+    mock_server.register(m).await;
+
+    // Construct reqwest client that also stores responses under their content location
+    let client = ClientBuilder::new(Client::new())
+        .with(Cache(HttpCache {
+            mode: CacheMode::Default,
+            manager: MokaManager::default(),
+            options: HttpCacheOptions {
+                follow_content_location: true,
+                ..Default::default()
+            },
+        }))
+        .build();
+
+    let res =
+        client.get(format!("{}/latest", &mock_server.uri())).send().await?;
+    assert_eq!(res.headers().get(XCACHE).unwrap(), "MISS");
+
+    // The canonical url is served from the cache
+    let res = client.get(format!("{}/v/5", &mock_server.uri())).send().await?;
+    assert_eq!(res.headers().get(XCACHE).unwrap(), "HIT");
+    assert_eq!(res.bytes().await?, TEST_BODY);
+    Ok(())
+}
+
+#[tokio::test]
+async fn pre_key_request_fn() -> Result<()> {
+    let mock_server = MockServer::start().await;
+    let m = build_mock(CACHEABLE_PUBLIC, TEST_BODY, 200, 1);
+    let _mock_guard = mock_server.register_as_scoped(m).await;
+
+    // Construct reqwest client that leaves tracking parameters out of the cache key
+    let client = ClientBuilder::new(Client::new())
+        .with(Cache(HttpCache {
+            mode: CacheMode::Default,
+            manager: MokaManager::default(),
+            options: HttpCacheOptions {
+                pre_key_request_fn: Some(Arc::new(
+                    |parts: &mut http::request::Parts| {
+                        let mut url =
+                            Url::parse(&parts.uri.to_string()).unwrap();
+                        let query: Vec<(String, String)> = url
+                            .query_pairs()
+                            .filter(|(name, _)| !name.starts_with("utm_"))
+                            .map(|(name, val)| {
+                                (name.into_owned(), val.into_owned())
+                            })
+                            .collect();
+                        url.query_pairs_mut().clear().extend_pairs(query);
+                        parts.uri = url.as_str().parse().unwrap();
+                    },
+                )),
+                ..Default::default()
+            },
+        }))
+        .build();
+
+    let res = client
+        .get(format!("{}/?page=1&utm_source=newsletter", &mock_server.uri()))
+        .send()
+        .await?;
+    assert_eq!(res.headers().get(XCACHE).unwrap(), "MISS");
+
+    // Requests that only differ in the tracking parameters share the entry
+    let res = client
+        .get(format!("{}/?page=1&utm_source=social", &mock_server.uri()))
+        .send()
+        .await?;
+    assert_eq!(res.headers().get(XCACHE).unwrap(), "HIT");
+    assert_eq!(res.bytes().await?, TEST_BODY);
+    Ok(())
+}
+
+#[tokio::test]
+async fn header_limits() -> Result<()> {
+    use std::sync::Mutex;
+
+    let mock_server = MockServer::start().await;
+    let m = Mock::given(method(GET)).respond_with(
+        ResponseTemplate::new(200)
+            .insert_header("cache-control", CACHEABLE_PUBLIC)
+            .insert_header("x-first", "1")
+            .insert_header("x-second", "2")
+            .insert_header("x-long", "a".repeat(64).as_str())
+            .set_body_bytes(TEST_BODY),
+    );
+    let _mock_guard = mock_server.register_as_scoped(m).await;
+    let url = format!("{}/", &mock_server.uri());
+    let key = format!("{}:{}", GET, &Url::parse(&url)?);
+
+    for options in [
+        HttpCacheOptions { max_headers: Some(3), ..Default::default() },
+        HttpCacheOptions {
+            max_header_value_len: Some(32),
+            ..Default::default()
+        },
+    ] {
+        let decisions = Arc::new(Mutex::new(Vec::new()));
+        let recorded = decisions.clone();
+        let options = HttpCacheOptions {
+            on_cache_decision: Some(Arc::new(
+                move |decision: &CacheDecision| {
+                    recorded.lock().unwrap().push(decision.clone())
+                },
+            )),
+            ..options
+        };
+        let manager = MokaManager::default();
+        let client = ClientBuilder::new(Client::new())
+            .with(Cache(HttpCache {
+                mode: CacheMode::Default,
+                manager: manager.clone(),
+                options,
+            }))
+            .build();
+
+        // Exceeding a limit returns the response without storing it, rather than
+        // failing the request, and the decision records why
+        let res = client.get(url.clone()).send().await?;
+        assert_eq!(
+            res.headers().get("x-long").unwrap(),
+            "a".repeat(64).as_str()
+        );
+        assert_eq!(res.bytes().await?, TEST_BODY);
+        assert!(manager.get(&key).await?.is_none());
+        let decisions = decisions.lock().unwrap();
+        assert_eq!(decisions.len(), 1);
+        assert_eq!(decisions[0].reason, CacheDecisionReason::HeaderLimits);
+        assert!(!decisions[0].cached);
+    }
+
+    // The response is cached when it's within the limits
+    let manager = MokaManager::default();
+    let client = ClientBuilder::new(Client::new())
+        .with(Cache(HttpCache {
+            mode: CacheMode::Default,
+            manager: manager.clone(),
+            options: HttpCacheOptions {
+                max_headers: Some(16),
+                max_header_value_len: Some(64),
+                ..Default::default()
+            },
+        }))
+        .build();
+    client.get(url).send().await?;
+    assert!(manager.get(&key).await?.is_some());
+    Ok(())
+}
+
+#[tokio::test]
+async fn must_understand() -> Result<()> {
+    let mock_server = MockServer::start().await;
+    let cache_control = "max-age=3600, must-understand, no-store";
+    let m = build_mock(cache_control, TEST_BODY, 200, 1);
+    let _mock_guard = mock_server.register_as_scoped(m).await;
+    let url = format!("{}/", &mock_server.uri());
+
+    // Construct reqwest client with cache defaults
+    let client = ClientBuilder::new(Client::new())
+        .with(Cache(HttpCache {
+            mode: CacheMode::Default,
+            manager: MokaManager::default(),
+            options: HttpCacheOptions::default(),
+        }))
+        .build();
+
+    // The status is understood, so `no-store` is ignored
+    client.get(url.clone()).send().await?;
+    let res = client.get(url).send().await?;
+    assert_eq!(res.headers().get(XCACHE).unwrap(), "HIT");
+    drop(_mock_guard);
+
+    // A status the cache doesn't understand isn't stored
+    let m = build_mock(cache_control, TEST_BODY, 299, 2);
+    let _mock_guard = mock_server.register_as_scoped(m).await;
+    let url = format!("{}/unusual", &mock_server.uri());
+    for _ in 0..2 {
+        let res = client.get(url.clone()).send().await?;
+        assert_eq!(res.status(), 299);
+        assert_eq!(res.headers().get(XCACHE).unwrap(), "MISS");
+    }
+    Ok(())
+}
+
+#[tokio::test]
+async fn expires_without_cache_control() -> Result<()> {
+    let mock_server = MockServer::start().await;
+    for (path, expires, expect) in [
+        ("/future", "Thu, 01 Jan 2099 00:00:00 GMT", 1),
+        ("/past", "Fri, 01 Jan 1999 00:00:00 GMT", 2),
+    ] {
+        let m = Mock::given(method(GET))
+            .and(wiremock::matchers::path(path))
+            .respond_with(
+                ResponseTemplate::new(200)
+                    .insert_header("expires", expires)
+                    .set_body_bytes(TEST_BODY),
+            )
+            .expect(expect);
+        mock_server.register(m).await;
+    }
+    let manager = MokaManager::default();
+
+    // Construct reqwest client with cache defaults
+    let client = ClientBuilder::new(Client::new())
+        .with(Cache(HttpCache {
+            mode: CacheMode::Default,
+            manager: manager.clone(),
+            options: HttpCacheOptions::default(),
+        }))
+        .build();
+
+    // A response with only an expires header is fresh until that date
+    let url = format!("{}/future", &mock_server.uri());
+    client.get(url.clone()).send().await?;
+    let key = format!("{}:{}", GET, &Url::parse(&url)?);
+    assert!(manager.get(&key).await?.is_some());
+    let res = client.get(url).send().await?;
+    assert_eq!(res.headers().get(XCACHE).unwrap(), "HIT");
+
+    // Once the date has passed it's fetched again
+    let url = format!("{}/past", &mock_server.uri());
+    for _ in 0..2 {
+        let res = client.get(url.clone()).send().await?;
+        assert_eq!(res.headers().get(XCACHE).unwrap(), "MISS");
+    }
+    Ok(())
+}
+
+#[tokio::test]
+async fn response_header_rewrite_fn() -> Result<()> {
+    let mock_server = MockServer::start().await;
+    let m = Mock::given(method(GET))
+        .respond_with(ResponseTemplate::new(200).set_body_bytes(TEST_BODY))
+        .expect(1);
+    let _mock_guard = mock_server.register_as_scoped(m).await;
+    let url = format!("{}/", &mock_server.uri());
+
+    // Construct reqwest client that gives responses without caching headers a max-age
+    let client = ClientBuilder::new(Client::new())
+        .with(Cache(HttpCache {
+            mode: CacheMode::Default,
+            manager: MokaManager::default(),
+            options: HttpCacheOptions {
+                response_header_rewrite_fn: Some(Arc::new(
+                    |_: &http::request::Parts, res: &mut HttpResponse| {
+                        res.headers
+                            .entry("cache-control".to_string())
+                            .or_insert_with(|| "max-age=300".to_string());
+                    },
+                )),
+                ..Default::default()
+            },
+        }))
+        .build();
+
+    let res = client.get(url.clone()).send().await?;
+    assert_eq!(res.headers().get("cache-control").unwrap(), "max-age=300");
+
+    // The synthetic max-age makes the response cacheable
+    let res = client.get(url).send().await?;
+    assert_eq!(res.headers().get(XCACHE).unwrap(), "HIT");
+    assert_eq!(res.bytes().await?, TEST_BODY);
+    Ok(())
+}
+
+#[tokio::test]
+async fn vary_changed_on_not_modified() -> Result<()> {
+    use wiremock::matchers::header_exists;
+
+    let mock_server = MockServer::start().await;
+    let m = Mock::given(method(GET))
+        .and(header_exists("if-none-match"))
+        .respond_with(
+            ResponseTemplate::new(304)
+                .insert_header("cache-control", "max-age=3600")
+                .insert_header("etag", "\"1\"")
+                .insert_header("vary", "accept"),
+        )
+        .with_priority(1)
+        .expect(1);
+    mock_server.register(m).await;
+    let m = Mock::given(method(GET))
+        .respond_with(
+            ResponseTemplate::new(200)
+                .insert_header("cache-control", "no-cache")
+                .insert_header("etag", "\"1\"")
+                .set_body_bytes(TEST_BODY),
+        )
+        .expect(2);
+    mock_server.register(m).await;
+    let url = format!("{}/", &mock_server.uri());
+
+    // Construct reqwest client with cache defaults
+    let client = ClientBuilder::new(Client::new())
+        .with(Cache(HttpCache {
+            mode: CacheMode::Default,
+            manager: MokaManager::default(),
+            options: HttpCacheOptions::default(),
+        }))
+        .build();
+
+    // The revalidation adds a vary header to the cached response
+    for expected in ["MISS", "HIT", "HIT"] {
+        let res = client.get(url.clone()).header("accept", "a").send().await?;
+        assert_eq!(res.headers().get(XCACHE).unwrap(), expected);
+    }
+
+    // The stored policy varies on the header from then on
+    let res = client.get(url).header("accept", "b").send().await?;
+    assert_eq!(res.headers().get(XCACHE).unwrap(), "MISS");
+    Ok(())
+}
@@ -30,6 +30,23 @@
 //!     Ok(())
 //! }
 //! ```
+//!
+//! ## Overriding the cache mode
+//!
+//! surf requests don't carry typed extensions, so the cache mode is overridden on a
+//! per-request basis with the [`XCACHEMODE`] header instead. The header is removed
+//! before the request is sent on.
+//!
+//! ```no_run
+//! use http_cache_surf::XCACHEMODE;
+//!
+//! # #[async_std::main]
+//! # async fn main() -> surf::Result<()> {
+//! let req = surf::get("https://developer.mozilla.org/en-US/docs/Web/HTTP/Caching")
+//!     .header(XCACHEMODE, "no-store");
+//! # Ok(())
+//! # }
+//! ```
 mod error;
 
 use anyhow::anyhow;
@@ -48,8 +65,11 @@ use surf::{middleware::Next, Client, Request};
 use url::Url;
 
 pub use http_cache::{
-    CacheManager, CacheMode, CacheOptions, HttpCache, HttpCacheOptions,
-    HttpResponse,
+    CacheDecision, CacheDecisionReason, CacheEvent, CacheEvents, CacheManager,
+    CacheMode, CacheOptions, ClosureError, ClosureErrorPolicy,
+    DeserializeErrorPolicy, HttpCache, HttpCacheOptions,
+    HttpCacheOptionsBuilder, HttpResponse, Refresher, StaleReason,
+    UnrefreshableKey,
 };
 
 #[cfg(feature = "manager-cacache")]
@@ -58,7 +78,34 @@ pub use http_cache::CACacheManager;
 
 #[cfg(feature = "manager-moka")]
 #[cfg_attr(docsrs, doc(cfg(feature = "manager-moka")))]
-pub use http_cache::{MokaCache, MokaCacheBuilder, MokaManager};
+pub use http_cache::{
+    MokaCache, MokaCacheBuilder, MokaManager, MokaRemovalCause,
+};
+
+#[cfg(feature = "manager-memory")]
+#[cfg_attr(docsrs, doc(cfg(feature = "manager-memory")))]
+pub use http_cache::MemoryManager;
+
+/// `x-http-cache-mode` request header: overrides the cache mode for the request, taking the
+/// kebab-case name of a [`CacheMode`] variant, e.g. `no-store` or `only-if-cached`.
+/// Unknown values are ignored.
+pub const XCACHEMODE: &str = "x-http-cache-mode";
+
+// The cache mode named by the value of the `x-http-cache-mode` header
+fn parse_cache_mode(value: &str) -> Option<CacheMode> {
+    Some(match value.trim().to_ascii_lowercase().as_str() {
+        "default" => CacheMode::Default,
+        "no-store" => CacheMode::NoStore,
+        "reload" => CacheMode::Reload,
+        "no-cache" => CacheMode::NoCache,
+        "force-cache" => CacheMode::ForceCache,
+        "only-if-cached" => CacheMode::OnlyIfCached,
+        "ignore-rules" => CacheMode::IgnoreRules,
+        "stale-while-revalidate" => CacheMode::StaleWhileRevalidate,
+        "force-refresh" => CacheMode::ForceRefresh,
+        _ => return None,
+    })
+}
 
 /// Wrapper for [`HttpCache`]
 #[derive(Debug)]
@@ -69,10 +116,14 @@ pub(crate) struct SurfMiddleware<'a> {
     pub req: Request,
     pub client: Client,
     pub next: Next<'a>,
+    pub mode: Option<CacheMode>,
 }
 
 #[async_trait::async_trait]
 impl Middleware for SurfMiddleware<'_> {
+    fn overridden_cache_mode(&self) -> Option<CacheMode> {
+        self.mode
+    }
     fn is_method_get_head(&self) -> bool {
         self.req.method() == Method::Get || self.req.method() == Method::Head
     }
@@ -128,6 +179,8 @@ impl Middleware for SurfMiddleware<'_> {
         Ok(self.req.method().as_ref().to_string())
     }
     async fn remote_fetch(&mut self) -> Result<HttpResponse> {
+        // surf responses don't carry the url they were fetched from, so the
+        // request url is the closest thing to the effective url available
         let url = self.req.url().clone();
         let mut res =
             self.next.run(self.req.clone(), self.client.clone()).await?;
@@ -159,11 +212,14 @@ fn to_http_types_error(e: BoxError) -> http_types::Error {
 impl<T: CacheManager> surf::middleware::Middleware for Cache<T> {
     async fn handle(
         &self,
-        req: Request,
+        mut req: Request,
         client: Client,
         next: Next<'_>,
     ) -> std::result::Result<surf::Response, http_types::Error> {
-        let mut middleware = SurfMiddleware { req, client, next };
+        let mode = req
+            .remove_header(XCACHEMODE)
+            .and_then(|values| parse_cache_mode(values.last().as_str()));
+        let mut middleware = SurfMiddleware { req, client, next, mode };
         if self
             .0
             .can_cache_request(&middleware)
@@ -171,13 +227,14 @@ impl<T: CacheManager> surf::middleware::Middleware for Cache<T> {
         {
             let res =
                 self.0.run(middleware).await.map_err(to_http_types_error)?;
-            let mut converted = Response::new(StatusCode::Ok);
+            // Keep the status of the cached response, e.g. a 404 or a redirect
+            let status = StatusCode::try_from(res.status)?;
+            let mut converted = Response::new(status);
             for header in &res.headers {
                 let val =
                     HeaderValue::from_bytes(header.1.as_bytes().to_vec())?;
                 converted.insert_header(header.0.as_str(), val);
             }
-            converted.set_status(res.status.try_into()?);
             converted.set_version(Some(res.version.into()));
             converted.set_body(res.body);
             Ok(surf::Response::from(converted))
@@ -105,6 +105,7 @@ mod with_moka {
                 cache_mode_fn: None,
                 cache_bust: None,
                 cache_status_headers: true,
+                ..Default::default()
             },
         }));
 
@@ -491,6 +492,123 @@ mod with_moka {
         Ok(())
     }
 
+    #[async_std::test]
+    async fn preserves_cached_status() -> Result<()> {
+        let mock_server = MockServer::start().await;
+        let m = build_mock(CACHEABLE_PUBLIC, TEST_BODY, 200, 1);
+        let _mock_guard = mock_server.register_as_scoped(m).await;
+        let url = format!("{}/", &mock_server.uri());
+        let manager = MokaManager::default();
+        let req = Request::new(Method::Get, Url::parse(&url)?);
+
+        // Construct Surf client with cache defaults
+        let client = Client::new().with(Cache(HttpCache {
+            mode: CacheMode::Default,
+            manager: manager.clone(),
+            options: HttpCacheOptions::default(),
+        }));
+
+        // Cold pass to load cache
+        client.send(req.clone()).await?;
+
+        // Replace the cached object with a 404 response
+        let cache_key = format!("{}:{}", GET, &Url::parse(&url)?);
+        let (mut cached, policy) = manager.get(&cache_key).await?.unwrap();
+        cached.status = 404;
+        manager.put(cache_key, cached, policy).await?;
+
+        // Hot pass to make sure the cached status is served
+        let mut res = client.send(req).await?;
+        assert_eq!(res.status(), 404);
+        assert_eq!(res.body_bytes().await?, TEST_BODY);
+        assert_eq!(res.header(XCACHE).unwrap(), HIT);
+        Ok(())
+    }
+
+    #[async_std::test]
+    async fn cache_mode_header() -> Result<()> {
+        use crate::XCACHEMODE;
+
+        let mock_server = MockServer::start().await;
+        let m = build_mock(CACHEABLE_PUBLIC, TEST_BODY, 200, 2);
+        let _mock_guard = mock_server.register_as_scoped(m).await;
+        let url = format!("{}/", &mock_server.uri());
+        let manager = MokaManager::default();
+
+        // Construct Surf client with cache defaults
+        let client = Client::new().with(Cache(HttpCache {
+            mode: CacheMode::Default,
+            manager: manager.clone(),
+            options: HttpCacheOptions::default(),
+        }));
+
+        // The no-store mode from the header bypasses the cache
+        let mut req = Request::new(Method::Get, Url::parse(&url)?);
+        req.insert_header(XCACHEMODE, "no-store");
+        client.send(req).await?;
+        let data =
+            manager.get(&format!("{}:{}", GET, &Url::parse(&url)?)).await?;
+        assert!(data.is_none());
+
+        // Without the header the response is cached
+        let req = Request::new(Method::Get, Url::parse(&url)?);
+        client.send(req).await?;
+        let data =
+            manager.get(&format!("{}:{}", GET, &Url::parse(&url)?)).await?;
+        assert!(data.is_some());
+
+        // The header isn't sent upstream
+        let requests = mock_server.received_requests().await.unwrap();
+        assert!(requests
+            .iter()
+            .all(|req| !req.headers.contains_key(XCACHEMODE)));
+        Ok(())
+    }
+
+    #[async_std::test]
+    async fn preserves_cached_version() -> Result<()> {
+        let versions = [
+            (HttpVersion::Http09, http_types::Version::Http0_9),
+            (HttpVersion::Http10, http_types::Version::Http1_0),
+            (HttpVersion::Http11, http_types::Version::Http1_1),
+            (HttpVersion::H2, http_types::Version::Http2_0),
+            (HttpVersion::H3, http_types::Version::Http3_0),
+        ];
+        for (version, expected) in versions {
+            let mock_server = MockServer::start().await;
+            let m = build_mock(CACHEABLE_PUBLIC, TEST_BODY, 200, 1);
+            let _mock_guard = mock_server.register_as_scoped(m).await;
+            let url = format!("{}/", &mock_server.uri());
+            let manager = MokaManager::default();
+            let req = Request::new(Method::Get, Url::parse(&url)?);
+
+            // Construct Surf client with cache defaults
+            let client = Client::new().with(Cache(HttpCache {
+                mode: CacheMode::Default,
+                manager: manager.clone(),
+                options: HttpCacheOptions::default(),
+            }));
+
+            // Cold pass to load cache
+            client.send(req.clone()).await?;
+
+            // Replace the version of the cached object
+            let cache_key = format!("{}:{}", GET, &Url::parse(&url)?);
+            let (mut cached, policy) = manager.get(&cache_key).await?.unwrap();
+            cached.version = version;
+            manager.put(cache_key, cached, policy).await?;
+
+            // Hot pass to make sure the cached version is served as is
+            let res = client.send(req).await?;
+            assert_eq!(res.version(), Some(expected));
+            assert_eq!(res.header(XCACHE).unwrap(), HIT);
+
+            // The conversion back to an HttpVersion is lossless as well
+            assert_eq!(HttpVersion::try_from(expected)?, version);
+        }
+        Ok(())
+    }
+
     #[cfg(test)]
     mod only_if_cached_mode {
         use super::*;
@@ -523,6 +641,39 @@ mod with_moka {
             Ok(())
         }
 
+        #[async_std::test]
+        async fn miss_error() -> Result<()> {
+            let mock_server = MockServer::start().await;
+            let m = build_mock(CACHEABLE_PUBLIC, TEST_BODY, 200, 0);
+            let _mock_guard = mock_server.register_as_scoped(m).await;
+            let url = format!("{}/", &mock_server.uri());
+            let manager = MokaManager::default();
+            let req = Request::new(Method::Get, Url::parse(&url)?);
+
+            // Construct Surf client returning an error on only-if-cached misses
+            let client = Client::new().with(Cache(HttpCache {
+                mode: CacheMode::OnlyIfCached,
+                manager: manager.clone(),
+                options: HttpCacheOptions {
+                    only_if_cached_error: true,
+                    ..Default::default()
+                },
+            }));
+
+            // Should result in an error rather than a synthetic 504 response
+            let err = client.send(req).await.unwrap_err();
+            assert_eq!(
+                err.to_string(),
+                "No cached response for an only-if-cached request"
+            );
+
+            // Try to load cached object
+            let data =
+                manager.get(&format!("{}:{}", GET, &Url::parse(&url)?)).await?;
+            assert!(data.is_none());
+            Ok(())
+        }
+
         #[async_std::test]
         async fn hit() -> Result<()> {
             let mock_server = MockServer::start().await;
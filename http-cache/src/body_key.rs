@@ -0,0 +1,32 @@
+use std::sync::Arc;
+
+use serde_json::Value;
+use sha2::{Digest, Sha256};
+
+use crate::CacheKeyBody;
+
+/// Returns a [`CacheKeyBody`] that hashes the values found at the given
+/// [JSON pointers](https://datatracker.ietf.org/doc/html/rfc6901) in a JSON request body.
+/// Fields that aren't listed, such as request ids or timestamps, don't affect the cache key.
+/// Bodies that aren't valid JSON are hashed in full.
+#[cfg_attr(docsrs, doc(cfg(feature = "json-body-key")))]
+pub fn json_fields_key(pointers: &[&str]) -> CacheKeyBody {
+    let pointers: Vec<String> =
+        pointers.iter().map(|pointer| pointer.to_string()).collect();
+    Arc::new(move |_parts, body| {
+        let mut hasher = Sha256::new();
+        match serde_json::from_slice::<Value>(body) {
+            Ok(value) => {
+                for pointer in &pointers {
+                    let field = value.pointer(pointer).unwrap_or(&Value::Null);
+                    hasher.update(pointer.as_bytes());
+                    hasher.update(b"=");
+                    hasher.update(field.to_string().as_bytes());
+                    hasher.update(b"\n");
+                }
+            }
+            Err(_) => hasher.update(body),
+        }
+        Some(format!("{:x}", hasher.finalize()))
+    })
+}
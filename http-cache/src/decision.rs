@@ -0,0 +1,60 @@
+use std::sync::Arc;
+
+use crate::CacheMode;
+
+/// Explains why a response fetched from the origin was or wasn't cached
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CacheDecision {
+    /// The cache key of the request
+    pub cache_key: String,
+    /// The status code of the response
+    pub status: u16,
+    /// The `cache-control` directives of the response
+    pub directives: Vec<String>,
+    /// The cache mode the request was handled with
+    pub mode: CacheMode,
+    /// Whether the cache policy considered the response storable
+    pub is_storable: bool,
+    /// Whether the response was stored
+    pub cached: bool,
+    /// The reason for the decision
+    pub reason: CacheDecisionReason,
+}
+
+/// The reason a response was or wasn't cached
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum CacheDecisionReason {
+    /// The response was storable according to the cache policy
+    Storable,
+    /// The response was stored because of [`CacheMode::IgnoreRules`]
+    IgnoreRules,
+    /// Only responses to GET and HEAD requests are cached
+    NotGetOrHead,
+    /// The request was handled with [`CacheMode::NoStore`]
+    ModeNoStore,
+    /// The request was handled with [`CacheMode::Reload`]
+    ModeReload,
+    /// Only responses with a 200 status, or a 301 or 308 permanent redirect, are cached
+    UncacheableStatus,
+    /// The response has a `no-store` directive
+    NoStore,
+    /// The response has a `private` directive and the cache is shared
+    Private,
+    /// The cache policy considered the response not storable for another reason,
+    /// e.g. missing freshness information or an `authorization` header
+    NotStorable,
+    /// The request carried a cookie, see [`crate::HttpCacheOptions::skip_cache_on_cookie`]
+    Cookie,
+    /// The request carried an `authorization` header,
+    /// see [`crate::HttpCacheOptions::respect_request_authorization`]
+    Authorization,
+    /// The response has a `vary: *` header and can never be served from cache
+    VaryStar,
+    /// The response exceeds [`crate::HttpCacheOptions::max_headers`] or
+    /// [`crate::HttpCacheOptions::max_header_value_len`]
+    HeaderLimits,
+}
+
+/// A closure that is called with the [`CacheDecision`] for every response fetched from the origin
+pub type CacheDecisionFn = Arc<dyn Fn(&CacheDecision) + Send + Sync>;
@@ -29,3 +29,85 @@ impl fmt::Display for BadHeader {
 }
 
 impl std::error::Error for BadHeader {}
+
+/// Error type wrapping an error returned from one of the fallible
+/// [`crate::HttpCacheOptions`] closures
+#[derive(Debug)]
+pub struct ClosureError(pub BoxError);
+
+impl fmt::Display for ClosureError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "Cache option closure failed: {}", self.0)
+    }
+}
+
+impl std::error::Error for ClosureError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        Some(self.0.as_ref())
+    }
+}
+
+/// Error type for operations a cache manager doesn't support
+#[derive(Debug, Default, Copy, Clone)]
+pub struct Unsupported;
+
+impl fmt::Display for Unsupported {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.pad("Operation not supported by this cache manager")
+    }
+}
+
+impl std::error::Error for Unsupported {}
+
+/// Error type for cache entries that couldn't be encrypted or decrypted,
+/// e.g. because they were written with a different key
+#[derive(Debug, Default, Copy, Clone)]
+pub struct EncryptionError;
+
+impl fmt::Display for EncryptionError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.pad("Error encrypting or decrypting a cache entry")
+    }
+}
+
+impl std::error::Error for EncryptionError {}
+
+/// Error type for [`crate::CacheMode::OnlyIfCached`] requests without a cached response,
+/// returned instead of a `504` response when [`crate::HttpCacheOptions::only_if_cached_error`] is set
+#[derive(Debug, Default, Copy, Clone)]
+pub struct NotCached;
+
+impl fmt::Display for NotCached {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.pad("No cached response for an only-if-cached request")
+    }
+}
+
+impl std::error::Error for NotCached {}
+
+/// Error type for a cache manager operation that didn't complete in time,
+/// see [`crate::TimeoutManager`]
+#[derive(Debug, Default, Copy, Clone)]
+pub struct Timeout;
+
+impl fmt::Display for Timeout {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.pad("Cache manager operation timed out")
+    }
+}
+
+impl std::error::Error for Timeout {}
+
+/// Error type for a cache key a [`crate::Refresher`] can't find the url of, holding the key.
+/// Only keys in the default `GET:<url>` form can be refreshed by key, see
+/// [`crate::HttpCache::refresher`] for refreshing by url instead.
+#[derive(Debug, Clone)]
+pub struct UnrefreshableKey(pub String);
+
+impl fmt::Display for UnrefreshableKey {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "Cache key can't be refreshed: {}", self.0)
+    }
+}
+
+impl std::error::Error for UnrefreshableKey {}
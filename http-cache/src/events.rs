@@ -0,0 +1,164 @@
+use std::{
+    collections::VecDeque,
+    fmt,
+    pin::Pin,
+    sync::{Arc, Mutex, MutexGuard, Weak},
+    task::{Context, Poll, Waker},
+};
+
+use futures::Stream;
+
+/// An event published as the cache handles a request.
+/// The contained value is the cache key the event relates to.
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum CacheEvent {
+    /// A response was served from the cache
+    Hit(String),
+    /// No response was found in the cache
+    Miss(String),
+    /// A response was written to the cache
+    Store(String),
+    /// A cached response was removed following a non GET/HEAD request,
+    /// it's only published when there was a response to remove
+    Evict(String),
+    /// A cached response was removed by the [`crate::CacheBust`] closure
+    Bust(String),
+    /// Refreshing a stale response served by [`crate::CacheMode::StaleWhileRevalidate`]
+    /// failed, the stale response was still served, or a [`crate::Refresher`] failed
+    /// to refresh a response
+    RefreshFailed(String),
+}
+
+/// A runtime agnostic broadcast channel for [`CacheEvent`]s.
+/// Clones share the same set of subscribers.
+///
+/// Each subscriber buffers up to a capacity of events, 1024 by default, see
+/// [`CacheEvents::with_capacity`]. Like `tokio::sync::broadcast`, a subscriber that falls
+/// behind loses the oldest events rather than holding up the cache or using more memory.
+#[derive(Clone)]
+pub struct CacheEvents {
+    shared: Arc<Shared>,
+}
+
+// The subscribers' queues, they're ended once every clone of the channel is dropped
+struct Shared {
+    subscribers: Mutex<Vec<Weak<Mutex<Queue>>>>,
+    capacity: usize,
+}
+
+impl Drop for Shared {
+    fn drop(&mut self) {
+        for queue in lock(&self.subscribers).iter().filter_map(Weak::upgrade) {
+            if let Some(waker) = lock(&queue).waker.take() {
+                waker.wake();
+            }
+        }
+    }
+}
+
+#[derive(Default)]
+struct Queue {
+    events: VecDeque<CacheEvent>,
+    waker: Option<Waker>,
+}
+
+// A panic while a lock was held can't leave the queues inconsistent
+fn lock<T>(mutex: &Mutex<T>) -> MutexGuard<'_, T> {
+    mutex.lock().unwrap_or_else(|e| e.into_inner())
+}
+
+// The stream returned by `CacheEvents::subscribe`
+struct Subscription {
+    queue: Arc<Mutex<Queue>>,
+    channel: Weak<Shared>,
+}
+
+impl Stream for Subscription {
+    type Item = CacheEvent;
+
+    fn poll_next(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+    ) -> Poll<Option<CacheEvent>> {
+        let mut queue = lock(&self.queue);
+        if let Some(event) = queue.events.pop_front() {
+            return Poll::Ready(Some(event));
+        }
+        if self.channel.strong_count() == 0 {
+            return Poll::Ready(None);
+        }
+        queue.waker = Some(cx.waker().clone());
+        Poll::Pending
+    }
+}
+
+impl Default for CacheEvents {
+    fn default() -> Self {
+        Self::with_capacity(1024)
+    }
+}
+
+impl fmt::Debug for CacheEvents {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("CacheEvents")
+            .field("subscribers", &self.subscriber_count())
+            .field("capacity", &self.shared.capacity)
+            .finish()
+    }
+}
+
+impl CacheEvents {
+    /// Create a new channel whose subscribers each buffer up to `capacity` events,
+    /// dropping the oldest once it's reached
+    ///
+    /// # Panics
+    ///
+    /// Panics if `capacity` is 0.
+    #[must_use]
+    pub fn with_capacity(capacity: usize) -> Self {
+        assert!(capacity > 0, "capacity must be greater than 0");
+        Self {
+            shared: Arc::new(Shared {
+                subscribers: Mutex::default(),
+                capacity,
+            }),
+        }
+    }
+
+    /// Returns a stream that receives the events published from now on,
+    /// it ends once every clone of the channel has been dropped
+    pub fn subscribe(&self) -> impl Stream<Item = CacheEvent> {
+        let queue = Arc::new(Mutex::new(Queue::default()));
+        lock(&self.shared.subscribers).push(Arc::downgrade(&queue));
+        Subscription { queue, channel: Arc::downgrade(&self.shared) }
+    }
+
+    /// Sends the event to all subscribers, dropping any that have gone away.
+    /// A subscriber whose buffer is full loses its oldest event.
+    pub fn publish(&self, event: CacheEvent) {
+        lock(&self.shared.subscribers).retain(|queue| {
+            let Some(queue) = queue.upgrade() else {
+                return false;
+            };
+            let mut queue = lock(&queue);
+            if queue.events.len() == self.shared.capacity {
+                queue.events.pop_front();
+            }
+            queue.events.push_back(event.clone());
+            if let Some(waker) = queue.waker.take() {
+                waker.wake();
+            }
+            true
+        });
+    }
+
+    /// Returns the number of active subscribers
+    #[must_use]
+    pub fn subscriber_count(&self) -> usize {
+        lock(&self.shared.subscribers)
+            .iter()
+            .filter(|queue| queue.strong_count() > 0)
+            .count()
+    }
+}
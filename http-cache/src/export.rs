@@ -0,0 +1,76 @@
+use std::collections::HashMap;
+
+use base64::{engine::general_purpose::STANDARD, Engine};
+use futures::io::{AsyncBufRead, AsyncBufReadExt, AsyncWrite, AsyncWriteExt};
+use http_cache_semantics::CachePolicy;
+use serde::{Deserialize, Serialize};
+use url::Url;
+
+use crate::{CacheManager, HttpResponse, HttpVersion, Result};
+
+/// A line of an export, the body is base64 encoded so binary data survives JSON
+#[derive(Debug, Deserialize, Serialize)]
+struct Entry {
+    key: String,
+    response: Response,
+    policy: CachePolicy,
+}
+
+#[derive(Debug, Deserialize, Serialize)]
+struct Response {
+    body: String,
+    headers: HashMap<String, String>,
+    status: u16,
+    url: Url,
+    version: HttpVersion,
+}
+
+/// Writes a cached response and policy as a line of JSON
+pub(crate) async fn write_entry(
+    writer: &mut (dyn AsyncWrite + Send + Unpin),
+    key: String,
+    response: HttpResponse,
+    policy: CachePolicy,
+) -> Result<()> {
+    let entry = Entry {
+        key,
+        response: Response {
+            body: STANDARD.encode(response.body),
+            headers: response.headers,
+            status: response.status,
+            url: response.url,
+            version: response.version,
+        },
+        policy,
+    };
+    let mut line = serde_json::to_vec(&entry)?;
+    line.push(b'\n');
+    writer.write_all(&line).await?;
+    Ok(())
+}
+
+/// Stores every line written by [`write_entry`] with the manager,
+/// returning the number of entries imported
+pub(crate) async fn import_entries<M: CacheManager + ?Sized>(
+    manager: &M,
+    reader: &mut (dyn AsyncBufRead + Send + Unpin),
+) -> Result<usize> {
+    let mut imported = 0;
+    let mut line = String::new();
+    while reader.read_line(&mut line).await? > 0 {
+        if !line.trim().is_empty() {
+            let entry: Entry = serde_json::from_str(&line)?;
+            let response = HttpResponse {
+                body: STANDARD.decode(entry.response.body)?,
+                headers: entry.response.headers,
+                status: entry.response.status,
+                url: entry.response.url,
+                version: entry.response.version,
+            };
+            manager.put(entry.key, response, entry.policy).await?;
+            imported += 1;
+        }
+        line.clear();
+    }
+    Ok(imported)
+}
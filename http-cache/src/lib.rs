@@ -18,7 +18,8 @@
 //!
 //! ## Features
 //!
-//! The following features are available. By default `manager-cacache` and `cacache-async-std` are enabled.
+//! The following features are available. By default `manager-cacache`, `cacache-async-std`, `sha2`
+//! and `futures-timer` are enabled.
 //!
 //! - `manager-cacache` (default): enable [cacache](https://github.com/zkat/cacache-rs),
 //! a high-performance disk cache, backend manager.
@@ -26,26 +27,74 @@
 //! - `cacache-tokio` (disabled): enable [tokio](https://github.com/tokio-rs/tokio) runtime support for cacache.
 //! - `manager-moka` (disabled): enable [moka](https://github.com/moka-rs/moka),
 //! a high-performance in-memory cache, backend manager.
+//! - `manager-memory` (disabled): enable `MemoryManager`, a dependency free in-memory
+//! backend manager for targets without a filesystem, like `wasm32-unknown-unknown`.
 //! - `with-http-types` (disabled): enable [http-types](https://github.com/http-rs/http-types)
 //! type conversion support
+//! - `json-body-key` (disabled): enable [`json_fields_key`], a [`CacheKeyBody`] helper
+//! that keys requests on selected fields of a JSON body.
+//! - `export` (disabled): enable [`CacheManager::export`] and [`CacheManager::import`]
+//! for the provided backend managers.
+//! - `encryption` (disabled): enable `EncryptedManager`, a wrapper that encrypts
+//! the entries of another manager at rest.
+//! - `bincode` (disabled): enable [`serialize_entry`] and [`deserialize_entry`], also
+//! enabled by `manager-cacache`, `manager-moka` and `encryption`.
+//! - `sha2` (default): enable the SHA-256 based `hash_keys` and `verify_body_checksum`
+//! options of [`HttpCacheOptions`], also enabled by `json-body-key`.
+//! - `futures-timer` (default): enable the runtime-agnostic default timer of `RetryingManager`,
+//! `TimeoutManager` and [`Refresher`]. Without it, a timer has to be set with their `with_sleeper`.
+#[cfg(feature = "json-body-key")]
+mod body_key;
+mod decision;
 mod error;
+mod events;
+#[cfg(feature = "export")]
+mod export;
 mod managers;
+mod refresher;
+mod single_flight;
 
 use std::{
-    collections::HashMap,
+    collections::{HashMap, HashSet},
     convert::TryFrom,
     fmt::{self, Debug},
+    future::Future,
     str::FromStr,
     sync::Arc,
-    time::SystemTime,
+    time::{Duration, SystemTime},
 };
 
-use http::{header::CACHE_CONTROL, request, response, StatusCode};
-use http_cache_semantics::{AfterResponse, BeforeRequest, CachePolicy};
+use bytes::Bytes;
+use futures::io::{AsyncBufRead, AsyncWrite};
+use http::{
+    header::{
+        HeaderName, ACCESS_CONTROL_MAX_AGE, AGE, AUTHORIZATION, CACHE_CONTROL,
+        CONTENT_LENGTH, CONTENT_LOCATION, COOKIE, ETAG, EXPIRES, LAST_MODIFIED,
+        PRAGMA, RANGE, TRANSFER_ENCODING, VARY, VIA,
+    },
+    request, response, StatusCode,
+};
+use http_cache_semantics::{
+    AfterResponse, BeforeRequest, CachePolicy, RequestLike,
+};
 use serde::{Deserialize, Serialize};
+#[cfg(feature = "sha2")]
+use sha2::{Digest, Sha256};
+pub use single_flight::InFlight;
+use single_flight::Join;
 use url::Url;
 
-pub use error::{BadHeader, BadVersion, BoxError, Result};
+pub use error::{
+    BadHeader, BadVersion, BoxError, ClosureError, EncryptionError, NotCached,
+    Result, Timeout, UnrefreshableKey, Unsupported,
+};
+
+pub use decision::{CacheDecision, CacheDecisionFn, CacheDecisionReason};
+pub use events::{CacheEvent, CacheEvents};
+pub use refresher::Refresher;
+
+#[cfg(feature = "json-body-key")]
+pub use body_key::json_fields_key;
 
 #[cfg(feature = "manager-cacache")]
 pub use managers::cacache::CACacheManager;
@@ -53,10 +102,27 @@ pub use managers::cacache::CACacheManager;
 #[cfg(feature = "manager-moka")]
 pub use managers::moka::MokaManager;
 
+#[cfg(feature = "manager-memory")]
+pub use managers::memory::MemoryManager;
+
+pub use managers::retrying::{RetryPredicate, RetryingManager, Sleeper};
+
+pub use managers::timeout::{TimeoutListener, TimeoutManager};
+
+pub use managers::namespaced::NamespacedManager;
+
+pub use managers::size_routing::SizeRoutingManager;
+
+#[cfg(feature = "encryption")]
+pub use managers::encrypted::EncryptedManager;
+
 // Exposing the moka cache for convenience, renaming to avoid naming conflicts
 #[cfg(feature = "manager-moka")]
 #[cfg_attr(docsrs, doc(cfg(feature = "manager-moka")))]
-pub use moka::future::{Cache as MokaCache, CacheBuilder as MokaCacheBuilder};
+pub use moka::{
+    future::{Cache as MokaCache, CacheBuilder as MokaCacheBuilder},
+    notification::RemovalCause as MokaRemovalCause,
+};
 
 // Custom headers used to indicate cache status (hit or miss)
 /// `x-cache` header: Value will be HIT if the response was served from cache, MISS if not
@@ -64,9 +130,17 @@ pub const XCACHE: &str = "x-cache";
 /// `x-cache-lookup` header: Value will be HIT if a response existed in cache, MISS if not
 pub const XCACHELOOKUP: &str = "x-cache-lookup";
 
+// Internal header holding the SHA-256 of a stored body, see
+// `HttpCacheOptions::verify_body_checksum`
+const XCHECKSUM: &str = "x-http-cache-checksum";
+
+// Suffix of a busted key that removes every key starting with the rest of it,
+// see `invalidate_path_prefix`
+const BUST_PREFIX: char = '*';
+
 /// Represents a basic cache status
 /// Used in the custom headers `x-cache` and `x-cache-lookup`
-#[derive(Debug, Copy, Clone)]
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
 pub enum HitOrMiss {
     /// Yes, there was a hit
     HIT,
@@ -83,6 +157,16 @@ impl fmt::Display for HitOrMiss {
     }
 }
 
+/// How the cache handled a request, returned by [`HttpCache::run_with_status`].
+/// The `x-cache` and `x-cache-lookup` headers are set from it.
+#[derive(Debug, Default, Copy, Clone, PartialEq, Eq)]
+pub struct HitStatus {
+    /// Whether the response was served from the cache, [`None`] when the cache was bypassed
+    pub cache: Option<HitOrMiss>,
+    /// Whether a cached response was found for the request, [`None`] when the cache was bypassed
+    pub lookup: Option<HitOrMiss>,
+}
+
 /// Represents an HTTP version
 #[derive(Debug, Copy, Clone, PartialEq, Eq, Deserialize, Serialize)]
 #[non_exhaustive]
@@ -125,7 +209,8 @@ pub struct HttpResponse {
     pub headers: HashMap<String, String>,
     /// HTTP response status code
     pub status: u16,
-    /// HTTP response url
+    /// HTTP response url. This is the effective url after any redirects when the
+    /// client exposes it, otherwise the request url.
     pub url: Url,
     /// HTTP response version
     pub version: HttpVersion,
@@ -140,7 +225,7 @@ impl HttpResponse {
             let headers = converted.headers_mut();
             for header in &self.headers {
                 headers.insert(
-                    http::header::HeaderName::from_str(header.0.as_str())?,
+                    HeaderName::from_str(header.0.as_str())?,
                     http::HeaderValue::from_str(header.1.as_str())?,
                 );
             }
@@ -148,6 +233,54 @@ impl HttpResponse {
         Ok(converted.into_parts().0)
     }
 
+    /// Converts the response into an `http::Response`, returning an error
+    /// for any header that isn't a valid header name or value
+    pub fn into_http_response(self) -> Result<http::Response<Vec<u8>>> {
+        let mut converted = http::Response::builder()
+            .status(self.status)
+            .version(self.version.into())
+            .body(self.body)?;
+        {
+            let headers = converted.headers_mut();
+            for (name, value) in &self.headers {
+                headers.insert(
+                    HeaderName::from_str(name)?,
+                    http::HeaderValue::from_str(value)?,
+                );
+            }
+        }
+        Ok(converted)
+    }
+
+    /// Converts the response into an `http::Response` with a [`Bytes`] body,
+    /// see [`HttpResponse::into_http_response`]
+    pub fn into_http_response_bytes(self) -> Result<http::Response<Bytes>> {
+        Ok(self.into_http_response()?.map(Bytes::from))
+    }
+
+    /// Creates a response from an `http::Response` with a [`Bytes`] body. It doesn't
+    /// carry the url it was fetched from, so that's passed in. The body is kept as it is,
+    /// an error is returned for a header value that isn't visible ASCII.
+    pub fn from_http_response(
+        response: http::Response<Bytes>,
+        url: Url,
+    ) -> Result<Self> {
+        let (parts, body) = response.into_parts();
+        let mut headers = HashMap::new();
+        for (name, value) in &parts.headers {
+            headers
+                .insert(name.as_str().to_owned(), value.to_str()?.to_owned());
+        }
+        Ok(Self {
+            // Doesn't copy when the body isn't shared
+            body: body.into(),
+            headers,
+            status: parts.status.as_u16(),
+            url,
+            version: parts.version.try_into()?,
+        })
+    }
+
     /// Returns the status code of the warning header if present
     #[must_use]
     pub fn warning_code(&self) -> Option<usize> {
@@ -156,6 +289,58 @@ impl HttpResponse {
         })
     }
 
+    /// Returns the value of the age header if present, the time the response
+    /// has spent in caches. It is accounted for by the cache policy and updated
+    /// when a fresh response is served from the cache.
+    #[must_use]
+    pub fn age(&self) -> Option<Duration> {
+        self.headers
+            .get(AGE.as_str())
+            .and_then(|age| age.trim().parse().ok())
+            .map(Duration::from_secs)
+    }
+
+    /// Returns the value of the etag header, including the `W/` prefix of weak etags
+    #[must_use]
+    pub fn etag(&self) -> Option<&str> {
+        self.headers.get(ETAG.as_str()).map(|etag| etag.trim())
+    }
+
+    /// Returns the time from the last-modified header,
+    /// or [`None`] if it is missing or not a valid HTTP date
+    #[must_use]
+    pub fn last_modified(&self) -> Option<SystemTime> {
+        self.headers
+            .get(LAST_MODIFIED.as_str())
+            .and_then(|date| httpdate::parse_http_date(date.trim()).ok())
+    }
+
+    /// Checks if the etag of the response matches an `if-none-match` header value,
+    /// using the weak comparison the header calls for so weak etags match their strong form.
+    /// (https://www.rfc-editor.org/rfc/rfc9110#section-13.1.2)
+    #[must_use]
+    pub fn matches_validator(&self, if_none_match: &str) -> bool {
+        let Some(etag) = self.etag() else {
+            return false;
+        };
+        let opaque = |tag: &str| {
+            let tag = tag.trim();
+            tag.strip_prefix("W/").unwrap_or(tag).to_string()
+        };
+        let etag = opaque(etag);
+        if_none_match
+            .split(',')
+            .any(|tag| tag.trim() == "*" || opaque(tag) == etag)
+    }
+
+    // Sets the age header to the current age of the cached response
+    fn update_age(&mut self, policy: &CachePolicy) {
+        self.headers.insert(
+            AGE.to_string(),
+            policy.age(SystemTime::now()).as_secs().to_string(),
+        );
+    }
+
     /// Adds a warning header to a response
     pub fn add_warning(&mut self, url: &Url, code: usize, message: &str) {
         // warning    = "warning" ":" 1#warning-value
@@ -167,12 +352,14 @@ impl HttpResponse {
         // warn-text  = quoted-string
         // warn-date  = <"> HTTP-date <">
         // (https://tools.ietf.org/html/rfc2616#section-14.46)
+        // URLs without a host, e.g. `file:` URLs, fall back to a "-" pseudonym
+        let warn_agent = url.host_str().unwrap_or("-");
         self.headers.insert(
             "warning".to_string(),
             format!(
                 "{} {} {:?} \"{}\"",
                 code,
-                url.host().expect("Invalid URL"),
+                warn_agent,
                 message,
                 httpdate::fmt_http_date(SystemTime::now())
             ),
@@ -195,6 +382,26 @@ impl HttpResponse {
         Ok(())
     }
 
+    /// Sets the Content-Length header to the length of the body, as a stored
+    /// length can go stale when headers are updated from a revalidation.
+    /// Responses without a body or with chunked transfer encoding are left as is.
+    pub fn update_content_length(&mut self) {
+        if self.body.is_empty() {
+            return;
+        }
+        let chunked =
+            self.headers.get(TRANSFER_ENCODING.as_str()).map_or(false, |val| {
+                val.split(',')
+                    .any(|coding| coding.trim().eq_ignore_ascii_case("chunked"))
+            });
+        if !chunked {
+            self.headers.insert(
+                CONTENT_LENGTH.to_string(),
+                self.body.len().to_string(),
+            );
+        }
+    }
+
     /// Checks if the Cache-Control header contains the must-revalidate directive
     #[must_use]
     pub fn must_revalidate(&self) -> bool {
@@ -203,6 +410,125 @@ impl HttpResponse {
         })
     }
 
+    /// Checks if the Cache-Control header contains the immutable directive
+    #[must_use]
+    pub fn is_immutable(&self) -> bool {
+        self.has_cache_control_directive("immutable")
+    }
+
+    /// Checks if the Cache-Control header contains the no-transform directive,
+    /// meaning the body must not be altered (e.g. recompressed) when stored
+    #[must_use]
+    pub fn no_transform(&self) -> bool {
+        self.has_cache_control_directive("no-transform")
+    }
+
+    /// Checks if the Cache-Control header contains the public directive
+    #[must_use]
+    pub fn is_public(&self) -> bool {
+        self.has_cache_control_directive("public")
+    }
+
+    /// Checks if the Cache-Control header contains the must-understand directive, meaning
+    /// the response may only be stored by a cache that understands its status code
+    /// (https://www.rfc-editor.org/rfc/rfc9111#section-5.2.2.3)
+    #[must_use]
+    pub fn must_understand(&self) -> bool {
+        self.has_cache_control_directive("must-understand")
+    }
+
+    /// Checks if the Vary header contains `*`, meaning the response varies on
+    /// more than request headers and can never be served from cache
+    /// (https://www.rfc-editor.org/rfc/rfc9110#section-12.5.5)
+    #[must_use]
+    pub fn has_vary_star(&self) -> bool {
+        self.headers
+            .get(VARY.as_str())
+            .map_or(false, |val| val.split(',').any(|name| name.trim() == "*"))
+    }
+
+    /// Removes the headers named by a qualified `no-cache` or `private` directive, such as
+    /// `no-cache="set-cookie"`, along with the directive itself. The named headers must
+    /// not be reused without revalidation, but the rest of the response can be.
+    /// (https://www.rfc-editor.org/rfc/rfc9111#section-5.2.2.4)
+    pub fn apply_qualified_directives(&mut self) {
+        let Some(cache_control) =
+            self.headers.get(CACHE_CONTROL.as_str()).cloned()
+        else {
+            return;
+        };
+        let directives = split_directives(&cache_control);
+        let mut fields = Vec::new();
+        let mut kept = Vec::new();
+        for directive in &directives {
+            match qualified_fields(directive) {
+                Some(named) => fields.extend(named),
+                None => kept.push(*directive),
+            }
+        }
+        if kept.len() == directives.len() {
+            return;
+        }
+        if kept.is_empty() {
+            self.headers.remove(CACHE_CONTROL.as_str());
+        } else {
+            self.headers.insert(CACHE_CONTROL.to_string(), kept.join(", "));
+        }
+        self.headers.retain(|name, _| {
+            !fields.iter().any(|field| field.eq_ignore_ascii_case(name))
+        });
+    }
+
+    fn has_qualified_directives(&self) -> bool {
+        self.headers.get(CACHE_CONTROL.as_str()).map_or(false, |val| {
+            split_directives(val)
+                .into_iter()
+                .any(|directive| qualified_fields(directive).is_some())
+        })
+    }
+
+    fn remove_cache_control_directive(&mut self, directive: &str) {
+        let Some(cache_control) =
+            self.headers.get(CACHE_CONTROL.as_str()).cloned()
+        else {
+            return;
+        };
+        let kept: Vec<&str> = split_directives(&cache_control)
+            .into_iter()
+            .filter(|d| !has_directive(d, directive))
+            .collect();
+        if kept.is_empty() {
+            self.headers.remove(CACHE_CONTROL.as_str());
+        } else {
+            self.headers.insert(CACHE_CONTROL.to_string(), kept.join(", "));
+        }
+    }
+
+    fn has_cache_control_directive(&self, directive: &str) -> bool {
+        self.headers
+            .get(CACHE_CONTROL.as_str())
+            .map_or(false, |val| has_directive(val, directive))
+    }
+
+    /// Appends this cache to the `via` header of the response,
+    /// preserving any value set upstream.
+    /// (https://www.rfc-editor.org/rfc/rfc7230#section-5.7.1)
+    pub fn add_via(&mut self, pseudonym: &str) {
+        let protocol = match self.version {
+            HttpVersion::Http09 => "0.9",
+            HttpVersion::Http10 => "1.0",
+            HttpVersion::Http11 => "1.1",
+            HttpVersion::H2 => "2",
+            HttpVersion::H3 => "3",
+        };
+        let received_by = format!("{protocol} {pseudonym}");
+        let via = match self.headers.get(VIA.as_str()) {
+            Some(upstream) => format!("{upstream}, {received_by}"),
+            None => received_by,
+        };
+        self.headers.insert(VIA.to_string(), via);
+    }
+
     /// Adds the custom `x-cache` header to the response
     pub fn cache_status(&mut self, hit_or_miss: HitOrMiss) {
         self.headers.insert(XCACHE.to_string(), hit_or_miss.to_string());
@@ -214,6 +540,208 @@ impl HttpResponse {
     }
 }
 
+// Whether a `cache-control` header value contains the directive
+fn has_directive(value: &str, directive: &str) -> bool {
+    value.split(',').any(|d| {
+        d.split('=')
+            .next()
+            .map_or(false, |name| name.trim().eq_ignore_ascii_case(directive))
+    })
+}
+
+// Whether a cached response is stale and past its `stale-while-revalidate` window,
+// so it's of no further use without going to the origin
+// (https://www.rfc-editor.org/rfc/rfc5861#section-3)
+#[cfg(any(feature = "manager-moka", feature = "manager-memory"))]
+fn is_expired(
+    res: &HttpResponse,
+    policy: &CachePolicy,
+    now: SystemTime,
+) -> bool {
+    let window = res
+        .headers
+        .get(CACHE_CONTROL.as_str())
+        .and_then(|val| {
+            val.split(',').find_map(|d| {
+                let (name, secs) = d.split_once('=')?;
+                name.trim()
+                    .eq_ignore_ascii_case("stale-while-revalidate")
+                    .then(|| secs.trim().parse::<u64>().ok())?
+            })
+        })
+        .unwrap_or(0);
+    stale_for_longer_than(policy, now, Duration::from_secs(window))
+}
+
+// Whether a response has been stale for longer than the duration, meaning it was
+// already stale that long ago. The policy can't tell for a time before the response
+// was received, in which case it's assumed not to have been.
+fn stale_for_longer_than(
+    policy: &CachePolicy,
+    now: SystemTime,
+    duration: Duration,
+) -> bool {
+    now.checked_sub(duration).map_or(false, |then| {
+        policy.age(now) - policy.age(then) >= duration && policy.is_stale(then)
+    })
+}
+
+// Splits a `cache-control` header value into its directives, keeping the commas
+// inside quoted values like `no-cache="set-cookie, set-cookie2"`
+fn split_directives(value: &str) -> Vec<&str> {
+    let mut directives = Vec::new();
+    let mut in_quotes = false;
+    let mut start = 0;
+    for (i, c) in value.char_indices() {
+        match c {
+            '"' => in_quotes = !in_quotes,
+            ',' if !in_quotes => {
+                directives.push(&value[start..i]);
+                start = i + 1;
+            }
+            _ => {}
+        }
+    }
+    directives.push(&value[start..]);
+    directives.into_iter().map(str::trim).filter(|d| !d.is_empty()).collect()
+}
+
+// The header names of a qualified `no-cache` or `private` directive
+fn qualified_fields(directive: &str) -> Option<Vec<&str>> {
+    let (name, value) = directive.split_once('=')?;
+    let name = name.trim();
+    if !(name.eq_ignore_ascii_case("no-cache")
+        || name.eq_ignore_ascii_case("private"))
+    {
+        return None;
+    }
+    Some(
+        value
+            .trim()
+            .trim_matches('"')
+            .split(',')
+            .map(str::trim)
+            .filter(|field| !field.is_empty())
+            .collect(),
+    )
+}
+
+// Hex encoded SHA-256 of a response body or cache key
+#[cfg(feature = "sha2")]
+fn checksum(body: &[u8]) -> String {
+    format!("{:x}", Sha256::digest(body))
+}
+
+// `request::Parts` isn't `Clone`, so the copy is left without the extensions
+fn copy_parts(parts: &request::Parts) -> Result<request::Parts> {
+    let mut req = http::Request::builder()
+        .method(parts.method.clone())
+        .uri(parts.uri.clone())
+        .version(parts.version)
+        .body(())?;
+    *req.headers_mut() = parts.headers.clone();
+    Ok(req.into_parts().0)
+}
+
+// A request that matches a cached response's url when `pre_key_request_fn` maps both
+// to the same one, as the policy otherwise only uses responses stored for the exact url
+struct KeyedRequest<'a> {
+    parts: &'a request::Parts,
+    canonical: http::Uri,
+    pre_key_request_fn: &'a PreKeyRequestFn,
+}
+
+impl<'a> KeyedRequest<'a> {
+    fn new(
+        parts: &'a request::Parts,
+        pre_key_request_fn: &'a PreKeyRequestFn,
+    ) -> Result<Self> {
+        let mut canonical = copy_parts(parts)?;
+        pre_key_request_fn(&mut canonical);
+        Ok(Self { parts, canonical: canonical.uri, pre_key_request_fn })
+    }
+}
+
+impl RequestLike for KeyedRequest<'_> {
+    fn uri(&self) -> http::Uri {
+        self.parts.uri.clone()
+    }
+
+    fn is_same_uri(&self, other: &http::Uri) -> bool {
+        if &self.parts.uri == other {
+            return true;
+        }
+        let Ok(mut stored) = copy_parts(self.parts) else {
+            return false;
+        };
+        stored.uri = other.clone();
+        (self.pre_key_request_fn)(&mut stored);
+        stored.uri == self.canonical
+    }
+
+    fn method(&self) -> &http::Method {
+        &self.parts.method
+    }
+
+    fn headers(&self) -> &http::HeaderMap {
+        &self.parts.headers
+    }
+}
+
+// The uri with a lowercase host, no default port and sorted query parameters,
+// relying on `Url` to normalize the host and port
+fn normalize_uri(uri: &http::Uri) -> String {
+    let mut url = match Url::parse(&uri.to_string()) {
+        Ok(url) => url,
+        Err(_) => return uri.to_string(),
+    };
+    if let Some(query) = url.query() {
+        let mut params: Vec<&str> = query.split('&').collect();
+        params.sort_unstable();
+        let query = params.join("&");
+        url.set_query(Some(&query));
+    }
+    url.to_string()
+}
+
+/// Serializes a cached response and its policy in the format the built-in managers store
+/// entries in, so a custom manager can share entries with them.
+///
+/// Available when any of the `manager-cacache`, `manager-moka` or `encryption` features
+/// is enabled, or `bincode` on its own.
+#[cfg(feature = "bincode")]
+#[cfg_attr(docsrs, doc(cfg(feature = "bincode")))]
+pub fn serialize_entry(
+    response: &HttpResponse,
+    policy: &CachePolicy,
+) -> Result<Vec<u8>> {
+    Ok(bincode::serialize(&(response, policy))?)
+}
+
+/// Deserializes an entry written by [`serialize_entry`] or one of the built-in managers
+#[cfg(feature = "bincode")]
+#[cfg_attr(docsrs, doc(cfg(feature = "bincode")))]
+pub fn deserialize_entry(bytes: &[u8]) -> Result<(HttpResponse, CachePolicy)> {
+    Ok(bincode::deserialize(bytes)?)
+}
+
+/// A predicate over a cache key and the cached response,
+/// see [`CacheManager::delete_matching`]
+pub type EntryPredicate = dyn Fn(&str, &HttpResponse) -> bool + Sync;
+
+/// A revalidation answered by the manager rather than the origin,
+/// see [`CacheManager::revalidate`]
+#[derive(Debug, Clone)]
+pub enum RevalidationOutcome {
+    /// The cached response is still valid. Handled like a `304 Not Modified` response
+    /// from the origin with these headers, which should include the response's validator,
+    /// such as its `etag`, and any caching headers that changed.
+    NotModified(HashMap<String, String>),
+    /// The resource has changed. Handled like a response from the origin,
+    /// replacing the cached response if it can be cached.
+    Modified(HttpResponse),
+}
+
 /// A trait providing methods for storing, reading, and removing cache records.
 #[async_trait::async_trait]
 pub trait CacheManager: Send + Sync + 'static {
@@ -231,6 +759,135 @@ pub trait CacheManager: Send + Sync + 'static {
     ) -> Result<HttpResponse>;
     /// Attempts to remove a record from cache.
     async fn delete(&self, cache_key: &str) -> Result<()>;
+    /// Attempts to pull several cached responses and related policies from cache.
+    /// Results are returned in the same order as the keys, with [`None`] for misses.
+    ///
+    /// The default implementation calls [`CacheManager::get`] for each key,
+    /// backends that support batched reads should override it.
+    async fn get_many(
+        &self,
+        cache_keys: &[String],
+    ) -> Result<Vec<Option<(HttpResponse, CachePolicy)>>> {
+        let mut results = Vec::with_capacity(cache_keys.len());
+        for cache_key in cache_keys {
+            results.push(self.get(cache_key).await?);
+        }
+        Ok(results)
+    }
+    /// Whether the manager can stream response bodies rather than buffering them.
+    /// None of the provided managers support streaming.
+    fn supports_streaming(&self) -> bool {
+        false
+    }
+    /// Writes every cached response and policy to the writer as JSON Lines,
+    /// which can be read back with [`CacheManager::import`].
+    ///
+    /// The default implementation returns an [`Unsupported`] error.
+    async fn export(
+        &self,
+        _writer: &mut (dyn AsyncWrite + Send + Unpin),
+    ) -> Result<()> {
+        Err(Box::new(Unsupported))
+    }
+    /// Stores every entry read from JSON Lines written by [`CacheManager::export`],
+    /// returning the number of entries imported.
+    ///
+    /// The default implementation returns an [`Unsupported`] error.
+    async fn import(
+        &self,
+        _reader: &mut (dyn AsyncBufRead + Send + Unpin),
+    ) -> Result<usize> {
+        Err(Box::new(Unsupported))
+    }
+    /// Lists the cache keys of every cached response, e.g. for debugging or admin tooling.
+    /// This visits the whole cache, so it may be expensive for large caches.
+    ///
+    /// The default implementation returns an [`Unsupported`] error.
+    async fn keys(&self) -> Result<Vec<String>> {
+        Err(Box::new(Unsupported))
+    }
+    /// Removes every cached response the predicate returns true for, given the
+    /// cache key and response, and returns the number of records removed.
+    ///
+    /// The default implementation returns an [`Unsupported`] error.
+    async fn delete_matching(
+        &self,
+        _predicate: &EntryPredicate,
+    ) -> Result<usize> {
+        Err(Box::new(Unsupported))
+    }
+    /// Removes every cached response that is stale and past any `stale-while-revalidate`
+    /// window, returning the number of records removed. Meant for periodic cleanup jobs.
+    ///
+    /// The default implementation returns an [`Unsupported`] error.
+    async fn drain_expired(&self) -> Result<usize> {
+        Err(Box::new(Unsupported))
+    }
+    /// Removes every cached response whose cache key starts with the prefix,
+    /// returning the number of records removed.
+    ///
+    /// The default implementation lists the keys with [`CacheManager::keys`]
+    /// and calls [`CacheManager::delete`] for each match.
+    async fn delete_prefix(&self, prefix: &str) -> Result<usize> {
+        let mut deleted = 0;
+        for cache_key in self.keys().await? {
+            if cache_key.starts_with(prefix) {
+                self.delete(&cache_key).await?;
+                deleted += 1;
+            }
+        }
+        Ok(deleted)
+    }
+    /// Called before a stale cached response is revalidated with the origin, with the
+    /// conditional request that would be sent. Returning an outcome answers the
+    /// revalidation without contacting the origin, e.g. for a backend that already
+    /// knows the resource's current validator from a request it made itself.
+    ///
+    /// The outcome is trusted as if it came from the origin, so only return one when
+    /// it's known to be current. The default implementation returns [`None`], always
+    /// revalidating with the origin.
+    async fn revalidate(
+        &self,
+        _cache_key: &str,
+        _parts: &request::Parts,
+    ) -> Result<Option<RevalidationOutcome>> {
+        Ok(None)
+    }
+    /// Refreshes the recency of an entry without changing it, for backends that evict
+    /// the least recently used entries first. Returns whether the entry exists.
+    ///
+    /// The default implementation reads the entry and writes it back.
+    async fn touch(&self, cache_key: &str) -> Result<bool> {
+        match self.get(cache_key).await? {
+            Some((response, policy)) => {
+                self.put(cache_key.to_string(), response, policy).await?;
+                Ok(true)
+            }
+            None => Ok(false),
+        }
+    }
+    /// Returns the cached response for the key, or awaits the future returned by
+    /// `compute` and caches its response and policy when there is none.
+    ///
+    /// The default implementation calls [`CacheManager::get`] then [`CacheManager::put`],
+    /// so concurrent callers may each run `compute`. Backends that can hold the entry
+    /// while it's computed should override it.
+    async fn get_or_put<F, Fut>(
+        &self,
+        cache_key: String,
+        compute: F,
+    ) -> Result<HttpResponse>
+    where
+        Self: Sized,
+        F: FnOnce() -> Fut + Send,
+        Fut: Future<Output = Result<(HttpResponse, CachePolicy)>> + Send,
+    {
+        if let Some((response, _)) = self.get(&cache_key).await? {
+            return Ok(response);
+        }
+        let (response, policy) = compute().await?;
+        self.put(cache_key, response, policy).await
+    }
 }
 
 /// Describes the functionality required for interfacing with HTTP client middleware
@@ -262,6 +919,15 @@ pub trait Middleware: Send {
     fn url(&self) -> Result<Url>;
     /// Attempts to determine the request method
     fn method(&self) -> Result<String>;
+    /// Returns the request body if it is available without consuming the request
+    fn body(&self) -> Option<&[u8]> {
+        None
+    }
+    /// Determines if the `x-cache` and `x-cache-lookup` headers should be left off
+    /// the response to this request, regardless of [`HttpCacheOptions::cache_status_headers`]
+    fn suppress_cache_headers(&self) -> bool {
+        false
+    }
     /// Attempts to fetch an upstream resource and return an [`HttpResponse`]
     async fn remote_fetch(&mut self) -> Result<HttpResponse>;
 }
@@ -300,6 +966,36 @@ pub enum CacheMode {
     /// not paying attention to staleness. If there was no response,
     /// it creates a normal request and updates the HTTP cache with the response.
     IgnoreRules,
+    /// Uses any response in the HTTP cache matching the request, even if it is stale.
+    /// A stale response is revalidated and the HTTP cache updated with the result,
+    /// but the cached response is what gets returned. If there was no response,
+    /// it creates a normal request and updates the HTTP cache with the response.
+    ///
+    /// Client middleware can't outlive the request it handles, so the revalidation
+    /// completes before the stale response is returned rather than in the background.
+    /// This keeps serving the cached content while the origin is failing or offline,
+    /// but doesn't save the latency of the revalidation request.
+    StaleWhileRevalidate,
+    /// Behaves as if there is no HTTP cache on the way to the network like [`CacheMode::Reload`],
+    /// but updates the HTTP cache with the response. If the request fails
+    /// (for example, if you're offline) the cached response is returned instead.
+    ForceRefresh,
+}
+
+impl TryFrom<(http::Response<Bytes>, Url)> for HttpResponse {
+    type Error = BoxError;
+
+    fn try_from((response, url): (http::Response<Bytes>, Url)) -> Result<Self> {
+        Self::from_http_response(response, url)
+    }
+}
+
+impl TryFrom<HttpResponse> for http::Response<Bytes> {
+    type Error = BoxError;
+
+    fn try_from(response: HttpResponse) -> Result<Self> {
+        response.into_http_response_bytes()
+    }
 }
 
 impl TryFrom<http::Version> for HttpVersion {
@@ -366,17 +1062,110 @@ pub use http_cache_semantics::CacheOptions;
 /// By default, the cache key is a combination of the request method and uri with a colon in between.
 pub type CacheKey = Arc<dyn Fn(&request::Parts) -> String + Send + Sync>;
 
+/// A closure that modifies a copy of the [`http::request::Parts`] the cache key is created from,
+/// see [`HttpCacheOptions::pre_key_request_fn`]. The copy doesn't have the request's extensions.
+pub type PreKeyRequestFn = Arc<dyn Fn(&mut request::Parts) + Send + Sync>;
+
+/// A closure that modifies a response fetched from the origin before its cache policy
+/// is created, see [`HttpCacheOptions::response_header_rewrite_fn`]
+pub type ResponseHeaderRewriteFn =
+    Arc<dyn Fn(&request::Parts, &mut HttpResponse) + Send + Sync>;
+
+/// Why a stale response was served
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum StaleReason {
+    /// The origin couldn't be reached or returned a server error while revalidating
+    RevalidationFailed,
+    /// The cache mode served the response without contacting the origin,
+    /// see [`CacheMode::ForceCache`], [`CacheMode::OnlyIfCached`] and [`CacheMode::IgnoreRules`]
+    Disconnected,
+    /// The cache mode served the stale response while refreshing it,
+    /// see [`CacheMode::StaleWhileRevalidate`]
+    StaleWhileRevalidate,
+}
+
+/// A closure that is called with the url and [`StaleReason`] when a stale response is served
+pub type StaleServedFn = Arc<dyn Fn(&Url, StaleReason) + Send + Sync>;
+
 /// A closure that takes [`http::request::Parts`] and returns a [`CacheMode`]
 pub type CacheModeFn = Arc<dyn Fn(&request::Parts) -> CacheMode + Send + Sync>;
 
 /// A closure that takes [`http::request::Parts`], [`Option<CacheKey>`], the default cache key ([`&str``]) and returns [`Vec<String>`] of keys to bust the cache for.
 /// An empty vector means that no cache busting will be performed.
+/// A key ending with `*` busts every key starting with the rest of it, see [`invalidate_path_prefix`].
 pub type CacheBust = Arc<
     dyn Fn(&request::Parts, &Option<CacheKey>, &str) -> Vec<String>
         + Send
         + Sync,
 >;
 
+/// Returns a [`CacheBust`] that, on a request with an unsafe method, removes the cached `GET`
+/// responses for every url on the request's host with a path starting with the prefix,
+/// whatever their query string. For example a `POST /items` invalidating both
+/// `GET /items` and `GET /items?page=2`.
+///
+/// Only the default cache keys are matched, the keys are removed with [`CacheManager::delete_prefix`].
+pub fn invalidate_path_prefix(prefix: impl Into<String>) -> CacheBust {
+    let prefix = prefix.into();
+    Arc::new(move |parts: &request::Parts, _, _| {
+        if parts.method.is_safe() {
+            return Vec::new();
+        }
+        let url = match (parts.uri.scheme_str(), parts.uri.authority()) {
+            (Some(scheme), Some(authority)) => {
+                format!("{scheme}://{authority}{prefix}")
+            }
+            _ => prefix.clone(),
+        };
+        vec![format!("GET:{url}{BUST_PREFIX}")]
+    })
+}
+
+/// A fallible [`CacheKey`], an error is handled according to [`ClosureErrorPolicy`].
+pub type TryCacheKey =
+    Arc<dyn Fn(&request::Parts) -> Result<String> + Send + Sync>;
+
+/// A fallible [`CacheModeFn`], an error is handled according to [`ClosureErrorPolicy`].
+pub type TryCacheModeFn =
+    Arc<dyn Fn(&request::Parts) -> Result<CacheMode> + Send + Sync>;
+
+/// A fallible [`CacheBust`], an error is handled according to [`ClosureErrorPolicy`].
+pub type TryCacheBust = Arc<
+    dyn Fn(&request::Parts, &Option<CacheKey>, &str) -> Result<Vec<String>>
+        + Send
+        + Sync,
+>;
+
+/// Determines how an error returned from a fallible closure is handled
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum ClosureErrorPolicy {
+    /// Fail the request with a [`ClosureError`]
+    #[default]
+    Fail,
+    /// Fetch the response without consulting or updating the cache
+    Bypass,
+}
+
+/// Determines how a cache manager handles a stored entry that fails to deserialize,
+/// e.g. because it is corrupted or was written in an older format
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum DeserializeErrorPolicy {
+    /// Return the error from [`CacheManager::get`]
+    #[default]
+    Error,
+    /// Treat the entry as a cache miss, leaving it in place
+    TreatAsMiss,
+    /// Delete the entry and treat it as a cache miss
+    DeleteAndMiss,
+}
+
+/// A closure that takes [`http::request::Parts`] and the request body and returns
+/// a [`String`] to be appended to the cache key, or [`None`] to leave the key as is.
+/// Only called when the client middleware can provide the request body.
+pub type CacheKeyBody =
+    Arc<dyn Fn(&request::Parts, &[u8]) -> Option<String> + Send + Sync>;
+
 /// Can be used to override the default [`CacheOptions`] and cache key.
 /// The cache key is a closure that takes [`http::request::Parts`] and returns a [`String`].
 #[derive(Clone)]
@@ -385,80 +1174,811 @@ pub struct HttpCacheOptions {
     pub cache_options: Option<CacheOptions>,
     /// Override the default cache key generator.
     pub cache_key: Option<CacheKey>,
+    /// Append a value derived from the request body to the cache key.
+    pub cache_key_body: Option<CacheKeyBody>,
     /// Override the default cache mode.
     pub cache_mode_fn: Option<CacheModeFn>,
     /// Bust the caches of the returned keys.
     pub cache_bust: Option<CacheBust>,
+    /// Fallible version of `cache_key`, takes precedence when set.
+    pub try_cache_key: Option<TryCacheKey>,
+    /// Fallible version of `cache_mode_fn`, takes precedence when set.
+    pub try_cache_mode_fn: Option<TryCacheModeFn>,
+    /// Fallible version of `cache_bust`, takes precedence when set.
+    pub try_cache_bust: Option<TryCacheBust>,
+    /// Determines how errors returned from the fallible closures are handled.
+    pub closure_error_policy: ClosureErrorPolicy,
     /// Determines if the cache status headers should be added to the response.
     pub cache_status_headers: bool,
+    /// Skip storing responses to requests carrying a `Cookie` header, unless
+    /// the response is explicitly marked `public`. Useful for shared caches
+    /// where such responses are likely personalized.
+    pub skip_cache_on_cookie: bool,
+    /// Only store responses to requests carrying an `Authorization` header when
+    /// the response has a `public`, `s-maxage` or `must-revalidate` directive,
+    /// whatever the cache mode or [`CacheOptions::shared`] setting.
+    /// (https://www.rfc-editor.org/rfc/rfc9111#section-3.5)
+    pub respect_request_authorization: bool,
+    /// Publish [`CacheEvent`]s to the subscribers of this channel.
+    pub cache_events: Option<CacheEvents>,
+    /// Only store the listed response headers, dropping all others.
+    /// Freshness is computed from the stored headers, so headers such as
+    /// `cache-control` should be included. The response returned from the
+    /// request that populated the cache is unaffected.
+    pub stored_header_allowlist: Option<Vec<HeaderName>>,
+    /// Share a single upstream fetch between concurrent cache misses for the same cache key.
+    /// Callers waiting on another request are served the response it stored, or fetch
+    /// their own when it wasn't stored, e.g. a `private` or `no-store` response.
+    pub single_flight: bool,
+    /// The fetches in flight when [`HttpCacheOptions::single_flight`] is enabled.
+    /// Caches built from clones of these options share their fetches.
+    pub in_flight: InFlight,
+    /// Append a `via` header identifying this cache, e.g. `via: 1.1 <pseudonym>`,
+    /// to responses returned from [`HttpCache::run`].
+    pub via_pseudonym: Option<String>,
+    /// Called with a [`CacheDecision`] explaining why each response fetched
+    /// from the origin was or wasn't cached.
+    pub on_cache_decision: Option<CacheDecisionFn>,
+    /// Called when a response is served with a `110 Response is stale`,
+    /// `111 Revalidation failed` or `112 Disconnected operation` warning,
+    /// e.g. to alert when the origin is down.
+    pub on_stale_served: Option<StaleServedFn>,
+    /// Response headers that are dropped before a response is stored, so
+    /// responses served from the cache lack them. Matched case-insensitively.
+    /// Defaults to the hop-by-hop headers and `set-cookie`, set to [`None`] to store every header.
+    /// The response returned from the request that populated the cache is unaffected.
+    pub strip_headers_before_cache: Option<Vec<String>>,
+    /// Treat the cache as shared between users, refusing to store `private` responses
+    /// and preferring `s-maxage` over `max-age` for freshness. Disabled by default, a private
+    /// cache. Takes the place of [`CacheOptions::shared`], which is ignored.
+    pub shared: bool,
+    /// Cache `206 Partial Content` responses under a key that includes the request's
+    /// `Range` header, so they're only served to requests for the same range.
+    /// Disabled by default, in which case partial responses aren't cached.
+    pub cache_range_requests: bool,
+    /// Normalize the url in the default cache key, lowercasing the host, removing
+    /// the default port and sorting the query parameters, so urls that only differ
+    /// in those respects share a cache entry. Disabled by default.
+    pub normalize_key: bool,
+    /// Store a SHA-256 checksum of the body with each cached response and verify it
+    /// when the response is read back, treating entries that don't match as a miss.
+    /// Guards against silent corruption of the backing store. Disabled by default.
+    #[cfg(feature = "sha2")]
+    #[cfg_attr(docsrs, doc(cfg(feature = "sha2")))]
+    pub verify_body_checksum: bool,
+    /// Cache responses with one of the [`HttpCacheOptions::negative_status_codes`] for
+    /// this long, whatever their caching headers, to shield the origin from repeated
+    /// requests while it's failing. Disabled by default.
+    pub negative_ttl: Option<Duration>,
+    /// The status codes cached for the [`HttpCacheOptions::negative_ttl`], e.g. 404 and 503.
+    pub negative_status_codes: HashSet<u16>,
+    /// Append the returned string to every cache key, whether it's the default key or
+    /// one from [`HttpCacheOptions::cache_key`], e.g. a version bumped on deploy so
+    /// none of the previously cached entries are used.
+    pub cache_key_suffix_fn: Option<CacheKey>,
+    /// Modify the request before its cache key is created, without replacing the
+    /// default key, e.g. to strip tracking query parameters like `utm_source` so
+    /// requests that only differ in them share an entry. A cached response is served
+    /// for any request the closure maps to the same url. Applies to a copy, the
+    /// request that's sent is left as it is.
+    pub pre_key_request_fn: Option<PreKeyRequestFn>,
+    /// Return a [`NotCached`] error for [`CacheMode::OnlyIfCached`] requests without a
+    /// cached response, rather than a synthetic `504 Gateway Timeout` response that can't
+    /// be told apart from one sent by the origin. Disabled by default.
+    pub only_if_cached_error: bool,
+    /// Cache the responses to `OPTIONS` requests, such as CORS preflights, which are
+    /// otherwise never cached. Their freshness is taken from `Access-Control-Max-Age`
+    /// when the response has no `max-age` of its own. Disabled by default.
+    pub cache_options_method: bool,
+    /// The longest a response may have been stale for and still be served without
+    /// being revalidated, such as by [`CacheMode::StaleWhileRevalidate`] or when the
+    /// origin can't be reached. Unlimited by default.
+    pub max_served_staleness: Option<Duration>,
+    /// The longest a response is considered fresh for, whatever the lifetime the
+    /// origin gave it. Older responses are revalidated before they're served.
+    /// Unlimited by default.
+    pub max_fresh_lifetime: Option<Duration>,
+    /// Request headers whose values are added to the default cache key, so requests
+    /// that differ in them get separate cache entries, e.g. `accept` for an API that
+    /// returns JSON or XML. Unlike the response's `Vary` header this doesn't rely on
+    /// the origin sending it. Empty by default.
+    pub vary_by_request_headers: Vec<HeaderName>,
+    /// Remove the cached `GET` response for a url when a request with an unsafe method,
+    /// such as `POST`, is sent to it. Enabled by default, disable it for endpoints that
+    /// don't modify anything, like a search taking a `POST` body.
+    pub invalidate_on_unsafe_methods: bool,
+    /// Also store a `GET` response under the key for its `Content-Location`, when that
+    /// names another url on the same origin, so a request to `/latest` answered with
+    /// `Content-Location: /v/5` makes a later request to `/v/5` a hit. Disabled by default.
+    pub follow_content_location: bool,
+    /// The most headers a response can have to be cached. Guards against an origin
+    /// sending an unbounded number of headers. Unlimited by default.
+    ///
+    /// A response with more is returned without being stored rather than failing
+    /// the request with a [`BadHeader`] error, and its [`CacheDecision`] has the
+    /// [`CacheDecisionReason::HeaderLimits`] reason.
+    pub max_headers: Option<usize>,
+    /// The longest a response header value can be in bytes for the response to be
+    /// cached. Unlimited by default.
+    ///
+    /// A response with a longer one is handled like one with too many headers,
+    /// see [`HttpCacheOptions::max_headers`].
+    pub max_header_value_len: Option<usize>,
+    /// Replace every cache key with the request method and a SHA-256 hash of the key,
+    /// e.g. `GET:9f86d0…`, so keys have a fixed length however long the url is.
+    /// Busting keys by prefix, like [`invalidate_path_prefix`] does, won't match
+    /// hashed keys. Disabled by default.
+    #[cfg(feature = "sha2")]
+    #[cfg_attr(docsrs, doc(cfg(feature = "sha2")))]
+    pub hash_keys: bool,
+    /// Modify the responses fetched from the origin before their cache policy is created,
+    /// e.g. to add a `cache-control: max-age=300` header to responses from an origin that
+    /// doesn't send any. Unlike overriding a ttl this can change any header the policy
+    /// looks at. The modified response is also the one returned.
+    pub response_header_rewrite_fn: Option<ResponseHeaderRewriteFn>,
 }
 
+// Hop-by-hop headers only apply to a single connection and `set-cookie`
+// is specific to the client it was sent to, so neither should be replayed.
+// (https://www.rfc-editor.org/rfc/rfc9110#section-7.6.1)
+const STRIPPED_HEADERS: [&str; 9] = [
+    "connection",
+    "keep-alive",
+    "proxy-connection",
+    "te",
+    "trailer",
+    "transfer-encoding",
+    "upgrade",
+    "set-cookie",
+    "set-cookie2",
+];
+
 impl Default for HttpCacheOptions {
     fn default() -> Self {
         Self {
             cache_options: None,
             cache_key: None,
+            cache_key_body: None,
             cache_mode_fn: None,
             cache_bust: None,
+            try_cache_key: None,
+            try_cache_mode_fn: None,
+            try_cache_bust: None,
+            closure_error_policy: ClosureErrorPolicy::Fail,
             cache_status_headers: true,
+            skip_cache_on_cookie: false,
+            respect_request_authorization: false,
+            cache_events: None,
+            stored_header_allowlist: None,
+            single_flight: false,
+            in_flight: InFlight::default(),
+            via_pseudonym: None,
+            on_cache_decision: None,
+            on_stale_served: None,
+            strip_headers_before_cache: Some(
+                STRIPPED_HEADERS.iter().map(|name| name.to_string()).collect(),
+            ),
+            shared: false,
+            cache_range_requests: false,
+            normalize_key: false,
+            #[cfg(feature = "sha2")]
+            verify_body_checksum: false,
+            negative_ttl: None,
+            negative_status_codes: HashSet::new(),
+            cache_key_suffix_fn: None,
+            pre_key_request_fn: None,
+            only_if_cached_error: false,
+            cache_options_method: false,
+            max_served_staleness: None,
+            max_fresh_lifetime: None,
+            vary_by_request_headers: Vec::new(),
+            invalidate_on_unsafe_methods: true,
+            follow_content_location: false,
+            max_headers: None,
+            max_header_value_len: None,
+            #[cfg(feature = "sha2")]
+            hash_keys: false,
+            response_header_rewrite_fn: None,
         }
     }
 }
 
 impl Debug for HttpCacheOptions {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
-        f.debug_struct("HttpCacheOptions")
+        let mut debug = f.debug_struct("HttpCacheOptions");
+        debug
             .field("cache_options", &self.cache_options)
             .field("cache_key", &"Fn(&request::Parts) -> String")
+            .field(
+                "cache_key_body",
+                &"Fn(&request::Parts, &[u8]) -> Option<String>",
+            )
             .field("cache_mode_fn", &"Fn(&request::Parts) -> CacheMode")
             .field("cache_bust", &"Fn(&request::Parts) -> Vec<String>")
+            .field("try_cache_key", &"Fn(&request::Parts) -> Result<String>")
+            .field(
+                "try_cache_mode_fn",
+                &"Fn(&request::Parts) -> Result<CacheMode>",
+            )
+            .field(
+                "try_cache_bust",
+                &"Fn(&request::Parts) -> Result<Vec<String>>",
+            )
+            .field("closure_error_policy", &self.closure_error_policy)
             .field("cache_status_headers", &self.cache_status_headers)
+            .field("skip_cache_on_cookie", &self.skip_cache_on_cookie)
+            .field(
+                "respect_request_authorization",
+                &self.respect_request_authorization,
+            )
+            .field("cache_events", &self.cache_events)
+            .field("stored_header_allowlist", &self.stored_header_allowlist)
+            .field("single_flight", &self.single_flight)
+            .field("in_flight", &self.in_flight)
+            .field("via_pseudonym", &self.via_pseudonym)
+            .field("on_cache_decision", &"Fn(&CacheDecision)")
+            .field("on_stale_served", &"Fn(&Url, StaleReason)")
+            .field(
+                "strip_headers_before_cache",
+                &self.strip_headers_before_cache,
+            )
+            .field("shared", &self.shared)
+            .field("cache_range_requests", &self.cache_range_requests)
+            .field("normalize_key", &self.normalize_key);
+        #[cfg(feature = "sha2")]
+        debug.field("verify_body_checksum", &self.verify_body_checksum);
+        debug
+            .field("negative_ttl", &self.negative_ttl)
+            .field("negative_status_codes", &self.negative_status_codes)
+            .field("cache_key_suffix_fn", &"Fn(&request::Parts) -> String")
+            .field("pre_key_request_fn", &"Fn(&mut request::Parts)")
+            .field("only_if_cached_error", &self.only_if_cached_error)
+            .field("cache_options_method", &self.cache_options_method)
+            .field("max_served_staleness", &self.max_served_staleness)
+            .field("max_fresh_lifetime", &self.max_fresh_lifetime)
+            .field("vary_by_request_headers", &self.vary_by_request_headers)
+            .field(
+                "invalidate_on_unsafe_methods",
+                &self.invalidate_on_unsafe_methods,
+            )
+            .field("follow_content_location", &self.follow_content_location)
+            .field("max_headers", &self.max_headers)
+            .field("max_header_value_len", &self.max_header_value_len);
+        #[cfg(feature = "sha2")]
+        debug.field("hash_keys", &self.hash_keys);
+        debug
+            .field(
+                "response_header_rewrite_fn",
+                &"Fn(&request::Parts, &mut HttpResponse)",
+            )
             .finish()
     }
 }
 
-impl HttpCacheOptions {
-    fn create_cache_key(
-        &self,
-        parts: &request::Parts,
-        override_method: Option<&str>,
-    ) -> String {
-        if let Some(cache_key) = &self.cache_key {
-            cache_key(parts)
-        } else {
-            format!(
-                "{}:{}",
-                override_method.unwrap_or_else(|| parts.method.as_str()),
-                parts.uri
-            )
-        }
-    }
+/// A builder for [`HttpCacheOptions`], starting from the defaults.
+///
+/// ```
+/// # use http_cache::HttpCacheOptions;
+/// let options = HttpCacheOptions::builder()
+///     .cache_status_headers(false)
+///     .single_flight(true)
+///     .build();
+/// assert!(!options.cache_status_headers);
+/// ```
+#[derive(Debug, Clone, Default)]
+pub struct HttpCacheOptionsBuilder {
+    options: HttpCacheOptions,
 }
 
-/// Caches requests according to http spec.
-#[derive(Debug, Clone)]
-pub struct HttpCache<T: CacheManager> {
-    /// Determines the manager behavior.
-    pub mode: CacheMode,
-    /// Manager instance that implements the [`CacheManager`] trait.
-    /// By default, a manager implementation with [`cacache`](https://github.com/zkat/cacache-rs)
-    /// as the backend has been provided, see [`CACacheManager`].
-    pub manager: T,
+impl HttpCacheOptionsBuilder {
     /// Override the default cache options.
-    pub options: HttpCacheOptions,
-}
+    #[must_use]
+    pub fn cache_options(mut self, cache_options: CacheOptions) -> Self {
+        self.options.cache_options = Some(cache_options);
+        self
+    }
 
-#[allow(dead_code)]
-impl<T: CacheManager> HttpCache<T> {
-    /// Determines if the request should be cached
-    pub fn can_cache_request(
-        &self,
+    /// Override the default cache key generator.
+    #[must_use]
+    pub fn cache_key(mut self, cache_key: CacheKey) -> Self {
+        self.options.cache_key = Some(cache_key);
+        self
+    }
+
+    /// Append a value derived from the request body to the cache key.
+    #[must_use]
+    pub fn cache_key_body(mut self, cache_key_body: CacheKeyBody) -> Self {
+        self.options.cache_key_body = Some(cache_key_body);
+        self
+    }
+
+    /// Override the default cache mode.
+    #[must_use]
+    pub fn cache_mode_fn(mut self, cache_mode_fn: CacheModeFn) -> Self {
+        self.options.cache_mode_fn = Some(cache_mode_fn);
+        self
+    }
+
+    /// Bust the caches of the returned keys.
+    #[must_use]
+    pub fn cache_bust(mut self, cache_bust: CacheBust) -> Self {
+        self.options.cache_bust = Some(cache_bust);
+        self
+    }
+
+    /// Fallible version of `cache_key`, takes precedence when set.
+    #[must_use]
+    pub fn try_cache_key(mut self, try_cache_key: TryCacheKey) -> Self {
+        self.options.try_cache_key = Some(try_cache_key);
+        self
+    }
+
+    /// Fallible version of `cache_mode_fn`, takes precedence when set.
+    #[must_use]
+    pub fn try_cache_mode_fn(
+        mut self,
+        try_cache_mode_fn: TryCacheModeFn,
+    ) -> Self {
+        self.options.try_cache_mode_fn = Some(try_cache_mode_fn);
+        self
+    }
+
+    /// Fallible version of `cache_bust`, takes precedence when set.
+    #[must_use]
+    pub fn try_cache_bust(mut self, try_cache_bust: TryCacheBust) -> Self {
+        self.options.try_cache_bust = Some(try_cache_bust);
+        self
+    }
+
+    /// Determines how errors returned from the fallible closures are handled.
+    #[must_use]
+    pub fn closure_error_policy(
+        mut self,
+        closure_error_policy: ClosureErrorPolicy,
+    ) -> Self {
+        self.options.closure_error_policy = closure_error_policy;
+        self
+    }
+
+    /// Determines if the cache status headers should be added to the response.
+    #[must_use]
+    pub fn cache_status_headers(mut self, cache_status_headers: bool) -> Self {
+        self.options.cache_status_headers = cache_status_headers;
+        self
+    }
+
+    /// Skip storing responses to requests carrying a `Cookie` header.
+    #[must_use]
+    pub fn skip_cache_on_cookie(mut self, skip_cache_on_cookie: bool) -> Self {
+        self.options.skip_cache_on_cookie = skip_cache_on_cookie;
+        self
+    }
+
+    /// Only store responses to requests carrying an `Authorization` header when the response allows it.
+    #[must_use]
+    pub fn respect_request_authorization(
+        mut self,
+        respect_request_authorization: bool,
+    ) -> Self {
+        self.options.respect_request_authorization =
+            respect_request_authorization;
+        self
+    }
+
+    /// Publish [`CacheEvent`]s to the subscribers of this channel.
+    #[must_use]
+    pub fn cache_events(mut self, cache_events: CacheEvents) -> Self {
+        self.options.cache_events = Some(cache_events);
+        self
+    }
+
+    /// Only store the listed response headers.
+    #[must_use]
+    pub fn stored_header_allowlist(
+        mut self,
+        stored_header_allowlist: Vec<HeaderName>,
+    ) -> Self {
+        self.options.stored_header_allowlist = Some(stored_header_allowlist);
+        self
+    }
+
+    /// Share a single upstream fetch between concurrent cache misses.
+    #[must_use]
+    pub fn single_flight(mut self, single_flight: bool) -> Self {
+        self.options.single_flight = single_flight;
+        self
+    }
+
+    /// Sets the fetches in flight, e.g. to share them with another cache.
+    #[must_use]
+    pub fn in_flight(mut self, in_flight: InFlight) -> Self {
+        self.options.in_flight = in_flight;
+        self
+    }
+
+    /// Append a `via` header identifying this cache.
+    #[must_use]
+    pub fn via_pseudonym(mut self, via_pseudonym: impl Into<String>) -> Self {
+        self.options.via_pseudonym = Some(via_pseudonym.into());
+        self
+    }
+
+    /// Called with the [`CacheDecision`] for each response fetched from the origin.
+    #[must_use]
+    pub fn on_cache_decision(
+        mut self,
+        on_cache_decision: CacheDecisionFn,
+    ) -> Self {
+        self.options.on_cache_decision = Some(on_cache_decision);
+        self
+    }
+
+    /// Called when a response is served with a `111` or `112` warning.
+    #[must_use]
+    pub fn on_stale_served(mut self, on_stale_served: StaleServedFn) -> Self {
+        self.options.on_stale_served = Some(on_stale_served);
+        self
+    }
+
+    /// Response headers that are dropped before a response is stored.
+    #[must_use]
+    pub fn strip_headers_before_cache(
+        mut self,
+        strip_headers_before_cache: Option<Vec<String>>,
+    ) -> Self {
+        self.options.strip_headers_before_cache = strip_headers_before_cache;
+        self
+    }
+
+    /// Treat the cache as shared between users, see [`HttpCacheOptions::shared`].
+    #[must_use]
+    pub fn shared(mut self, shared: bool) -> Self {
+        self.options.shared = shared;
+        self
+    }
+
+    /// Cache partial responses under a key that includes the requested range.
+    #[must_use]
+    pub fn cache_range_requests(mut self, cache_range_requests: bool) -> Self {
+        self.options.cache_range_requests = cache_range_requests;
+        self
+    }
+
+    /// Normalize the url in the default cache key.
+    #[must_use]
+    pub fn normalize_key(mut self, normalize_key: bool) -> Self {
+        self.options.normalize_key = normalize_key;
+        self
+    }
+
+    /// Store and verify a checksum of each cached body.
+    #[cfg(feature = "sha2")]
+    #[cfg_attr(docsrs, doc(cfg(feature = "sha2")))]
+    #[must_use]
+    pub fn verify_body_checksum(mut self, verify_body_checksum: bool) -> Self {
+        self.options.verify_body_checksum = verify_body_checksum;
+        self
+    }
+
+    /// Cache responses with the negative status codes for this long.
+    #[must_use]
+    pub fn negative_ttl(mut self, negative_ttl: Option<Duration>) -> Self {
+        self.options.negative_ttl = negative_ttl;
+        self
+    }
+
+    /// Sets the status codes cached for the negative ttl.
+    #[must_use]
+    pub fn negative_status_codes(
+        mut self,
+        negative_status_codes: HashSet<u16>,
+    ) -> Self {
+        self.options.negative_status_codes = negative_status_codes;
+        self
+    }
+
+    /// Append the returned string to every cache key.
+    #[must_use]
+    pub fn cache_key_suffix_fn(
+        mut self,
+        cache_key_suffix_fn: CacheKey,
+    ) -> Self {
+        self.options.cache_key_suffix_fn = Some(cache_key_suffix_fn);
+        self
+    }
+
+    /// Modify a copy of the request before its cache key is created.
+    #[must_use]
+    pub fn pre_key_request_fn(
+        mut self,
+        pre_key_request_fn: PreKeyRequestFn,
+    ) -> Self {
+        self.options.pre_key_request_fn = Some(pre_key_request_fn);
+        self
+    }
+
+    /// Return an error for only-if-cached requests without a cached response.
+    #[must_use]
+    pub fn only_if_cached_error(mut self, only_if_cached_error: bool) -> Self {
+        self.options.only_if_cached_error = only_if_cached_error;
+        self
+    }
+
+    /// Cache the responses to `OPTIONS` requests.
+    #[must_use]
+    pub fn cache_options_method(mut self, cache_options_method: bool) -> Self {
+        self.options.cache_options_method = cache_options_method;
+        self
+    }
+
+    /// Sets the longest a stale response may be served for without being revalidated.
+    #[must_use]
+    pub fn max_served_staleness(
+        mut self,
+        max_served_staleness: Option<Duration>,
+    ) -> Self {
+        self.options.max_served_staleness = max_served_staleness;
+        self
+    }
+
+    /// Sets the longest a response is considered fresh for.
+    #[must_use]
+    pub fn max_fresh_lifetime(
+        mut self,
+        max_fresh_lifetime: Option<Duration>,
+    ) -> Self {
+        self.options.max_fresh_lifetime = max_fresh_lifetime;
+        self
+    }
+
+    /// Sets the request headers whose values are added to the default cache key.
+    #[must_use]
+    pub fn vary_by_request_headers(
+        mut self,
+        vary_by_request_headers: Vec<HeaderName>,
+    ) -> Self {
+        self.options.vary_by_request_headers = vary_by_request_headers;
+        self
+    }
+
+    /// Remove the cached `GET` response for a url on requests with unsafe methods.
+    #[must_use]
+    pub fn invalidate_on_unsafe_methods(
+        mut self,
+        invalidate_on_unsafe_methods: bool,
+    ) -> Self {
+        self.options.invalidate_on_unsafe_methods =
+            invalidate_on_unsafe_methods;
+        self
+    }
+
+    /// Also store responses under the url named by their `Content-Location` header.
+    #[must_use]
+    pub fn follow_content_location(
+        mut self,
+        follow_content_location: bool,
+    ) -> Self {
+        self.options.follow_content_location = follow_content_location;
+        self
+    }
+
+    /// Limit the number of headers a cached response can have.
+    #[must_use]
+    pub fn max_headers(mut self, max_headers: usize) -> Self {
+        self.options.max_headers = Some(max_headers);
+        self
+    }
+
+    /// Limit the length of the header values of a cached response.
+    #[must_use]
+    pub fn max_header_value_len(mut self, max_header_value_len: usize) -> Self {
+        self.options.max_header_value_len = Some(max_header_value_len);
+        self
+    }
+
+    /// Replace cache keys with a hash of them to bound their length.
+    #[cfg(feature = "sha2")]
+    #[cfg_attr(docsrs, doc(cfg(feature = "sha2")))]
+    #[must_use]
+    pub fn hash_keys(mut self, hash_keys: bool) -> Self {
+        self.options.hash_keys = hash_keys;
+        self
+    }
+
+    /// Modify responses from the origin before their cache policy is created.
+    #[must_use]
+    pub fn response_header_rewrite_fn(
+        mut self,
+        response_header_rewrite_fn: ResponseHeaderRewriteFn,
+    ) -> Self {
+        self.options.response_header_rewrite_fn =
+            Some(response_header_rewrite_fn);
+        self
+    }
+
+    /// Builds the [`HttpCacheOptions`]
+    #[must_use]
+    pub fn build(self) -> HttpCacheOptions {
+        self.options
+    }
+}
+
+impl HttpCacheOptions {
+    /// Returns a builder for [`HttpCacheOptions`]
+    #[must_use]
+    pub fn builder() -> HttpCacheOptionsBuilder {
+        HttpCacheOptionsBuilder::default()
+    }
+
+    fn create_cache_key(
+        &self,
+        parts: &request::Parts,
+        body: Option<&[u8]>,
+        override_method: Option<&str>,
+    ) -> Result<String> {
+        let key =
+            self.create_unhashed_cache_key(parts, body, override_method)?;
+        #[cfg(feature = "sha2")]
+        if self.hash_keys {
+            let method =
+                override_method.unwrap_or_else(|| parts.method.as_str());
+            return Ok(format!("{method}:{}", checksum(key.as_bytes())));
+        }
+        Ok(key)
+    }
+
+    fn create_unhashed_cache_key(
+        &self,
+        parts: &request::Parts,
+        body: Option<&[u8]>,
+        override_method: Option<&str>,
+    ) -> Result<String> {
+        let canonical;
+        let parts = match &self.pre_key_request_fn {
+            Some(pre_key_request_fn) => {
+                let mut copy = copy_parts(parts)?;
+                pre_key_request_fn(&mut copy);
+                canonical = copy;
+                &canonical
+            }
+            None => parts,
+        };
+        let key = if let Some(try_cache_key) = &self.try_cache_key {
+            try_cache_key(parts).map_err(ClosureError)?
+        } else if let Some(cache_key) = &self.cache_key {
+            cache_key(parts)
+        } else {
+            let uri = if self.normalize_key {
+                normalize_uri(&parts.uri)
+            } else {
+                parts.uri.to_string()
+            };
+            let key = format!(
+                "{}:{}",
+                override_method.unwrap_or_else(|| parts.method.as_str()),
+                uri
+            );
+            self.vary_by_request_headers.iter().fold(key, |key, name| {
+                let values: Vec<&str> = parts
+                    .headers
+                    .get_all(name)
+                    .iter()
+                    .filter_map(|val| val.to_str().ok())
+                    .collect();
+                if values.is_empty() {
+                    key
+                } else {
+                    format!("{key}:{name}={}", values.join(", "))
+                }
+            })
+        };
+        let key = match &self.cache_key_suffix_fn {
+            Some(cache_key_suffix_fn) => {
+                format!("{key}:{}", cache_key_suffix_fn(parts))
+            }
+            None => key,
+        };
+        // The body doesn't belong to the request an overridden method refers to
+        if override_method.is_some() {
+            return Ok(key);
+        }
+        let key = match self.range(parts) {
+            Some(range) => format!("{key}:range={range}"),
+            None => key,
+        };
+        Ok(match (&self.cache_key_body, body) {
+            (Some(cache_key_body), Some(body)) => {
+                match cache_key_body(parts, body) {
+                    Some(suffix) => format!("{key}:{suffix}"),
+                    None => key,
+                }
+            }
+            _ => key,
+        })
+    }
+
+    // How long to cache an error response for, if it's negatively cached
+    fn negative_ttl(&self, res: &HttpResponse) -> Option<Duration> {
+        self.negative_ttl
+            .filter(|_| self.negative_status_codes.contains(&res.status))
+    }
+
+    // The requested range when partial responses are cached under their range
+    fn range<'a>(&self, parts: &'a request::Parts) -> Option<&'a str> {
+        if !self.cache_range_requests {
+            return None;
+        }
+        parts.headers.get(RANGE).and_then(|range| range.to_str().ok())
+    }
+
+    fn skip_cache_for_cookie(
+        &self,
+        parts: &request::Parts,
+        response: &HttpResponse,
+    ) -> bool {
+        self.skip_cache_on_cookie
+            && parts.headers.contains_key(COOKIE)
+            && !response.is_public()
+    }
+
+    fn skip_cache_for_authorization(
+        &self,
+        parts: &request::Parts,
+        response: &HttpResponse,
+    ) -> bool {
+        self.respect_request_authorization
+            && parts.headers.contains_key(AUTHORIZATION)
+            && !(response.is_public()
+                || response.has_cache_control_directive("s-maxage")
+                || response.has_cache_control_directive("must-revalidate"))
+    }
+
+    // The headers the cache adds itself aren't counted against the limits
+    fn within_header_limits(&self, response: &HttpResponse) -> bool {
+        let headers: Vec<&String> = response
+            .headers
+            .iter()
+            .filter(|(name, _)| {
+                ![XCACHE, XCACHELOOKUP, XCHECKSUM].contains(&name.as_str())
+            })
+            .map(|(_, value)| value)
+            .collect();
+        let too_many =
+            self.max_headers.map_or(false, |max| headers.len() > max);
+        let too_long = self
+            .max_header_value_len
+            .map_or(false, |max| headers.iter().any(|value| value.len() > max));
+        !(too_many || too_long)
+    }
+}
+
+/// Caches requests according to http spec.
+#[derive(Debug, Clone)]
+pub struct HttpCache<T: CacheManager> {
+    /// Determines the manager behavior.
+    pub mode: CacheMode,
+    /// Manager instance that implements the [`CacheManager`] trait.
+    /// By default, a manager implementation with [`cacache`](https://github.com/zkat/cacache-rs)
+    /// as the backend has been provided, see [`CACacheManager`].
+    pub manager: T,
+    /// Override the default cache options.
+    pub options: HttpCacheOptions,
+}
+
+#[allow(dead_code)]
+impl<T: CacheManager> HttpCache<T> {
+    /// Determines if the request should be cached
+    pub fn can_cache_request(
+        &self,
         middleware: &impl Middleware,
     ) -> Result<bool> {
+        if self.bypass(middleware)? {
+            return Ok(false);
+        }
         let mode = self.cache_mode(middleware)?;
 
         Ok(mode == CacheMode::IgnoreRules
-            || middleware.is_method_get_head()
+            || self.is_cacheable_method(middleware)?
                 && mode != CacheMode::NoStore
                 && mode != CacheMode::Reload)
     }
@@ -468,131 +1988,701 @@ impl<T: CacheManager> HttpCache<T> {
         &self,
         middleware: &mut impl Middleware,
     ) -> Result<()> {
-        self.manager
-            .delete(
-                &self
-                    .options
-                    .create_cache_key(&middleware.parts()?, Some("GET")),
-            )
-            .await
-            .ok();
+        if self.bypass(middleware)? {
+            return Ok(());
+        }
+        self.evict(middleware).await?;
 
-        let cache_key =
-            self.options.create_cache_key(&middleware.parts()?, None);
+        let cache_key = self.cache_key(middleware)?;
 
-        if let Some(cache_bust) = &self.options.cache_bust {
-            for key_to_cache_bust in cache_bust(
-                &middleware.parts()?,
-                &self.options.cache_key,
-                &cache_key,
-            ) {
-                self.manager.delete(&key_to_cache_bust).await?;
-            }
-        }
+        self.bust(middleware, &cache_key).await
+    }
 
-        Ok(())
+    /// Whether the manager can stream response bodies, see [`CacheManager::supports_streaming`]
+    pub fn supports_streaming(&self) -> bool {
+        self.manager.supports_streaming()
+    }
+
+    /// Returns the cached response for the key as it was stored, if there is one.
+    /// Meant for tests and tooling, no freshness checks are made and the response isn't
+    /// given the headers, such as `age` and `x-cache`, that are added when it's served.
+    pub async fn inspect(
+        &self,
+        cache_key: &str,
+    ) -> Result<Option<HttpResponse>> {
+        Ok(self.lookup(cache_key).await?.map(|(res, _)| res))
+    }
+
+    /// Returns a stream of [`CacheEvent`]s if [`HttpCacheOptions::cache_events`] is set
+    pub fn subscribe(&self) -> Option<impl futures::Stream<Item = CacheEvent>> {
+        self.options.cache_events.as_ref().map(CacheEvents::subscribe)
     }
 
     /// Attempts to run the passed middleware along with the cache
     pub async fn run(
+        &self,
+        middleware: impl Middleware,
+    ) -> Result<HttpResponse> {
+        Ok(self.run_with_status(middleware).await?.0)
+    }
+
+    /// Attempts to run the passed middleware along with the cache, also returning
+    /// how the cache handled the request whether or not the status headers are added
+    pub async fn run_with_status(
+        &self,
+        middleware: impl Middleware,
+    ) -> Result<(HttpResponse, HitStatus)> {
+        let cache_status_headers = self.cache_status_headers(&middleware);
+        let mut status = HitStatus::default();
+        let mut res = self.serve(middleware, &mut status).await?;
+        if cache_status_headers {
+            if let Some(cache) = status.cache {
+                res.cache_status(cache);
+            }
+            if let Some(lookup) = status.lookup {
+                res.cache_lookup_status(lookup);
+            }
+        }
+        res.update_content_length();
+        if let Some(pseudonym) = &self.options.via_pseudonym {
+            res.add_via(pseudonym);
+        }
+        Ok((res, status))
+    }
+
+    async fn serve(
         &self,
         mut middleware: impl Middleware,
+        status: &mut HitStatus,
     ) -> Result<HttpResponse> {
+        if self.bypass(&middleware)? {
+            return middleware.remote_fetch().await;
+        }
         let is_cacheable = self.can_cache_request(&middleware)?;
         if !is_cacheable {
-            return self.remote_fetch(&mut middleware).await;
+            return self.remote_fetch(&mut middleware, status).await;
         }
 
-        let cache_key =
-            self.options.create_cache_key(&middleware.parts()?, None);
+        let cache_key = self.cache_key(&middleware)?;
 
-        if let Some(cache_bust) = &self.options.cache_bust {
-            for key_to_cache_bust in cache_bust(
-                &middleware.parts()?,
-                &self.options.cache_key,
-                &cache_key,
-            ) {
-                self.manager.delete(&key_to_cache_bust).await?;
-            }
-        }
-
-        if let Some(store) = self.manager.get(&cache_key).await? {
-            let (mut res, policy) = store;
-            if self.options.cache_status_headers {
-                res.cache_lookup_status(HitOrMiss::HIT);
-            }
-            if let Some(warning_code) = res.warning_code() {
-                // https://tools.ietf.org/html/rfc7234#section-4.3.4
-                //
-                // If a stored response is selected for update, the cache MUST:
-                //
-                // * delete any warning header fields in the stored response with
-                //   warn-code 1xx (see Section 5.5);
-                //
-                // * retain any warning header fields in the stored response with
-                //   warn-code 2xx;
-                //
-                if (100..200).contains(&warning_code) {
-                    res.remove_warning();
-                }
-            }
+        self.bust(&middleware, &cache_key).await?;
 
-            match self.cache_mode(&middleware)? {
-                CacheMode::Default => {
-                    self.conditional_fetch(middleware, res, policy).await
-                }
-                CacheMode::NoCache => {
-                    middleware.force_no_cache()?;
-                    let mut res = self.remote_fetch(&mut middleware).await?;
-                    if self.options.cache_status_headers {
-                        res.cache_lookup_status(HitOrMiss::HIT);
-                    }
-                    Ok(res)
-                }
-                CacheMode::ForceCache
-                | CacheMode::OnlyIfCached
-                | CacheMode::IgnoreRules => {
-                    //   112 Disconnected operation
-                    // SHOULD be included if the cache is intentionally disconnected from
-                    // the rest of the network for a period of time.
-                    // (https://tools.ietf.org/html/rfc2616#section-14.46)
-                    res.add_warning(
-                        &res.url.clone(),
-                        112,
-                        "Disconnected operation",
-                    );
-                    if self.options.cache_status_headers {
-                        res.cache_status(HitOrMiss::HIT);
-                    }
-                    Ok(res)
-                }
-                _ => self.remote_fetch(&mut middleware).await,
-            }
+        if let Some((res, policy)) = self.lookup(&cache_key).await? {
+            self.serve_hit(middleware, res, policy, cache_key, status).await
         } else {
+            self.publish(CacheEvent::Miss(cache_key.clone()));
             match self.cache_mode(&middleware)? {
                 CacheMode::OnlyIfCached => {
                     // ENOTCACHED
-                    let mut res = HttpResponse {
+                    if self.options.only_if_cached_error {
+                        return Err(Box::new(NotCached));
+                    }
+                    let res = HttpResponse {
                         body: b"GatewayTimeout".to_vec(),
                         headers: HashMap::default(),
                         status: 504,
                         url: middleware.url()?,
                         version: HttpVersion::Http11,
                     };
-                    if self.options.cache_status_headers {
-                        res.cache_status(HitOrMiss::MISS);
-                        res.cache_lookup_status(HitOrMiss::MISS);
-                    }
+                    status.cache = Some(HitOrMiss::MISS);
+                    status.lookup = Some(HitOrMiss::MISS);
                     Ok(res)
                 }
-                _ => self.remote_fetch(&mut middleware).await,
+                _ => self.miss_fetch(middleware, cache_key, status).await,
+            }
+        }
+    }
+
+    // Serves a request there is a cached response for, as the cache mode directs
+    async fn serve_hit(
+        &self,
+        mut middleware: impl Middleware,
+        mut res: HttpResponse,
+        policy: CachePolicy,
+        cache_key: String,
+        status: &mut HitStatus,
+    ) -> Result<HttpResponse> {
+        status.lookup = Some(HitOrMiss::HIT);
+        if let Some(warning_code) = res.warning_code() {
+            // https://tools.ietf.org/html/rfc7234#section-4.3.4
+            //
+            // If a stored response is selected for update, the cache MUST:
+            //
+            // * delete any warning header fields in the stored response with
+            //   warn-code 1xx (see Section 5.5);
+            //
+            // * retain any warning header fields in the stored response with
+            //   warn-code 2xx;
+            //
+            if (100..200).contains(&warning_code) {
+                res.remove_warning();
+            }
+        }
+
+        match self.cache_mode(&middleware)? {
+            CacheMode::Default => {
+                self.conditional_fetch(
+                    middleware, res, policy, cache_key, status,
+                )
+                .await
+            }
+            CacheMode::NoCache => {
+                middleware.force_no_cache()?;
+                let res = self.remote_fetch(&mut middleware, status).await?;
+                status.lookup = Some(HitOrMiss::HIT);
+                Ok(res)
+            }
+            CacheMode::ForceRefresh => {
+                // Only a failure to reach the origin falls back to the stale response,
+                // errors storing the new one are returned
+                match middleware.remote_fetch().await {
+                    Ok(fresh) => {
+                        let (fresh, _) = self
+                            .store_fetched(&mut middleware, fresh, status)
+                            .await?;
+                        status.lookup = Some(HitOrMiss::HIT);
+                        Ok(fresh)
+                    }
+                    Err(_) => {
+                        //   111 Revalidation failed
+                        //   MUST be included if a cache returns a stale response
+                        //   because an attempt to revalidate the response failed,
+                        //   due to an inability to reach the server.
+                        // (https://tools.ietf.org/html/rfc2616#section-14.46)
+                        res.add_warning(
+                            &res.url.clone(),
+                            111,
+                            "Revalidation failed",
+                        );
+                        self.stale_served(
+                            &res.url,
+                            StaleReason::RevalidationFailed,
+                        );
+                        res.update_age(&policy);
+                        status.cache = Some(HitOrMiss::HIT);
+                        self.publish(CacheEvent::Hit(cache_key));
+                        Ok(res)
+                    }
+                }
+            }
+            CacheMode::ForceCache
+            | CacheMode::OnlyIfCached
+            | CacheMode::IgnoreRules => {
+                //   112 Disconnected operation
+                // SHOULD be included if the cache is intentionally disconnected from
+                // the rest of the network for a period of time.
+                // (https://tools.ietf.org/html/rfc2616#section-14.46)
+                res.add_warning(
+                    &res.url.clone(),
+                    112,
+                    "Disconnected operation",
+                );
+                self.stale_served(&res.url, StaleReason::Disconnected);
+                res.update_age(&policy);
+                status.cache = Some(HitOrMiss::HIT);
+                self.publish(CacheEvent::Hit(cache_key));
+                Ok(res)
+            }
+            CacheMode::StaleWhileRevalidate => {
+                let now = SystemTime::now();
+                if !policy.is_stale(now)
+                    && !self.past_max_fresh_lifetime(&policy, now)
+                    || !self.within_max_served_staleness(&policy, now)
+                {
+                    return self
+                        .conditional_fetch(
+                            middleware, res, policy, cache_key, status,
+                        )
+                        .await;
+                }
+                let mut stale = res.clone();
+                stale.update_age(&policy);
+                // The stale response is served whatever the outcome of the refresh,
+                // a failed refresh is only reported
+                if self
+                    .conditional_fetch(
+                        middleware,
+                        res,
+                        policy,
+                        cache_key.clone(),
+                        status,
+                    )
+                    .await
+                    .is_err()
+                {
+                    self.publish(CacheEvent::RefreshFailed(cache_key));
+                }
+                //   110 Response is stale
+                //   MUST be included whenever the returned response is stale.
+                // (https://tools.ietf.org/html/rfc2616#section-14.46)
+                stale.add_warning(&stale.url.clone(), 110, "Response is stale");
+                self.stale_served(
+                    &stale.url,
+                    StaleReason::StaleWhileRevalidate,
+                );
+                status.cache = Some(HitOrMiss::HIT);
+                Ok(stale)
             }
+            _ => self.remote_fetch(&mut middleware, status).await,
         }
     }
 
+    async fn miss_fetch(
+        &self,
+        mut middleware: impl Middleware,
+        cache_key: String,
+        status: &mut HitStatus,
+    ) -> Result<HttpResponse> {
+        if !self.options.single_flight {
+            return self.remote_fetch(&mut middleware, status).await;
+        }
+        match self.options.in_flight.join(cache_key.clone()) {
+            Join::Leader(leader) => {
+                let (res, stored) =
+                    self.fetch_and_store(&mut middleware, status).await?;
+                leader.complete(stored);
+                Ok(res)
+            }
+            Join::Follower(follower) => {
+                // Only a stored response is shared, it is read back from the cache so
+                // the follower's request is still checked against it, e.g. its `vary`
+                // headers, and anything the cache doesn't keep isn't passed along
+                if follower.wait().await {
+                    if let Some((res, policy)) = self.lookup(&cache_key).await?
+                    {
+                        return self
+                            .serve_hit(
+                                middleware, res, policy, cache_key, status,
+                            )
+                            .await;
+                    }
+                }
+                self.remote_fetch(&mut middleware, status).await
+            }
+        }
+    }
+
+    /// Returns a [`Refresher`] that revalidates the cached responses for the urls
+    /// every `interval`. Failures are published under the keys the urls are cached at,
+    /// so this works with any [`HttpCacheOptions::cache_key`], or with hashed keys.
+    pub fn refresher(
+        &self,
+        urls: Vec<Url>,
+        interval: Duration,
+    ) -> Result<Refresher> {
+        let mut entries = Vec::with_capacity(urls.len());
+        for url in urls {
+            let (parts, _) =
+                http::Request::get(url.as_str()).body(())?.into_parts();
+            entries.push((
+                self.options.create_cache_key(&parts, None, None)?,
+                url,
+            ));
+        }
+        Ok(Refresher::new(entries, interval, self.options.cache_events.clone()))
+    }
+
+    /// Returns a [`Refresher`] for the responses cached under the keys, e.g. from
+    /// [`CacheManager::keys`]. The keys must be in the default `GET:<url>` form, any other
+    /// key, including a custom or hashed one, returns an [`UnrefreshableKey`] error,
+    /// use [`HttpCache::refresher`] to refresh those by url.
+    pub fn refresher_for_keys(
+        &self,
+        cache_keys: &[String],
+        interval: Duration,
+    ) -> Result<Refresher> {
+        let mut entries = Vec::with_capacity(cache_keys.len());
+        for cache_key in cache_keys {
+            let url = cache_key
+                .strip_prefix("GET:")
+                .and_then(|url| Url::parse(url).ok())
+                .ok_or_else(|| UnrefreshableKey(cache_key.clone()))?;
+            entries.push((cache_key.clone(), url));
+        }
+        Ok(Refresher::new(entries, interval, self.options.cache_events.clone()))
+    }
+
+    /// Returns the cache key the request will be stored under
+    pub fn cache_key(&self, middleware: &impl Middleware) -> Result<String> {
+        self.options.create_cache_key(
+            &middleware.parts()?,
+            middleware.body(),
+            None,
+        )
+    }
+
+    // Whether the cache should be skipped because a fallible closure failed
+    // and the configured policy is to bypass the cache.
+    fn bypass(&self, middleware: &impl Middleware) -> Result<bool> {
+        if self.options.closure_error_policy != ClosureErrorPolicy::Bypass {
+            return Ok(false);
+        }
+        match self
+            .cache_mode(middleware)
+            .and_then(|_| self.cache_key(middleware))
+        {
+            Ok(_) => Ok(false),
+            Err(e) if e.is::<ClosureError>() => Ok(true),
+            Err(e) => Err(e),
+        }
+    }
+
+    // Whether the cache status headers should be added to the response
+    fn cache_status_headers(&self, middleware: &impl Middleware) -> bool {
+        self.options.cache_status_headers
+            && !middleware.suppress_cache_headers()
+    }
+
+    // Whether the response is older than the `max_fresh_lifetime`, so it's
+    // revalidated even though the origin gave it a longer lifetime
+    fn past_max_fresh_lifetime(
+        &self,
+        policy: &CachePolicy,
+        now: SystemTime,
+    ) -> bool {
+        self.options
+            .max_fresh_lifetime
+            .map_or(false, |lifetime| policy.age(now) > lifetime)
+    }
+
+    // Whether a stale response has been stale for no longer than the
+    // `max_served_staleness`, counting from the `max_fresh_lifetime` if it's shorter
+    fn within_max_served_staleness(
+        &self,
+        policy: &CachePolicy,
+        now: SystemTime,
+    ) -> bool {
+        let Some(staleness) = self.options.max_served_staleness else {
+            return true;
+        };
+        !(stale_for_longer_than(policy, now, staleness)
+            || self.options.max_fresh_lifetime.map_or(false, |lifetime| {
+                policy.age(now) > lifetime.saturating_add(staleness)
+            }))
+    }
+
+    fn stale_served(&self, url: &Url, reason: StaleReason) {
+        if let Some(on_stale_served) = &self.options.on_stale_served {
+            on_stale_served(url, reason);
+        }
+    }
+
+    fn publish(&self, event: CacheEvent) {
+        if let Some(events) = &self.options.cache_events {
+            events.publish(event);
+        }
+    }
+
+    // Whether the request's method is one responses are cached for
+    fn is_cacheable_method(
+        &self,
+        middleware: &impl Middleware,
+    ) -> Result<bool> {
+        Ok(middleware.is_method_get_head() || self.is_options(middleware)?)
+    }
+
+    // Whether the request is an `OPTIONS` request whose response is cached
+    fn is_options(&self, middleware: &impl Middleware) -> Result<bool> {
+        Ok(self.options.cache_options_method
+            && middleware.method()? == "OPTIONS")
+    }
+
+    // The request parts the policy is evaluated against. The policy only
+    // understands `GET` and `HEAD`, so a cached `OPTIONS` request is evaluated
+    // as a `GET`, its key already being distinct from the `GET` request's.
+    fn policy_parts(
+        &self,
+        middleware: &impl Middleware,
+    ) -> Result<request::Parts> {
+        let mut parts = middleware.parts()?;
+        if self.is_options(middleware)? {
+            parts.method = http::Method::GET;
+        }
+        Ok(parts)
+    }
+
+    fn cache_options(&self) -> CacheOptions {
+        let options = self.options.cache_options.unwrap_or_default();
+        CacheOptions { shared: self.options.shared, ..options }
+    }
+
+    // The key and policy to also store a response under for its `Content-Location`
+    fn content_location_entry(
+        &self,
+        middleware: &impl Middleware,
+        res: &HttpResponse,
+    ) -> Result<Option<(String, CachePolicy)>> {
+        if !self.options.follow_content_location || res.status != 200 {
+            return Ok(None);
+        }
+        let mut parts = middleware.parts()?;
+        if parts.method != http::Method::GET {
+            return Ok(None);
+        }
+        let Some(location) = res.headers.get(CONTENT_LOCATION.as_str()) else {
+            return Ok(None);
+        };
+        // Only urls on the same origin are trusted to hold the same content
+        let location = match res.url.join(location) {
+            Ok(location)
+                if location.origin() == res.url.origin()
+                    && location != res.url =>
+            {
+                location
+            }
+            _ => return Ok(None),
+        };
+        parts.uri = location.as_str().parse()?;
+        let cache_key = self.options.create_cache_key(&parts, None, None)?;
+        let policy = CachePolicy::new_options(
+            &parts,
+            &res.parts()?,
+            SystemTime::now(),
+            self.cache_options(),
+        );
+        Ok(Some((cache_key, policy)))
+    }
+
+    fn policy(
+        &self,
+        middleware: &impl Middleware,
+        res: &HttpResponse,
+    ) -> Result<CachePolicy> {
+        let options = self.cache_options();
+        // A cache that understands the status code ignores `no-store` when it's
+        // paired with `must-understand`, it's only there for caches that don't
+        let overrides_no_store = res.must_understand()
+            && res.has_cache_control_directive("no-store")
+            && self.understands_status(middleware, res)?;
+        // Qualified directives only apply to the headers they name, which
+        // aren't stored, so the policy is evaluated without them
+        let applied;
+        let res = if res.has_qualified_directives() || overrides_no_store {
+            let mut res = res.clone();
+            res.apply_qualified_directives();
+            if overrides_no_store {
+                res.remove_cache_control_directive("no-store");
+            }
+            applied = res;
+            &applied
+        } else {
+            res
+        };
+        // Negatively cached responses are only fresh for the negative ttl,
+        // the policy is evaluated as if the response were a 200 with that max-age
+        if let Some(ttl) = self.options.negative_ttl(res) {
+            let mut negative = HttpResponse { status: 200, ..res.clone() };
+            for name in [EXPIRES, PRAGMA] {
+                negative.headers.remove(name.as_str());
+            }
+            negative.headers.insert(
+                CACHE_CONTROL.to_string(),
+                format!("max-age={}", ttl.as_secs()),
+            );
+            return middleware.policy_with_options(&negative, options);
+        }
+        // The policy doesn't understand partial responses, which are keyed on
+        // their range, so it's evaluated as if the response were complete
+        if res.status == 206
+            && self.options.range(&middleware.parts()?).is_some()
+        {
+            let complete = HttpResponse { status: 200, ..res.clone() };
+            return middleware.policy_with_options(&complete, options);
+        }
+        // A preflight response states how long it can be cached for with
+        // `Access-Control-Max-Age`, which is used unless it has a `max-age`
+        // (https://fetch.spec.whatwg.org/#http-access-control-max-age)
+        if self.is_options(middleware)? {
+            let mut preflight = res.clone();
+            let cache_control = res.headers.get(CACHE_CONTROL.as_str());
+            let has_max_age = cache_control
+                .map_or(false, |val| has_directive(val, "max-age"));
+            if let (Some(max_age), false) =
+                (res.headers.get(ACCESS_CONTROL_MAX_AGE.as_str()), has_max_age)
+            {
+                let max_age = format!("max-age={}", max_age.trim());
+                preflight.headers.insert(
+                    CACHE_CONTROL.to_string(),
+                    match cache_control {
+                        Some(val) => format!("{val}, {max_age}"),
+                        None => max_age,
+                    },
+                );
+            }
+            return Ok(CachePolicy::new_options(
+                &self.policy_parts(middleware)?,
+                &preflight.parts()?,
+                SystemTime::now(),
+                options,
+            ));
+        }
+        middleware.policy_with_options(res, options)
+    }
+
+    // Reads a cached response, treating one that fails its checksum as a miss
+    async fn lookup(
+        &self,
+        cache_key: &str,
+    ) -> Result<Option<(HttpResponse, CachePolicy)>> {
+        let Some((mut res, policy)) = self.manager.get(cache_key).await? else {
+            return Ok(None);
+        };
+        // Removed whether or not it's verified, so it isn't served
+        let stored = res.headers.remove(XCHECKSUM);
+        #[cfg(feature = "sha2")]
+        if self.options.verify_body_checksum
+            && stored.as_deref() != Some(checksum(&res.body).as_str())
+        {
+            return Ok(None);
+        }
+        #[cfg(not(feature = "sha2"))]
+        let _ = stored;
+        Ok(Some((res, policy)))
+    }
+
+    async fn put(
+        &self,
+        middleware: &impl Middleware,
+        mut res: HttpResponse,
+        mut policy: CachePolicy,
+    ) -> Result<HttpResponse> {
+        if !self.options.within_header_limits(&res) {
+            return Ok(res);
+        }
+        let cache_key = self.cache_key(middleware)?;
+        let mut dropped = HashMap::new();
+        if res.has_qualified_directives() {
+            let original = res.headers.clone();
+            res.apply_qualified_directives();
+            for (name, value) in original {
+                if res.headers.get(&name) != Some(&value) {
+                    dropped.insert(name, value);
+                }
+            }
+        }
+        if let Some(allowlist) = &self.options.stored_header_allowlist {
+            let names: Vec<String> =
+                res.headers.keys().map(|name| name.to_string()).collect();
+            for name in names {
+                if !allowlist.iter().any(|allowed| allowed.as_str() == name) {
+                    if let Some(value) = res.headers.remove(&name) {
+                        dropped.insert(name, value);
+                    }
+                }
+            }
+        }
+        if let Some(stripped) = &self.options.strip_headers_before_cache {
+            let names: Vec<String> = res
+                .headers
+                .keys()
+                .filter(|name| {
+                    stripped
+                        .iter()
+                        .any(|strip| strip.eq_ignore_ascii_case(name))
+                })
+                .map(|name| name.to_string())
+                .collect();
+            for name in names {
+                if let Some(value) = res.headers.remove(&name) {
+                    dropped.insert(name, value);
+                }
+            }
+        }
+        if !dropped.is_empty() {
+            // The policy keeps its own copy of the response headers,
+            // so it needs to be rebuilt from the filtered response.
+            policy = self.policy(middleware, &res)?;
+        }
+        #[cfg(feature = "sha2")]
+        if self.options.verify_body_checksum {
+            res.headers.insert(XCHECKSUM.to_string(), checksum(&res.body));
+        }
+        if let Some((alias_key, alias_policy)) =
+            self.content_location_entry(middleware, &res)?
+        {
+            self.manager
+                .put(alias_key.clone(), res.clone(), alias_policy)
+                .await?;
+            self.publish(CacheEvent::Store(alias_key));
+        }
+        let mut res = self.manager.put(cache_key.clone(), res, policy).await?;
+        res.headers.remove(XCHECKSUM);
+        res.headers.extend(dropped);
+        self.publish(CacheEvent::Store(cache_key));
+        Ok(res)
+    }
+
+    // Removes the cached GET response for a resource targeted by a request
+    // that may have modified it.
+    async fn evict(&self, middleware: &impl Middleware) -> Result<()> {
+        if !self.options.invalidate_on_unsafe_methods {
+            return Ok(());
+        }
+        let cache_key = self.options.create_cache_key(
+            &middleware.parts()?,
+            None,
+            Some("GET"),
+        )?;
+        // Only looked up when there are events to publish, so the eviction
+        // is reported when there was a cached response
+        let cached = self.options.cache_events.is_some()
+            && matches!(self.manager.get(&cache_key).await, Ok(Some(_)));
+        if self.manager.delete(&cache_key).await.is_ok() && cached {
+            self.publish(CacheEvent::Evict(cache_key));
+        }
+        Ok(())
+    }
+
+    async fn bust(
+        &self,
+        middleware: &impl Middleware,
+        cache_key: &str,
+    ) -> Result<()> {
+        let keys_to_cache_bust = if let Some(try_cache_bust) =
+            &self.options.try_cache_bust
+        {
+            match try_cache_bust(
+                &middleware.parts()?,
+                &self.options.cache_key,
+                cache_key,
+            ) {
+                Ok(keys) => keys,
+                Err(_)
+                    if self.options.closure_error_policy
+                        == ClosureErrorPolicy::Bypass =>
+                {
+                    Vec::new()
+                }
+                Err(e) => return Err(Box::new(ClosureError(e))),
+            }
+        } else if let Some(cache_bust) = &self.options.cache_bust {
+            cache_bust(&middleware.parts()?, &self.options.cache_key, cache_key)
+        } else {
+            Vec::new()
+        };
+        for key_to_cache_bust in keys_to_cache_bust {
+            match key_to_cache_bust.strip_suffix(BUST_PREFIX) {
+                Some(prefix) => {
+                    // A manager that can't list its keys can't bust by prefix,
+                    // which shouldn't fail the request
+                    match self.manager.delete_prefix(prefix).await {
+                        Err(e) if e.is::<Unsupported>() => continue,
+                        result => result?,
+                    };
+                }
+                None => self.manager.delete(&key_to_cache_bust).await?,
+            }
+            self.publish(CacheEvent::Bust(key_to_cache_bust));
+        }
+        Ok(())
+    }
+
     fn cache_mode(&self, middleware: &impl Middleware) -> Result<CacheMode> {
         Ok(if let Some(mode) = middleware.overridden_cache_mode() {
             mode
+        } else if let Some(try_cache_mode_fn) = &self.options.try_cache_mode_fn
+        {
+            try_cache_mode_fn(&middleware.parts()?).map_err(ClosureError)?
         } else if let Some(cache_mode_fn) = &self.options.cache_mode_fn {
             cache_mode_fn(&middleware.parts()?)
         } else {
@@ -603,48 +2693,158 @@ impl<T: CacheManager> HttpCache<T> {
     async fn remote_fetch(
         &self,
         middleware: &mut impl Middleware,
+        status: &mut HitStatus,
     ) -> Result<HttpResponse> {
-        let mut res = middleware.remote_fetch().await?;
-        if self.options.cache_status_headers {
-            res.cache_status(HitOrMiss::MISS);
-            res.cache_lookup_status(HitOrMiss::MISS);
-        }
-        let policy = match self.options.cache_options {
-            Some(options) => middleware.policy_with_options(&res, options)?,
-            None => middleware.policy(&res)?,
-        };
-        let is_get_head = middleware.is_method_get_head();
+        Ok(self.fetch_and_store(middleware, status).await?.0)
+    }
+
+    // Also returns whether the response was stored
+    async fn fetch_and_store(
+        &self,
+        middleware: &mut impl Middleware,
+        status: &mut HitStatus,
+    ) -> Result<(HttpResponse, bool)> {
+        let res = middleware.remote_fetch().await?;
+        self.store_fetched(middleware, res, status).await
+    }
+
+    // Stores a response fetched from the origin if it can be cached,
+    // also returning whether it was stored
+    async fn store_fetched(
+        &self,
+        middleware: &mut impl Middleware,
+        mut res: HttpResponse,
+        status: &mut HitStatus,
+    ) -> Result<(HttpResponse, bool)> {
+        self.rewrite_response(middleware, &mut res)?;
+        status.cache = Some(HitOrMiss::MISS);
+        status.lookup = Some(HitOrMiss::MISS);
+        let policy = self.policy(middleware, &res)?;
+        if self.decide(middleware, &res, &policy)?.cached {
+            Ok((self.put(middleware, res, policy).await?, true))
+        } else if !self.is_cacheable_method(middleware)? {
+            self.evict(middleware).await?;
+            Ok((res, false))
+        } else {
+            Ok((res, false))
+        }
+    }
+
+    // Whether the cache knows how to store responses with this status code
+    fn understands_status(
+        &self,
+        middleware: &impl Middleware,
+        res: &HttpResponse,
+    ) -> Result<bool> {
+        Ok(match res.status {
+            // Permanent redirects are cacheable by default like a 200
+            // (https://www.rfc-editor.org/rfc/rfc9110#section-15.1)
+            200 | 301 | 308 => true,
+            // Partial responses are only cached under a key including their range
+            206 if self.options.range(&middleware.parts()?).is_some() => true,
+            _ => self.options.negative_ttl(res).is_some(),
+        })
+    }
+
+    fn rewrite_response(
+        &self,
+        middleware: &impl Middleware,
+        res: &mut HttpResponse,
+    ) -> Result<()> {
+        if let Some(rewrite) = &self.options.response_header_rewrite_fn {
+            rewrite(&middleware.parts()?, res);
+        }
+        Ok(())
+    }
+
+    // Decides whether a response fetched from the origin is cached,
+    // passing the decision to the `on_cache_decision` callback
+    fn decide(
+        &self,
+        middleware: &impl Middleware,
+        res: &HttpResponse,
+        policy: &CachePolicy,
+    ) -> Result<CacheDecision> {
+        let decision = self.cache_decision(middleware, res, policy)?;
+        if let Some(on_cache_decision) = &self.options.on_cache_decision {
+            on_cache_decision(&decision);
+        }
+        Ok(decision)
+    }
+
+    fn cache_decision(
+        &self,
+        middleware: &impl Middleware,
+        res: &HttpResponse,
+        policy: &CachePolicy,
+    ) -> Result<CacheDecision> {
         let mode = self.cache_mode(middleware)?;
-        let mut is_cacheable = is_get_head
-            && mode != CacheMode::NoStore
-            && mode != CacheMode::Reload
-            && res.status == 200
-            && policy.is_storable();
-        if mode == CacheMode::IgnoreRules && res.status == 200 {
-            is_cacheable = true;
-        }
-        if is_cacheable {
-            Ok(self
-                .manager
-                .put(
-                    self.options.create_cache_key(&middleware.parts()?, None),
-                    res,
-                    policy,
-                )
-                .await?)
-        } else if !is_get_head {
-            self.manager
-                .delete(
-                    &self
-                        .options
-                        .create_cache_key(&middleware.parts()?, Some("GET")),
-                )
-                .await
-                .ok();
-            Ok(res)
+        let is_storable = policy.is_storable();
+        let directives: Vec<String> = res
+            .headers
+            .get(CACHE_CONTROL.as_str())
+            .map(|val| {
+                val.split(',')
+                    .map(|d| d.trim().to_string())
+                    .filter(|d| !d.is_empty())
+                    .collect()
+            })
+            .unwrap_or_default();
+        let has = |directive: &str| {
+            directives.iter().any(|d| has_directive(d, directive))
+        };
+        let parts = middleware.parts()?;
+        let mut reason = if mode == CacheMode::IgnoreRules && res.status == 200
+        {
+            CacheDecisionReason::IgnoreRules
+        } else if !self.is_cacheable_method(middleware)? {
+            CacheDecisionReason::NotGetOrHead
+        } else if mode == CacheMode::NoStore {
+            CacheDecisionReason::ModeNoStore
+        } else if mode == CacheMode::Reload {
+            CacheDecisionReason::ModeReload
+        } else if !self.understands_status(middleware, res)? {
+            // This is also what keeps responses with `must-understand` and a status
+            // the cache doesn't know from being stored
+            CacheDecisionReason::UncacheableStatus
+        } else if is_storable {
+            CacheDecisionReason::Storable
+        } else if has("no-store") {
+            CacheDecisionReason::NoStore
+        } else if has("private") {
+            CacheDecisionReason::Private
         } else {
-            Ok(res)
+            CacheDecisionReason::NotStorable
+        };
+        let mut cached = matches!(
+            reason,
+            CacheDecisionReason::Storable | CacheDecisionReason::IgnoreRules
+        );
+        if cached && res.has_vary_star() {
+            reason = CacheDecisionReason::VaryStar;
+            cached = false;
+        }
+        if cached && self.options.skip_cache_for_authorization(&parts, res) {
+            reason = CacheDecisionReason::Authorization;
+            cached = false;
         }
+        if cached && self.options.skip_cache_for_cookie(&parts, res) {
+            reason = CacheDecisionReason::Cookie;
+            cached = false;
+        }
+        if cached && !self.options.within_header_limits(res) {
+            reason = CacheDecisionReason::HeaderLimits;
+            cached = false;
+        }
+        Ok(CacheDecision {
+            cache_key: self.cache_key(middleware)?,
+            status: res.status,
+            directives,
+            mode,
+            is_storable,
+            cached,
+            reason,
+        })
     }
 
     async fn conditional_fetch(
@@ -652,16 +2852,54 @@ impl<T: CacheManager> HttpCache<T> {
         mut middleware: impl Middleware,
         mut cached_res: HttpResponse,
         mut policy: CachePolicy,
+        cache_key: String,
+        status: &mut HitStatus,
     ) -> Result<HttpResponse> {
-        let before_req =
-            policy.before_request(&middleware.parts()?, SystemTime::now());
+        // An immutable response won't change during its freshness lifetime,
+        // so there is nothing to gain from revalidating it.
+        // (https://www.rfc-editor.org/rfc/rfc8246#section-2)
+        // A request that bounds the age it will accept, such as `max-age=0`,
+        // is still checked against the policy below.
+        // (https://www.rfc-editor.org/rfc/rfc7234#section-5.2.1.1)
+        let parts = middleware.parts()?;
+        let bounds_freshness =
+            parts.headers.get_all(CACHE_CONTROL).iter().any(|val| {
+                val.to_str().map_or(false, |val| {
+                    has_directive(val, "max-age")
+                        || has_directive(val, "min-fresh")
+                })
+            });
+        // A response past the max fresh lifetime is revalidated as if it were stale
+        let past_max_fresh_lifetime =
+            self.past_max_fresh_lifetime(&policy, SystemTime::now());
+        if past_max_fresh_lifetime {
+            middleware.force_no_cache()?;
+        }
+        if cached_res.is_immutable()
+            && !bounds_freshness
+            && !past_max_fresh_lifetime
+            && !policy.is_stale(SystemTime::now())
+        {
+            cached_res.update_age(&policy);
+            status.cache = Some(HitOrMiss::HIT);
+            status.lookup = Some(HitOrMiss::HIT);
+            self.publish(CacheEvent::Hit(cache_key));
+            return Ok(cached_res);
+        }
+        let parts = self.policy_parts(&middleware)?;
+        let before_req = match &self.options.pre_key_request_fn {
+            Some(pre_key_request_fn) => policy.before_request(
+                &KeyedRequest::new(&parts, pre_key_request_fn)?,
+                SystemTime::now(),
+            ),
+            None => policy.before_request(&parts, SystemTime::now()),
+        };
         match before_req {
             BeforeRequest::Fresh(parts) => {
                 cached_res.update_headers(&parts)?;
-                if self.options.cache_status_headers {
-                    cached_res.cache_status(HitOrMiss::HIT);
-                    cached_res.cache_lookup_status(HitOrMiss::HIT);
-                }
+                status.cache = Some(HitOrMiss::HIT);
+                status.lookup = Some(HitOrMiss::HIT);
+                self.publish(CacheEvent::Hit(cache_key));
                 return Ok(cached_res);
             }
             BeforeRequest::Stale { request: parts, matches } => {
@@ -671,10 +2909,27 @@ impl<T: CacheManager> HttpCache<T> {
             }
         }
         let req_url = middleware.url()?;
-        match middleware.remote_fetch().await {
+        // The manager may already know whether the cached response is still valid
+        let revalidated =
+            self.manager.revalidate(&cache_key, &middleware.parts()?).await?;
+        let fetched = match revalidated {
+            Some(RevalidationOutcome::NotModified(headers)) => {
+                Ok(HttpResponse {
+                    body: Vec::new(),
+                    headers,
+                    status: 304,
+                    url: cached_res.url.clone(),
+                    version: cached_res.version,
+                })
+            }
+            Some(RevalidationOutcome::Modified(res)) => Ok(res),
+            None => middleware.remote_fetch().await,
+        };
+        match fetched {
             Ok(mut cond_res) => {
-                let status = StatusCode::from_u16(cond_res.status)?;
-                if status.is_server_error() && cached_res.must_revalidate() {
+                self.rewrite_response(&middleware, &mut cond_res)?;
+                let code = StatusCode::from_u16(cond_res.status)?;
+                if code.is_server_error() && cached_res.must_revalidate() {
                     //   111 Revalidation failed
                     //   MUST be included if a cache returns a stale response
                     //   because an attempt to revalidate the response failed,
@@ -685,13 +2940,16 @@ impl<T: CacheManager> HttpCache<T> {
                         111,
                         "Revalidation failed",
                     );
-                    if self.options.cache_status_headers {
-                        cached_res.cache_status(HitOrMiss::HIT);
-                    }
+                    self.stale_served(
+                        &req_url,
+                        StaleReason::RevalidationFailed,
+                    );
+                    status.cache = Some(HitOrMiss::HIT);
+                    self.publish(CacheEvent::Hit(cache_key));
                     Ok(cached_res)
                 } else if cond_res.status == 304 {
                     let after_res = policy.after_response(
-                        &middleware.parts()?,
+                        &self.policy_parts(&middleware)?,
                         &cond_res.parts()?,
                         SystemTime::now(),
                     );
@@ -702,49 +2960,40 @@ impl<T: CacheManager> HttpCache<T> {
                             cached_res.update_headers(&parts)?;
                         }
                     }
-                    if self.options.cache_status_headers {
-                        cached_res.cache_status(HitOrMiss::HIT);
-                        cached_res.cache_lookup_status(HitOrMiss::HIT);
+                    // The policy only takes over headers the cached response already
+                    // had, so a `vary` header first sent with the 304 is added here and
+                    // the policy recreated, otherwise it would be served for any request
+                    if let Some(vary) = cond_res.headers.get(VARY.as_str()) {
+                        if cached_res.headers.get(VARY.as_str()) != Some(vary) {
+                            cached_res
+                                .headers
+                                .insert(VARY.to_string(), vary.clone());
+                            policy = self.policy(&middleware, &cached_res)?;
+                        }
                     }
-                    let res = self
-                        .manager
-                        .put(
-                            self.options
-                                .create_cache_key(&middleware.parts()?, None),
-                            cached_res,
-                            policy,
-                        )
-                        .await?;
-                    Ok(res)
-                } else if cond_res.status == 200 {
-                    let policy = match self.options.cache_options {
-                        Some(options) => middleware
-                            .policy_with_options(&cond_res, options)?,
-                        None => middleware.policy(&cond_res)?,
-                    };
-                    if self.options.cache_status_headers {
-                        cond_res.cache_status(HitOrMiss::MISS);
-                        cond_res.cache_lookup_status(HitOrMiss::HIT);
+                    status.cache = Some(HitOrMiss::HIT);
+                    status.lookup = Some(HitOrMiss::HIT);
+                    self.publish(CacheEvent::Hit(cache_key));
+                    self.put(&middleware, cached_res, policy).await
+                } else if matches!(cond_res.status, 200 | 301 | 308) {
+                    let policy = self.policy(&middleware, &cond_res)?;
+                    status.cache = Some(HitOrMiss::MISS);
+                    status.lookup = Some(HitOrMiss::HIT);
+                    if !self.decide(&middleware, &cond_res, &policy)?.cached {
+                        return Ok(cond_res);
                     }
-                    let res = self
-                        .manager
-                        .put(
-                            self.options
-                                .create_cache_key(&middleware.parts()?, None),
-                            cond_res,
-                            policy,
-                        )
-                        .await?;
-                    Ok(res)
+                    self.put(&middleware, cond_res, policy).await
                 } else {
-                    if self.options.cache_status_headers {
-                        cached_res.cache_status(HitOrMiss::HIT);
-                    }
+                    status.cache = Some(HitOrMiss::HIT);
+                    self.publish(CacheEvent::Hit(cache_key));
                     Ok(cached_res)
                 }
             }
             Err(e) => {
-                if cached_res.must_revalidate() {
+                if cached_res.must_revalidate()
+                    || !self
+                        .within_max_served_staleness(&policy, SystemTime::now())
+                {
                     Err(e)
                 } else {
                     //   111 Revalidation failed
@@ -757,9 +3006,12 @@ impl<T: CacheManager> HttpCache<T> {
                         111,
                         "Revalidation failed",
                     );
-                    if self.options.cache_status_headers {
-                        cached_res.cache_status(HitOrMiss::HIT);
-                    }
+                    self.stale_served(
+                        &req_url,
+                        StaleReason::RevalidationFailed,
+                    );
+                    status.cache = Some(HitOrMiss::HIT);
+                    self.publish(CacheEvent::Hit(cache_key));
                     Ok(cached_res)
                 }
             }
@@ -1,7 +1,14 @@
-use std::path::PathBuf;
+use std::{
+    collections::{BTreeMap, BTreeSet, HashMap},
+    path::{Path, PathBuf},
+    str::FromStr,
+    sync::{Mutex, MutexGuard},
+    time::{SystemTime, UNIX_EPOCH},
+};
 
-use crate::{CacheManager, HttpResponse, Result};
+use crate::{CacheManager, DeserializeErrorPolicy, HttpResponse, Result};
 
+use cacache::{Integrity, Metadata, Value, WriteOpts};
 use http_cache_semantics::CachePolicy;
 use serde::{Deserialize, Serialize};
 
@@ -11,11 +18,26 @@ use serde::{Deserialize, Serialize};
 pub struct CACacheManager {
     /// Directory where the cache will be stored.
     pub path: PathBuf,
+    /// How entries that fail to deserialize are handled.
+    pub on_deserialize_error: DeserializeErrorPolicy,
+    /// The total size in bytes of the cached entries, when set the oldest entries
+    /// are evicted after each `put` until the cache is back under the limit.
+    /// Content shared by several entries is counted once. The size is read from the
+    /// index once and then tracked as entries are written and deleted in this process.
+    pub max_size: Option<u64>,
+    /// Store the bodies of responses with a strong etag separately from the rest of
+    /// the entry, so entries with the same body and etag share a single copy of it.
+    pub dedup_by_etag: bool,
 }
 
 impl Default for CACacheManager {
     fn default() -> Self {
-        Self { path: "./http-cacache".into() }
+        Self {
+            path: "./http-cacache".into(),
+            on_deserialize_error: DeserializeErrorPolicy::default(),
+            max_size: None,
+            dedup_by_etag: false,
+        }
     }
 }
 
@@ -25,13 +47,203 @@ struct Store {
     policy: CachePolicy,
 }
 
+// The integrity of a body stored apart from its entry, see `dedup_by_etag`
+fn body_integrity(metadata: &Metadata) -> Option<Integrity> {
+    let raw = metadata.raw_metadata.as_deref()?;
+    Integrity::from_str(std::str::from_utf8(raw).ok()?).ok()
+}
+
+// The contents an entry refers to, with their sizes
+fn contents(metadata: &Metadata) -> Vec<(Integrity, u64)> {
+    let mut contents = vec![(metadata.integrity.clone(), metadata.size as u64)];
+    if let Some(body) = body_integrity(metadata) {
+        // The size of a body stored apart is kept in the entry's JSON metadata
+        contents.push((body, metadata.metadata.as_u64().unwrap_or_default()));
+    }
+    contents
+}
+
+fn now_millis() -> u128 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |now| now.as_millis())
+}
+
+// The usage of each cache directory a size limit is enforced for. It's keyed by the
+// canonical path rather than kept per manager, as every manager writing to a directory
+// shares its size, however the path is spelled. Directories that have been removed are
+// dropped the next time a limit is enforced.
+static USAGE: Mutex<BTreeMap<PathBuf, Usage>> = Mutex::new(BTreeMap::new());
+
+fn usage() -> MutexGuard<'static, BTreeMap<PathBuf, Usage>> {
+    USAGE.lock().unwrap_or_else(|poisoned| poisoned.into_inner())
+}
+
+// Tracks the size of a cache directory as entries are written and removed,
+// counting content shared by several entries once
+#[derive(Debug, Default)]
+struct Usage {
+    // The time each entry was written and the contents it refers to
+    entries: HashMap<String, (u128, Vec<(Integrity, u64)>)>,
+    // Entries ordered from the oldest
+    by_time: BTreeSet<(u128, String)>,
+    // The number of entries referring to each content
+    refs: HashMap<Integrity, usize>,
+    total: u64,
+}
+
+impl Usage {
+    fn load(path: &Path) -> Result<Self> {
+        let mut usage = Self::default();
+        for metadata in cacache::list_sync(path) {
+            let metadata = metadata?;
+            let contents = contents(&metadata);
+            usage.insert(metadata.key, metadata.time, contents);
+        }
+        Ok(usage)
+    }
+
+    // Returns the contents no entry refers to anymore
+    fn insert(
+        &mut self,
+        key: String,
+        time: u128,
+        contents: Vec<(Integrity, u64)>,
+    ) -> Vec<Integrity> {
+        // Added before the replaced entry is removed so content it shares isn't orphaned
+        for (integrity, size) in &contents {
+            let refs = self.refs.entry(integrity.clone()).or_default();
+            if *refs == 0 {
+                self.total += size;
+            }
+            *refs += 1;
+        }
+        let orphaned = self.remove(&key);
+        self.by_time.insert((time, key.clone()));
+        self.entries.insert(key, (time, contents));
+        orphaned
+    }
+
+    // Returns the contents no entry refers to anymore
+    fn remove(&mut self, key: &str) -> Vec<Integrity> {
+        let Some((time, contents)) = self.entries.remove(key) else {
+            return Vec::new();
+        };
+        self.by_time.remove(&(time, key.to_string()));
+        let mut orphaned = Vec::new();
+        for (integrity, size) in contents {
+            if let Some(refs) = self.refs.get_mut(&integrity) {
+                *refs -= 1;
+                if *refs == 0 {
+                    self.refs.remove(&integrity);
+                    self.total = self.total.saturating_sub(size);
+                    orphaned.push(integrity);
+                }
+            }
+        }
+        orphaned
+    }
+
+    // Removes the oldest entries until the total is within `max_size`,
+    // returning their keys and the contents no entry refers to anymore
+    fn evict(&mut self, max_size: u64) -> (Vec<String>, Vec<Integrity>) {
+        let mut keys = Vec::new();
+        let mut orphaned = Vec::new();
+        while self.total > max_size {
+            let Some((_, key)) = self.by_time.iter().next().cloned() else {
+                break;
+            };
+            orphaned.extend(self.remove(&key));
+            keys.push(key);
+        }
+        (keys, orphaned)
+    }
+}
+
 #[allow(dead_code)]
 impl CACacheManager {
+    /// Create a new manager storing the cache in `path`, evicting the oldest entries
+    /// once their total size exceeds `max_bytes`.
+    pub fn with_max_size(path: impl Into<PathBuf>, max_bytes: u64) -> Self {
+        Self {
+            path: path.into(),
+            max_size: Some(max_bytes),
+            ..Default::default()
+        }
+    }
+
+    /// Evicts the oldest entries, by the time they were written, until the total size
+    /// of the cache is within [`CACacheManager::max_size`]. Does nothing without a limit.
+    pub async fn enforce_size_limit(&self) -> Result<()> {
+        let Some(max_size) = self.max_size else {
+            return Ok(());
+        };
+        // Nothing has been written yet if the directory doesn't exist
+        let Some(dir) = self.canonical_path() else {
+            return Ok(());
+        };
+        let (keys, orphaned) = {
+            let mut usage = usage();
+            if !usage.contains_key(&dir) {
+                usage.retain(|path, _| path.exists());
+                usage.insert(dir.clone(), Usage::load(&dir)?);
+            }
+            usage
+                .get_mut(&dir)
+                .map(|usage| usage.evict(max_size))
+                .unwrap_or_default()
+        };
+        for key in keys {
+            cacache::remove(&self.path, &key).await?;
+        }
+        self.remove_contents(orphaned).await
+    }
+
     /// Clears out the entire cache.
     pub async fn clear(&self) -> Result<()> {
+        let dir = self.canonical_path();
         cacache::clear(&self.path).await?;
+        if let Some(dir) = dir {
+            usage().remove(&dir);
+        }
+        Ok(())
+    }
+
+    // The path the usage of the directory is tracked under, if it exists
+    fn canonical_path(&self) -> Option<PathBuf> {
+        std::fs::canonicalize(&self.path).ok()
+    }
+
+    // Keeps the usage up to date once a size limit has been enforced for the path,
+    // returning the contents no entry refers to anymore
+    fn track(
+        &self,
+        update: impl FnOnce(&mut Usage) -> Vec<Integrity>,
+    ) -> Vec<Integrity> {
+        let Some(dir) = self.canonical_path() else {
+            return Vec::new();
+        };
+        usage().get_mut(&dir).map(update).unwrap_or_default()
+    }
+
+    async fn remove_contents(&self, contents: Vec<Integrity>) -> Result<()> {
+        for integrity in contents {
+            cacache::remove_hash(&self.path, &integrity).await?;
+        }
         Ok(())
     }
+
+    /// Returns the [subresource integrity](https://w3c.github.io/webappsec-subresource-integrity/)
+    /// string of the content stored for a cache key, if there is an entry.
+    /// Identical content is stored once, so entries sharing content share an integrity.
+    pub async fn integrity_for(
+        &self,
+        cache_key: &str,
+    ) -> Result<Option<String>> {
+        Ok(cacache::metadata(&self.path, cache_key)
+            .await?
+            .map(|metadata| metadata.integrity.to_string()))
+    }
 }
 
 #[async_trait::async_trait]
@@ -40,12 +252,37 @@ impl CacheManager for CACacheManager {
         &self,
         cache_key: &str,
     ) -> Result<Option<(HttpResponse, CachePolicy)>> {
-        let store: Store = match cacache::read(&self.path, cache_key).await {
-            Ok(d) => bincode::deserialize(&d)?,
-            Err(_e) => {
-                return Ok(None);
+        let metadata = match cacache::metadata(&self.path, cache_key).await {
+            Ok(Some(metadata)) => metadata,
+            Ok(None) | Err(_) => return Ok(None),
+        };
+        let data =
+            match cacache::read_hash(&self.path, &metadata.integrity).await {
+                Ok(d) => d,
+                Err(_e) => {
+                    return Ok(None);
+                }
+            };
+        let mut store: Store = match bincode::deserialize(&data) {
+            Ok(store) => store,
+            Err(e) => {
+                return match self.on_deserialize_error {
+                    DeserializeErrorPolicy::Error => Err(e.into()),
+                    DeserializeErrorPolicy::TreatAsMiss => Ok(None),
+                    DeserializeErrorPolicy::DeleteAndMiss => {
+                        self.delete(cache_key).await?;
+                        Ok(None)
+                    }
+                };
             }
         };
+        if let Some(body) = body_integrity(&metadata) {
+            store.response.body =
+                match cacache::read_hash(&self.path, &body).await {
+                    Ok(body) => body,
+                    Err(_e) => return Ok(None),
+                };
+        }
         Ok(Some((store.response, store.policy)))
     }
 
@@ -55,13 +292,81 @@ impl CacheManager for CACacheManager {
         response: HttpResponse,
         policy: CachePolicy,
     ) -> Result<HttpResponse> {
-        let data = Store { response: response.clone(), policy };
-        let bytes = bincode::serialize(&data)?;
-        cacache::write(&self.path, cache_key, bytes).await?;
+        let is_strong_etag =
+            response.etag().map_or(false, |etag| !etag.starts_with("W/"));
+        if self.dedup_by_etag && is_strong_etag && !response.body.is_empty() {
+            // The body is content addressed, so it's stored once however many
+            // entries refer to it, the entry keeps its integrity as metadata
+            let body = cacache::write_hash(&self.path, &response.body).await?;
+            let data = Store {
+                response: HttpResponse { body: Vec::new(), ..response.clone() },
+                policy,
+            };
+            let bytes = bincode::serialize(&data)?;
+            let integrity = cacache::write_hash(&self.path, &bytes).await?;
+            let contents = vec![
+                (integrity.clone(), bytes.len() as u64),
+                (body.clone(), response.body.len() as u64),
+            ];
+            let opts = WriteOpts::new()
+                .integrity(integrity)
+                .size(bytes.len())
+                .metadata(Value::from(response.body.len()))
+                .raw_metadata(body.to_string().into_bytes());
+            cacache::index::insert_async(self.path.as_ref(), &cache_key, opts)
+                .await?;
+            let orphaned = self
+                .track(|usage| usage.insert(cache_key, now_millis(), contents));
+            self.remove_contents(orphaned).await?;
+        } else {
+            let data = Store { response: response.clone(), policy };
+            let bytes = bincode::serialize(&data)?;
+            let size = bytes.len() as u64;
+            let integrity =
+                cacache::write(&self.path, &cache_key, bytes).await?;
+            let orphaned = self.track(|usage| {
+                usage.insert(cache_key, now_millis(), vec![(integrity, size)])
+            });
+            self.remove_contents(orphaned).await?;
+        }
+        self.enforce_size_limit().await?;
         Ok(response)
     }
 
+    #[cfg(feature = "export")]
+    async fn export(
+        &self,
+        writer: &mut (dyn futures::io::AsyncWrite + Send + Unpin),
+    ) -> Result<()> {
+        for metadata in cacache::list_sync(&self.path) {
+            let key = metadata?.key;
+            if let Some((response, policy)) = self.get(&key).await? {
+                crate::export::write_entry(writer, key, response, policy)
+                    .await?;
+            }
+        }
+        Ok(())
+    }
+
+    #[cfg(feature = "export")]
+    async fn import(
+        &self,
+        reader: &mut (dyn futures::io::AsyncBufRead + Send + Unpin),
+    ) -> Result<usize> {
+        crate::export::import_entries(self, reader).await
+    }
+
+    async fn keys(&self) -> Result<Vec<String>> {
+        let mut keys = Vec::new();
+        for metadata in cacache::list_sync(&self.path) {
+            keys.push(metadata?.key);
+        }
+        Ok(keys)
+    }
+
     async fn delete(&self, cache_key: &str) -> Result<()> {
-        Ok(cacache::remove(&self.path, cache_key).await?)
+        cacache::remove(&self.path, cache_key).await?;
+        let orphaned = self.track(|usage| usage.remove(cache_key));
+        self.remove_contents(orphaned).await
     }
 }
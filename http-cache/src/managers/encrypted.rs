@@ -0,0 +1,221 @@
+use std::{collections::HashMap, fmt, future::Future, time::SystemTime};
+
+use crate::{CacheManager, EncryptionError, HttpResponse, Result};
+
+use chacha20poly1305::{
+    aead::{Aead, AeadCore, KeyInit, OsRng, Payload},
+    ChaCha20Poly1305, Key, Nonce,
+};
+use http::header::CACHE_CONTROL;
+use http_cache_semantics::CachePolicy;
+
+// Length in bytes of the nonce stored in front of the ciphertext
+const NONCE_LEN: usize = 12;
+
+// The cache control of the placeholder response stored by the inner manager
+const SEALED_CACHE_CONTROL: &str = "max-age=31536000, immutable";
+
+/// Implements [`CacheManager`] by encrypting entries with
+/// [ChaCha20-Poly1305](https://www.rfc-editor.org/rfc/rfc8439) before handing them
+/// to another manager, e.g. a disk backed `CACacheManager`.
+///
+/// The response, including its headers, and the cache policy are encrypted together
+/// with a random nonce stored alongside the ciphertext. The inner manager only sees
+/// the cache key, the url and a placeholder response. Entries are bound to their
+/// cache key, so an entry moved to another key fails to decrypt.
+///
+/// The placeholder is fresh for a year, so an inner manager that expires entries by
+/// their policy, like `MokaManager::with_policy_ttl`, keeps them for that long.
+/// Expired entries are removed by [`CacheManager::drain_expired`] on this manager,
+/// which decrypts them to read their policy.
+#[cfg_attr(docsrs, doc(cfg(feature = "encryption")))]
+#[derive(Clone)]
+pub struct EncryptedManager<M: CacheManager> {
+    /// Manager that stores the encrypted entries
+    pub inner: M,
+    cipher: ChaCha20Poly1305,
+}
+
+impl<M: CacheManager + fmt::Debug> fmt::Debug for EncryptedManager<M> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        // The key is deliberately left out
+        f.debug_struct("EncryptedManager")
+            .field("inner", &self.inner)
+            .finish_non_exhaustive()
+    }
+}
+
+impl<M: CacheManager> EncryptedManager<M> {
+    /// Create a new manager encrypting the entries stored in `inner` with a 32-byte key
+    pub fn new(inner: M, key: [u8; 32]) -> Self {
+        Self { inner, cipher: ChaCha20Poly1305::new(Key::from_slice(&key)) }
+    }
+}
+
+impl<M: CacheManager> EncryptedManager<M> {
+    // Encrypts the response and policy into the placeholder response stored by the inner manager
+    fn seal(
+        &self,
+        cache_key: &str,
+        response: &HttpResponse,
+        policy: &CachePolicy,
+    ) -> Result<(HttpResponse, CachePolicy)> {
+        let plaintext = bincode::serialize(&(response, policy))?;
+        let nonce = ChaCha20Poly1305::generate_nonce(&mut OsRng);
+        let ciphertext = self
+            .cipher
+            .encrypt(
+                &nonce,
+                Payload { msg: &plaintext, aad: cache_key.as_bytes() },
+            )
+            .map_err(|_| EncryptionError)?;
+        let mut body = nonce.to_vec();
+        body.extend(ciphertext);
+        // The placeholder never goes stale, so the inner manager doesn't expire the
+        // entry on its own, the real policy is part of the ciphertext
+        let sealed = HttpResponse {
+            body,
+            headers: HashMap::from([(
+                CACHE_CONTROL.as_str().to_string(),
+                SEALED_CACHE_CONTROL.to_string(),
+            )]),
+            status: 200,
+            url: response.url.clone(),
+            version: response.version,
+        };
+        let req = http::Request::get(response.url.as_str()).body(())?;
+        let sealed_policy = CachePolicy::new(&req, &sealed.parts()?);
+        Ok((sealed, sealed_policy))
+    }
+
+    // Decrypts the response and policy from a placeholder response read from the inner manager
+    fn open(
+        &self,
+        cache_key: &str,
+        sealed: &HttpResponse,
+    ) -> Result<(HttpResponse, CachePolicy)> {
+        if sealed.body.len() < NONCE_LEN {
+            return Err(Box::new(EncryptionError));
+        }
+        let (nonce, ciphertext) = sealed.body.split_at(NONCE_LEN);
+        let plaintext = self
+            .cipher
+            .decrypt(
+                Nonce::from_slice(nonce),
+                Payload { msg: ciphertext, aad: cache_key.as_bytes() },
+            )
+            .map_err(|_| EncryptionError)?;
+        Ok(bincode::deserialize(&plaintext)?)
+    }
+}
+
+#[async_trait::async_trait]
+impl<M: CacheManager> CacheManager for EncryptedManager<M> {
+    async fn get(
+        &self,
+        cache_key: &str,
+    ) -> Result<Option<(HttpResponse, CachePolicy)>> {
+        match self.inner.get(cache_key).await? {
+            Some((sealed, _)) => self.open(cache_key, &sealed).map(Some),
+            None => Ok(None),
+        }
+    }
+
+    async fn put(
+        &self,
+        cache_key: String,
+        response: HttpResponse,
+        policy: CachePolicy,
+    ) -> Result<HttpResponse> {
+        let (sealed, sealed_policy) =
+            self.seal(&cache_key, &response, &policy)?;
+        self.inner.put(cache_key, sealed, sealed_policy).await?;
+        Ok(response)
+    }
+
+    async fn get_many(
+        &self,
+        cache_keys: &[String],
+    ) -> Result<Vec<Option<(HttpResponse, CachePolicy)>>> {
+        let sealed = self.inner.get_many(cache_keys).await?;
+        cache_keys
+            .iter()
+            .zip(sealed)
+            .map(|(cache_key, sealed)| match sealed {
+                Some((sealed, _)) => self.open(cache_key, &sealed).map(Some),
+                None => Ok(None),
+            })
+            .collect()
+    }
+
+    fn supports_streaming(&self) -> bool {
+        self.inner.supports_streaming()
+    }
+
+    // The computed response is sealed before the inner manager stores it,
+    // and whichever response it returns is opened again
+    async fn get_or_put<F, Fut>(
+        &self,
+        cache_key: String,
+        compute: F,
+    ) -> Result<HttpResponse>
+    where
+        F: FnOnce() -> Fut + Send,
+        Fut: Future<Output = Result<(HttpResponse, CachePolicy)>> + Send,
+    {
+        let key = &cache_key;
+        let sealed = self
+            .inner
+            .get_or_put(cache_key.clone(), move || async move {
+                let (response, policy) = compute().await?;
+                self.seal(key, &response, &policy)
+            })
+            .await?;
+        let (response, _) = self.open(&cache_key, &sealed)?;
+        Ok(response)
+    }
+
+    // The entries are exported and imported sealed, so they can only be read
+    // by a manager with the same key
+    async fn export(
+        &self,
+        writer: &mut (dyn futures::io::AsyncWrite + Send + Unpin),
+    ) -> Result<()> {
+        self.inner.export(writer).await
+    }
+
+    async fn import(
+        &self,
+        reader: &mut (dyn futures::io::AsyncBufRead + Send + Unpin),
+    ) -> Result<usize> {
+        self.inner.import(reader).await
+    }
+
+    async fn keys(&self) -> Result<Vec<String>> {
+        self.inner.keys().await
+    }
+
+    // The inner manager only sees the placeholder policy, so the entries are
+    // decrypted to check them against their own
+    async fn drain_expired(&self) -> Result<usize> {
+        let now = SystemTime::now();
+        let mut drained = 0;
+        for cache_key in self.inner.keys().await? {
+            if let Some((response, policy)) = self.get(&cache_key).await? {
+                if crate::is_expired(&response, &policy, now) {
+                    self.inner.delete(&cache_key).await?;
+                    drained += 1;
+                }
+            }
+        }
+        Ok(drained)
+    }
+
+    async fn touch(&self, cache_key: &str) -> Result<bool> {
+        self.inner.touch(cache_key).await
+    }
+
+    async fn delete(&self, cache_key: &str) -> Result<()> {
+        self.inner.delete(cache_key).await
+    }
+}
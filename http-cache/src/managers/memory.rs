@@ -0,0 +1,177 @@
+use crate::{
+    single_flight::{InFlight, Join},
+    CacheManager, EntryPredicate, HttpResponse, Result,
+};
+
+use std::{
+    collections::HashMap,
+    fmt,
+    future::Future,
+    sync::{Arc, RwLock, RwLockReadGuard, RwLockWriteGuard},
+    time::SystemTime,
+};
+
+use http_cache_semantics::CachePolicy;
+
+type Entries = HashMap<String, (HttpResponse, CachePolicy)>;
+
+/// Implements [`CacheManager`] with a `HashMap` as the backend, without any dependencies.
+///
+/// Entries are kept until they are deleted, there is no capacity limit or eviction.
+/// It's meant for targets the other managers can't run on, like
+/// `wasm32-unknown-unknown` where there is no filesystem, and for tests.
+#[cfg_attr(docsrs, doc(cfg(feature = "manager-memory")))]
+#[derive(Clone, Default)]
+pub struct MemoryManager {
+    entries: Arc<RwLock<Entries>>,
+    // The entries `get_or_put` is computing, keyed by cache key
+    computing: InFlight,
+}
+
+impl fmt::Debug for MemoryManager {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("MemoryManager").finish_non_exhaustive()
+    }
+}
+
+impl MemoryManager {
+    /// Create a new, empty manager
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Clears out the entire cache.
+    pub fn clear(&self) {
+        self.write().clear();
+    }
+
+    // A panic while the lock was held can't leave the map itself inconsistent
+    fn read(&self) -> RwLockReadGuard<'_, Entries> {
+        self.entries.read().unwrap_or_else(|e| e.into_inner())
+    }
+
+    fn write(&self) -> RwLockWriteGuard<'_, Entries> {
+        self.entries.write().unwrap_or_else(|e| e.into_inner())
+    }
+}
+
+#[async_trait::async_trait]
+impl CacheManager for MemoryManager {
+    async fn get(
+        &self,
+        cache_key: &str,
+    ) -> Result<Option<(HttpResponse, CachePolicy)>> {
+        Ok(self.read().get(cache_key).cloned())
+    }
+
+    // Read under a single lock, so the results come from the same state
+    async fn get_many(
+        &self,
+        cache_keys: &[String],
+    ) -> Result<Vec<Option<(HttpResponse, CachePolicy)>>> {
+        let entries = self.read();
+        Ok(cache_keys.iter().map(|key| entries.get(key).cloned()).collect())
+    }
+
+    async fn put(
+        &self,
+        cache_key: String,
+        response: HttpResponse,
+        policy: CachePolicy,
+    ) -> Result<HttpResponse> {
+        self.write().insert(cache_key, (response.clone(), policy));
+        Ok(response)
+    }
+
+    // Concurrent callers for a key share a single computation, the others wait
+    // for it and read the entry it stored. Callers for other keys, and hits,
+    // don't wait on it.
+    async fn get_or_put<F, Fut>(
+        &self,
+        cache_key: String,
+        compute: F,
+    ) -> Result<HttpResponse>
+    where
+        F: FnOnce() -> Fut + Send,
+        Fut: Future<Output = Result<(HttpResponse, CachePolicy)>> + Send,
+    {
+        loop {
+            if let Some((response, _)) = self.get(&cache_key).await? {
+                return Ok(response);
+            }
+            match self.computing.join(cache_key.clone()) {
+                Join::Leader(leader) => {
+                    // The entry may have been stored since the check above
+                    if let Some((response, _)) = self.get(&cache_key).await? {
+                        leader.complete(true);
+                        return Ok(response);
+                    }
+                    // On error the leader is dropped, and a waiting caller
+                    // runs its own computation
+                    let (response, policy) = compute().await?;
+                    let response =
+                        self.put(cache_key, response, policy).await?;
+                    leader.complete(true);
+                    return Ok(response);
+                }
+                Join::Follower(follower) => {
+                    follower.wait().await;
+                }
+            }
+        }
+    }
+
+    #[cfg(feature = "export")]
+    async fn export(
+        &self,
+        writer: &mut (dyn futures::io::AsyncWrite + Send + Unpin),
+    ) -> Result<()> {
+        // Copied out so the lock isn't held across the writes
+        let entries = self.read().clone();
+        for (key, (response, policy)) in entries {
+            crate::export::write_entry(writer, key, response, policy).await?;
+        }
+        Ok(())
+    }
+
+    #[cfg(feature = "export")]
+    async fn import(
+        &self,
+        reader: &mut (dyn futures::io::AsyncBufRead + Send + Unpin),
+    ) -> Result<usize> {
+        crate::export::import_entries(self, reader).await
+    }
+
+    async fn keys(&self) -> Result<Vec<String>> {
+        Ok(self.read().keys().cloned().collect())
+    }
+
+    async fn delete_matching(
+        &self,
+        predicate: &EntryPredicate,
+    ) -> Result<usize> {
+        let mut entries = self.write();
+        let before = entries.len();
+        entries.retain(|key, (response, _)| !predicate(key, response));
+        Ok(before - entries.len())
+    }
+
+    async fn drain_expired(&self) -> Result<usize> {
+        let now = SystemTime::now();
+        let mut entries = self.write();
+        let before = entries.len();
+        entries.retain(|_, (response, policy)| {
+            !crate::is_expired(response, policy, now)
+        });
+        Ok(before - entries.len())
+    }
+
+    async fn touch(&self, cache_key: &str) -> Result<bool> {
+        Ok(self.read().contains_key(cache_key))
+    }
+
+    async fn delete(&self, cache_key: &str) -> Result<()> {
+        self.write().remove(cache_key);
+        Ok(())
+    }
+}
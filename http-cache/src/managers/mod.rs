@@ -3,3 +3,17 @@ pub mod cacache;
 
 #[cfg(feature = "manager-moka")]
 pub mod moka;
+
+#[cfg(feature = "manager-memory")]
+pub mod memory;
+
+pub mod retrying;
+
+pub mod timeout;
+
+pub mod namespaced;
+
+pub mod size_routing;
+
+#[cfg(feature = "encryption")]
+pub mod encrypted;
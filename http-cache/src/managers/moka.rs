@@ -1,11 +1,24 @@
-use crate::{CacheManager, HttpResponse, Result};
+use crate::{BoxError, CacheManager, EntryPredicate, HttpResponse, Result};
 
-use std::{fmt, sync::Arc};
+use std::{
+    error::Error,
+    fmt,
+    future::Future,
+    sync::Arc,
+    time::{Duration, Instant, SystemTime},
+};
 
 use http_cache_semantics::CachePolicy;
-use moka::future::Cache;
+use moka::{
+    future::{Cache, CacheBuilder},
+    notification::RemovalCause,
+    Expiry,
+};
 use serde::{Deserialize, Serialize};
 
+type MokaBuilder =
+    CacheBuilder<String, Arc<Vec<u8>>, Cache<String, Arc<Vec<u8>>>>;
+
 /// Implements [`CacheManager`] with [`moka`](https://github.com/moka-rs/moka) as the backend.
 #[cfg_attr(docsrs, doc(cfg(feature = "manager-moka")))]
 #[derive(Clone)]
@@ -33,17 +46,114 @@ struct Store {
     policy: CachePolicy,
 }
 
+// Error from a `get_or_put` computation, shared by every caller waiting on it
+#[derive(Debug)]
+struct SharedError(Arc<BoxError>);
+
+impl fmt::Display for SharedError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        self.0.fmt(f)
+    }
+}
+
+impl Error for SharedError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        self.0.source()
+    }
+}
+
+// Expires each entry once its stored policy is no longer fresh
+struct PolicyExpiry;
+
+impl PolicyExpiry {
+    fn time_to_live(value: &[u8]) -> Option<Duration> {
+        // Entries that can't be read are left to the capacity limit
+        let store: Store = bincode::deserialize(value).ok()?;
+        Some(store.policy.time_to_live(SystemTime::now()))
+    }
+}
+
+impl Expiry<String, Arc<Vec<u8>>> for PolicyExpiry {
+    fn expire_after_create(
+        &self,
+        _key: &String,
+        value: &Arc<Vec<u8>>,
+        _created_at: Instant,
+    ) -> Option<Duration> {
+        Self::time_to_live(value)
+    }
+
+    fn expire_after_update(
+        &self,
+        _key: &String,
+        value: &Arc<Vec<u8>>,
+        _updated_at: Instant,
+        _duration_until_expiry: Option<Duration>,
+    ) -> Option<Duration> {
+        Self::time_to_live(value)
+    }
+}
+
 impl MokaManager {
     /// Create a new manager from a pre-configured Cache
     pub fn new(cache: Cache<String, Arc<Vec<u8>>>) -> Self {
         Self { cache: Arc::new(cache) }
     }
+    /// Create a new manager from a cache builder, calling the listener with the
+    /// cache key and cause whenever an entry is evicted or otherwise removed.
+    ///
+    /// The listener is called synchronously by moka while it performs maintenance,
+    /// so it should be cheap and must not block.
+    pub fn with_eviction_listener<F>(builder: MokaBuilder, listener: F) -> Self
+    where
+        F: Fn(&str, RemovalCause) + Send + Sync + 'static,
+    {
+        Self::new(
+            builder
+                .eviction_listener(move |key: Arc<String>, _, cause| {
+                    listener(key.as_str(), cause)
+                })
+                .build(),
+        )
+    }
+    /// Create a new manager from a cache builder, expiring each entry once the
+    /// freshness lifetime of its cache policy has passed instead of leaving stale
+    /// entries in memory until the capacity limit is reached.
+    ///
+    /// Stale entries can no longer be revalidated with a conditional request,
+    /// so responses with validators are fetched in full again once expired.
+    pub fn with_policy_ttl(builder: MokaBuilder) -> Self {
+        Self::new(builder.expire_after(PolicyExpiry).build())
+    }
     /// Clears out the entire cache.
     pub async fn clear(&self) -> Result<()> {
         self.cache.invalidate_all();
         self.cache.run_pending_tasks().await;
         Ok(())
     }
+    /// Evicts entries until the cache is down to the given fraction of its capacity,
+    /// e.g. from a memory pressure monitor. When the cache is unbounded the fraction
+    /// is applied to its current size.
+    ///
+    /// Moka doesn't expose its eviction order, so the entries removed are arbitrary.
+    pub async fn trim_to(&self, fraction: f64) -> Result<()> {
+        self.cache.run_pending_tasks().await;
+        let fraction = fraction.clamp(0.0, 1.0);
+        let capacity = self
+            .cache
+            .policy()
+            .max_capacity()
+            .unwrap_or_else(|| self.cache.weighted_size());
+        let target = (capacity as f64 * fraction) as u64;
+        for (key, _) in self.cache.iter() {
+            if self.cache.weighted_size() <= target {
+                break;
+            }
+            self.cache.invalidate(key.as_str()).await;
+            self.cache.run_pending_tasks().await;
+        }
+        Ok(())
+    }
 }
 
 #[async_trait::async_trait]
@@ -72,6 +182,100 @@ impl CacheManager for MokaManager {
         Ok(response)
     }
 
+    async fn get_or_put<F, Fut>(
+        &self,
+        cache_key: String,
+        compute: F,
+    ) -> Result<HttpResponse>
+    where
+        F: FnOnce() -> Fut + Send,
+        Fut: Future<Output = Result<(HttpResponse, CachePolicy)>> + Send,
+    {
+        // Moka runs a single computation per key, concurrent callers wait for it
+        let bytes = self
+            .cache
+            .try_get_with(cache_key, async {
+                let (response, policy) = compute().await?;
+                let bytes = bincode::serialize(&Store { response, policy })?;
+                Ok::<_, BoxError>(Arc::new(bytes))
+            })
+            .await
+            .map_err(SharedError)?;
+        self.cache.run_pending_tasks().await;
+        let store: Store = bincode::deserialize(&bytes)?;
+        Ok(store.response)
+    }
+
+    #[cfg(feature = "export")]
+    async fn export(
+        &self,
+        writer: &mut (dyn futures::io::AsyncWrite + Send + Unpin),
+    ) -> Result<()> {
+        for (key, data) in self.cache.iter() {
+            let store: Store = bincode::deserialize(&data)?;
+            crate::export::write_entry(
+                writer,
+                key.to_string(),
+                store.response,
+                store.policy,
+            )
+            .await?;
+        }
+        Ok(())
+    }
+
+    #[cfg(feature = "export")]
+    async fn import(
+        &self,
+        reader: &mut (dyn futures::io::AsyncBufRead + Send + Unpin),
+    ) -> Result<usize> {
+        crate::export::import_entries(self, reader).await
+    }
+
+    async fn keys(&self) -> Result<Vec<String>> {
+        self.cache.run_pending_tasks().await;
+        Ok(self.cache.iter().map(|(key, _)| key.to_string()).collect())
+    }
+
+    async fn delete_matching(
+        &self,
+        predicate: &EntryPredicate,
+    ) -> Result<usize> {
+        let mut matching = Vec::new();
+        for (key, data) in self.cache.iter() {
+            let store: Store = bincode::deserialize(&data)?;
+            if predicate(key.as_str(), &store.response) {
+                matching.push(key);
+            }
+        }
+        for key in &matching {
+            self.cache.invalidate(key.as_str()).await;
+        }
+        self.cache.run_pending_tasks().await;
+        Ok(matching.len())
+    }
+
+    async fn drain_expired(&self) -> Result<usize> {
+        let now = SystemTime::now();
+        let mut expired = Vec::new();
+        for (key, data) in self.cache.iter() {
+            let store: Store = bincode::deserialize(&data)?;
+            if crate::is_expired(&store.response, &store.policy, now) {
+                expired.push(key);
+            }
+        }
+        for key in &expired {
+            self.cache.invalidate(key.as_str()).await;
+        }
+        self.cache.run_pending_tasks().await;
+        Ok(expired.len())
+    }
+
+    async fn touch(&self, cache_key: &str) -> Result<bool> {
+        // Reading an entry is enough for moka to record the access
+        Ok(self.cache.get(cache_key).await.is_some())
+    }
+
     async fn delete(&self, cache_key: &str) -> Result<()> {
         self.cache.invalidate(cache_key).await;
         self.cache.run_pending_tasks().await;
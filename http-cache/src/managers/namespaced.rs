@@ -0,0 +1,145 @@
+use std::{fmt, future::Future};
+
+use crate::{
+    CacheManager, EntryPredicate, HttpResponse, Result, RevalidationOutcome,
+};
+
+use http_cache_semantics::CachePolicy;
+
+/// Implements [`CacheManager`] by prefixing every cache key with a namespace before
+/// handing it to another manager, so several caches can share one backend without
+/// seeing each other's entries, e.g. two clients writing to the same `MokaManager`.
+///
+/// Keys are stored as `{namespace}:{key}`. [`CacheManager::keys`] and the deletion
+/// methods only cover the namespace's own entries, `drain_expired` applies to the
+/// whole backend since expired entries are of no use to any namespace.
+#[derive(Clone)]
+pub struct NamespacedManager<M: CacheManager> {
+    /// Manager the namespaced entries are stored in
+    pub inner: M,
+    prefix: String,
+}
+
+impl<M: CacheManager + fmt::Debug> fmt::Debug for NamespacedManager<M> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("NamespacedManager")
+            .field("inner", &self.inner)
+            .field("namespace", &self.namespace())
+            .finish()
+    }
+}
+
+impl<M: CacheManager> NamespacedManager<M> {
+    /// Create a new manager storing its entries in `inner` under the namespace
+    pub fn new(inner: M, namespace: impl Into<String>) -> Self {
+        Self { inner, prefix: format!("{}:", namespace.into()) }
+    }
+
+    /// Returns the namespace the keys are prefixed with
+    #[must_use]
+    pub fn namespace(&self) -> &str {
+        &self.prefix[..self.prefix.len() - 1]
+    }
+
+    fn key(&self, cache_key: &str) -> String {
+        format!("{}{cache_key}", self.prefix)
+    }
+}
+
+#[async_trait::async_trait]
+impl<M: CacheManager> CacheManager for NamespacedManager<M> {
+    async fn get(
+        &self,
+        cache_key: &str,
+    ) -> Result<Option<(HttpResponse, CachePolicy)>> {
+        self.inner.get(&self.key(cache_key)).await
+    }
+
+    async fn put(
+        &self,
+        cache_key: String,
+        response: HttpResponse,
+        policy: CachePolicy,
+    ) -> Result<HttpResponse> {
+        self.inner.put(self.key(&cache_key), response, policy).await
+    }
+
+    async fn get_many(
+        &self,
+        cache_keys: &[String],
+    ) -> Result<Vec<Option<(HttpResponse, CachePolicy)>>> {
+        let cache_keys: Vec<String> =
+            cache_keys.iter().map(|cache_key| self.key(cache_key)).collect();
+        self.inner.get_many(&cache_keys).await
+    }
+
+    fn supports_streaming(&self) -> bool {
+        self.inner.supports_streaming()
+    }
+
+    async fn get_or_put<F, Fut>(
+        &self,
+        cache_key: String,
+        compute: F,
+    ) -> Result<HttpResponse>
+    where
+        F: FnOnce() -> Fut + Send,
+        Fut: Future<Output = Result<(HttpResponse, CachePolicy)>> + Send,
+    {
+        self.inner.get_or_put(self.key(&cache_key), compute).await
+    }
+
+    async fn keys(&self) -> Result<Vec<String>> {
+        Ok(self
+            .inner
+            .keys()
+            .await?
+            .into_iter()
+            .filter_map(|key| {
+                key.strip_prefix(&self.prefix).map(str::to_string)
+            })
+            .collect())
+    }
+
+    // The predicate is borrowed, so it can't be passed on to the inner manager
+    // wrapped in a closure that strips the namespace
+    async fn delete_matching(
+        &self,
+        predicate: &EntryPredicate,
+    ) -> Result<usize> {
+        let mut deleted = 0;
+        for cache_key in self.keys().await? {
+            if let Some((response, _)) = self.get(&cache_key).await? {
+                if predicate(&cache_key, &response) {
+                    self.delete(&cache_key).await?;
+                    deleted += 1;
+                }
+            }
+        }
+        Ok(deleted)
+    }
+
+    async fn delete_prefix(&self, prefix: &str) -> Result<usize> {
+        self.inner.delete_prefix(&self.key(prefix)).await
+    }
+
+    async fn drain_expired(&self) -> Result<usize> {
+        self.inner.drain_expired().await
+    }
+
+    async fn revalidate(
+        &self,
+        cache_key: &str,
+        parts: &http::request::Parts,
+    ) -> Result<Option<RevalidationOutcome>> {
+        self.inner.revalidate(&self.key(cache_key), parts).await
+    }
+
+    async fn touch(&self, cache_key: &str) -> Result<bool> {
+        self.inner.touch(&self.key(cache_key)).await
+    }
+
+    async fn delete(&self, cache_key: &str) -> Result<()> {
+        self.inner.delete(&self.key(cache_key)).await
+    }
+}
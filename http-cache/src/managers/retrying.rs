@@ -0,0 +1,224 @@
+use std::{fmt, future::Future, sync::Arc, time::Duration};
+
+use crate::{
+    BoxError, CacheManager, EntryPredicate, HttpResponse, Result,
+    RevalidationOutcome,
+};
+
+use futures::future::BoxFuture;
+use http_cache_semantics::CachePolicy;
+
+/// Returns a future that completes after the given delay, see [`RetryingManager::with_sleeper`]
+pub type Sleeper =
+    Arc<dyn Fn(Duration) -> BoxFuture<'static, ()> + Send + Sync>;
+
+/// Decides whether a failed manager operation should be retried,
+/// see [`RetryingManager::with_retry_if`]
+pub type RetryPredicate = Arc<dyn Fn(&BoxError) -> bool + Send + Sync>;
+
+/// Implements [`CacheManager`] by retrying the `get`, `put` and `delete` operations
+/// of another manager when they fail, waiting with an exponential backoff between attempts.
+/// Useful for backends with transient errors, like a remote cache dropping a connection.
+///
+/// Every error is retried by default. The core crate doesn't depend on an async runtime,
+/// so the default backoff waits on a runtime-agnostic timer, use [`RetryingManager::with_sleeper`]
+/// to wait on the runtime's timer instead. Without the `futures-timer` feature there is no
+/// default timer, and one must be set before an operation is retried.
+#[derive(Clone)]
+pub struct RetryingManager<M: CacheManager> {
+    /// Manager the operations are retried against
+    pub inner: M,
+    max_retries: u32,
+    backoff: Duration,
+    sleeper: Sleeper,
+    retry_if: RetryPredicate,
+}
+
+impl<M: CacheManager + fmt::Debug> fmt::Debug for RetryingManager<M> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("RetryingManager")
+            .field("inner", &self.inner)
+            .field("max_retries", &self.max_retries)
+            .field("backoff", &self.backoff)
+            .finish_non_exhaustive()
+    }
+}
+
+impl<M: CacheManager> RetryingManager<M> {
+    /// Create a new manager retrying failed operations up to 3 times,
+    /// waiting 50ms before the first retry and doubling the wait for each one after
+    pub fn new(inner: M) -> Self {
+        Self {
+            inner,
+            max_retries: 3,
+            backoff: Duration::from_millis(50),
+            sleeper: Arc::new(timer_sleep),
+            retry_if: Arc::new(|_| true),
+        }
+    }
+
+    /// Sets the number of times a failed operation is retried
+    #[must_use]
+    pub fn with_max_retries(mut self, max_retries: u32) -> Self {
+        self.max_retries = max_retries;
+        self
+    }
+
+    /// Sets the wait before the first retry, which doubles for each retry after it
+    #[must_use]
+    pub fn with_backoff(mut self, backoff: Duration) -> Self {
+        self.backoff = backoff;
+        self
+    }
+
+    /// Sets the function used to wait between attempts, e.g. `tokio::time::sleep`
+    #[must_use]
+    pub fn with_sleeper<F, Fut>(mut self, sleeper: F) -> Self
+    where
+        F: Fn(Duration) -> Fut + Send + Sync + 'static,
+        Fut: Future<Output = ()> + Send + 'static,
+    {
+        self.sleeper = Arc::new(move |delay| Box::pin(sleeper(delay)));
+        self
+    }
+
+    /// Only retries the errors the predicate returns true for
+    #[must_use]
+    pub fn with_retry_if<F>(mut self, retry_if: F) -> Self
+    where
+        F: Fn(&BoxError) -> bool + Send + Sync + 'static,
+    {
+        self.retry_if = Arc::new(retry_if);
+        self
+    }
+
+    async fn retry<T, F, Fut>(&self, operation: F) -> Result<T>
+    where
+        F: Fn() -> Fut,
+        Fut: Future<Output = Result<T>>,
+    {
+        let mut backoff = self.backoff;
+        let mut retries = 0;
+        loop {
+            match operation().await {
+                Err(e) if retries < self.max_retries && (self.retry_if)(&e) => {
+                    (self.sleeper)(backoff).await;
+                    backoff = backoff.saturating_mul(2);
+                    retries += 1;
+                }
+                result => return result,
+            }
+        }
+    }
+}
+
+// Doesn't require any particular async runtime, the timers share a single
+// background thread and are cancelled when dropped
+#[cfg(feature = "futures-timer")]
+pub(crate) fn timer_sleep(delay: Duration) -> BoxFuture<'static, ()> {
+    Box::pin(futures_timer::Delay::new(delay))
+}
+
+// Without a timer to fall back on, a sleeper has to be set before waiting
+#[cfg(not(feature = "futures-timer"))]
+pub(crate) fn timer_sleep(_delay: Duration) -> BoxFuture<'static, ()> {
+    Box::pin(async {
+        panic!("no default timer without the `futures-timer` feature, set one with `with_sleeper`")
+    })
+}
+
+#[async_trait::async_trait]
+impl<M: CacheManager> CacheManager for RetryingManager<M> {
+    async fn get(
+        &self,
+        cache_key: &str,
+    ) -> Result<Option<(HttpResponse, CachePolicy)>> {
+        self.retry(|| self.inner.get(cache_key)).await
+    }
+
+    async fn put(
+        &self,
+        cache_key: String,
+        response: HttpResponse,
+        policy: CachePolicy,
+    ) -> Result<HttpResponse> {
+        self.retry(|| {
+            self.inner.put(cache_key.clone(), response.clone(), policy.clone())
+        })
+        .await
+    }
+
+    async fn get_many(
+        &self,
+        cache_keys: &[String],
+    ) -> Result<Vec<Option<(HttpResponse, CachePolicy)>>> {
+        self.retry(|| self.inner.get_many(cache_keys)).await
+    }
+
+    fn supports_streaming(&self) -> bool {
+        self.inner.supports_streaming()
+    }
+
+    // The computation can only run once, so rather than retrying the inner
+    // manager's `get_or_put` the lookup and the store are retried on their own
+    async fn get_or_put<F, Fut>(
+        &self,
+        cache_key: String,
+        compute: F,
+    ) -> Result<HttpResponse>
+    where
+        F: FnOnce() -> Fut + Send,
+        Fut: Future<Output = Result<(HttpResponse, CachePolicy)>> + Send,
+    {
+        if let Some((response, _)) = self.get(&cache_key).await? {
+            return Ok(response);
+        }
+        let (response, policy) = compute().await?;
+        self.put(cache_key, response, policy).await
+    }
+
+    async fn keys(&self) -> Result<Vec<String>> {
+        self.inner.keys().await
+    }
+
+    async fn delete_matching(
+        &self,
+        predicate: &EntryPredicate,
+    ) -> Result<usize> {
+        self.inner.delete_matching(predicate).await
+    }
+
+    async fn export(
+        &self,
+        writer: &mut (dyn futures::io::AsyncWrite + Send + Unpin),
+    ) -> Result<()> {
+        self.inner.export(writer).await
+    }
+
+    async fn import(
+        &self,
+        reader: &mut (dyn futures::io::AsyncBufRead + Send + Unpin),
+    ) -> Result<usize> {
+        self.inner.import(reader).await
+    }
+
+    async fn revalidate(
+        &self,
+        cache_key: &str,
+        parts: &http::request::Parts,
+    ) -> Result<Option<RevalidationOutcome>> {
+        self.retry(|| self.inner.revalidate(cache_key, parts)).await
+    }
+
+    async fn drain_expired(&self) -> Result<usize> {
+        self.inner.drain_expired().await
+    }
+
+    async fn touch(&self, cache_key: &str) -> Result<bool> {
+        self.retry(|| self.inner.touch(cache_key)).await
+    }
+
+    async fn delete(&self, cache_key: &str) -> Result<()> {
+        self.retry(|| self.inner.delete(cache_key)).await
+    }
+}
@@ -0,0 +1,172 @@
+use std::future::Future;
+
+use crate::{
+    CacheManager, EntryPredicate, HttpResponse, Result, RevalidationOutcome,
+};
+
+use http_cache_semantics::CachePolicy;
+
+/// Implements [`CacheManager`] by routing responses to one of two managers based on
+/// the size of their body, e.g. small responses to an in-memory `MokaManager`
+/// and large ones to a disk backed `CACacheManager`.
+///
+/// Lookups check the manager for small responses first.
+#[derive(Debug, Clone)]
+pub struct SizeRoutingManager<S: CacheManager, L: CacheManager> {
+    /// Manager that stores responses with a body up to the threshold
+    pub small: S,
+    /// Manager that stores responses with a body over the threshold
+    pub large: L,
+    /// The largest body size in bytes that will be stored in the `small` manager
+    pub threshold: usize,
+}
+
+impl<S: CacheManager, L: CacheManager> SizeRoutingManager<S, L> {
+    /// Create a new manager routing bodies up to `threshold` bytes to `small`
+    pub fn new(small: S, large: L, threshold: usize) -> Self {
+        Self { small, large, threshold }
+    }
+}
+
+#[async_trait::async_trait]
+impl<S: CacheManager, L: CacheManager> CacheManager
+    for SizeRoutingManager<S, L>
+{
+    async fn get(
+        &self,
+        cache_key: &str,
+    ) -> Result<Option<(HttpResponse, CachePolicy)>> {
+        match self.small.get(cache_key).await? {
+            Some(store) => Ok(Some(store)),
+            None => self.large.get(cache_key).await,
+        }
+    }
+
+    async fn put(
+        &self,
+        cache_key: String,
+        response: HttpResponse,
+        policy: CachePolicy,
+    ) -> Result<HttpResponse> {
+        // Remove any copy held by the other manager, as the size may have changed
+        if response.body.len() <= self.threshold {
+            self.large.delete(&cache_key).await.ok();
+            self.small.put(cache_key, response, policy).await
+        } else {
+            self.small.delete(&cache_key).await.ok();
+            self.large.put(cache_key, response, policy).await
+        }
+    }
+
+    async fn get_many(
+        &self,
+        cache_keys: &[String],
+    ) -> Result<Vec<Option<(HttpResponse, CachePolicy)>>> {
+        let mut results = self.small.get_many(cache_keys).await?;
+        let (missing, keys): (Vec<usize>, Vec<String>) = results
+            .iter()
+            .zip(cache_keys)
+            .enumerate()
+            .filter(|(_, (result, _))| result.is_none())
+            .map(|(i, (_, key))| (i, key.clone()))
+            .unzip();
+        if !keys.is_empty() {
+            let large = self.large.get_many(&keys).await?;
+            for (i, result) in missing.into_iter().zip(large) {
+                results[i] = result;
+            }
+        }
+        Ok(results)
+    }
+
+    fn supports_streaming(&self) -> bool {
+        self.small.supports_streaming() && self.large.supports_streaming()
+    }
+
+    // The manager is chosen by the size of the computed body, which isn't known
+    // until it's computed, so neither manager's `get_or_put` can be used
+    async fn get_or_put<F, Fut>(
+        &self,
+        cache_key: String,
+        compute: F,
+    ) -> Result<HttpResponse>
+    where
+        F: FnOnce() -> Fut + Send,
+        Fut: Future<Output = Result<(HttpResponse, CachePolicy)>> + Send,
+    {
+        if let Some((response, _)) = self.get(&cache_key).await? {
+            return Ok(response);
+        }
+        let (response, policy) = compute().await?;
+        self.put(cache_key, response, policy).await
+    }
+
+    async fn keys(&self) -> Result<Vec<String>> {
+        let mut keys = self.small.keys().await?;
+        keys.extend(self.large.keys().await?);
+        Ok(keys)
+    }
+
+    async fn delete_matching(
+        &self,
+        predicate: &EntryPredicate,
+    ) -> Result<usize> {
+        Ok(self.small.delete_matching(predicate).await?
+            + self.large.delete_matching(predicate).await?)
+    }
+
+    async fn drain_expired(&self) -> Result<usize> {
+        Ok(self.small.drain_expired().await?
+            + self.large.drain_expired().await?)
+    }
+
+    // Asked in the same order as lookups, the large manager is only asked
+    // when the small one leaves the revalidation to the origin
+    async fn revalidate(
+        &self,
+        cache_key: &str,
+        parts: &http::request::Parts,
+    ) -> Result<Option<RevalidationOutcome>> {
+        match self.small.revalidate(cache_key, parts).await? {
+            Some(outcome) => Ok(Some(outcome)),
+            None => self.large.revalidate(cache_key, parts).await,
+        }
+    }
+
+    async fn touch(&self, cache_key: &str) -> Result<bool> {
+        Ok(self.small.touch(cache_key).await?
+            || self.large.touch(cache_key).await?)
+    }
+
+    async fn delete(&self, cache_key: &str) -> Result<()> {
+        let small = self.small.delete(cache_key).await;
+        let large = self.large.delete(cache_key).await;
+        small.or(large)
+    }
+
+    async fn export(
+        &self,
+        writer: &mut (dyn futures::io::AsyncWrite + Send + Unpin),
+    ) -> Result<()> {
+        self.small.export(writer).await?;
+        self.large.export(writer).await
+    }
+
+    // The export isn't decoded here, so the entries are imported into the large
+    // manager and the ones that fit are then moved to the small one
+    async fn import(
+        &self,
+        reader: &mut (dyn futures::io::AsyncBufRead + Send + Unpin),
+    ) -> Result<usize> {
+        let imported = self.large.import(reader).await?;
+        for cache_key in self.large.keys().await? {
+            if let Some((response, policy)) = self.large.get(&cache_key).await?
+            {
+                if response.body.len() <= self.threshold {
+                    self.put(cache_key, response, policy).await?;
+                }
+            }
+        }
+        Ok(imported)
+    }
+}
@@ -0,0 +1,222 @@
+use std::{fmt, future::Future, sync::Arc, time::Duration};
+
+use crate::{
+    managers::retrying::timer_sleep, CacheManager, EntryPredicate,
+    HttpResponse, Result, RevalidationOutcome, Sleeper, Timeout,
+};
+
+use futures::{
+    future::{select, Either},
+    pin_mut,
+};
+use http_cache_semantics::CachePolicy;
+
+/// Called with the name of a manager operation, such as `"get"` or `"put"`, when it
+/// times out, see [`TimeoutManager::with_timeout_listener`]
+pub type TimeoutListener = Arc<dyn Fn(&str) + Send + Sync>;
+
+/// Implements [`CacheManager`] by giving up on the operations of another manager when
+/// they take longer than a timeout, so a slow backend can't hold up the requests going
+/// through the cache.
+///
+/// A `get` that times out is treated as a miss and a `put` that times out returns the
+/// response as if it had been stored, since the cache is only an optimization for both.
+/// The other operations, like `delete` and `keys`, return a [`Timeout`] error, as their
+/// outcome is unknown. Every timeout is passed to the listener set with
+/// [`TimeoutManager::with_timeout_listener`], so one that is otherwise swallowed, such as
+/// a slow backend no longer storing responses, can still be noticed.
+/// The abandoned operation is dropped, so it's cancelled at its next await point.
+///
+/// The core crate doesn't depend on an async runtime, so the default timer is runtime-agnostic,
+/// use [`TimeoutManager::with_sleeper`] to use the runtime's timer instead. Without the
+/// `futures-timer` feature there is no default timer, and one must be set before use.
+#[derive(Clone)]
+pub struct TimeoutManager<M: CacheManager> {
+    /// Manager the operations are bounded for
+    pub inner: M,
+    timeout: Duration,
+    sleeper: Sleeper,
+    on_timeout: Option<TimeoutListener>,
+}
+
+impl<M: CacheManager + fmt::Debug> fmt::Debug for TimeoutManager<M> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("TimeoutManager")
+            .field("inner", &self.inner)
+            .field("timeout", &self.timeout)
+            .finish_non_exhaustive()
+    }
+}
+
+impl<M: CacheManager> TimeoutManager<M> {
+    /// Create a new manager giving up on operations that take longer than `timeout`
+    pub fn new(inner: M, timeout: Duration) -> Self {
+        Self {
+            inner,
+            timeout,
+            sleeper: Arc::new(timer_sleep),
+            on_timeout: None,
+        }
+    }
+
+    /// Sets the function used to wait for the timeout, e.g. `tokio::time::sleep`
+    #[must_use]
+    pub fn with_sleeper<F, Fut>(mut self, sleeper: F) -> Self
+    where
+        F: Fn(Duration) -> Fut + Send + Sync + 'static,
+        Fut: Future<Output = ()> + Send + 'static,
+    {
+        self.sleeper = Arc::new(move |delay| Box::pin(sleeper(delay)));
+        self
+    }
+
+    /// Sets the function called with the name of an operation when it times out,
+    /// e.g. to log it or record a metric
+    #[must_use]
+    pub fn with_timeout_listener<F>(mut self, on_timeout: F) -> Self
+    where
+        F: Fn(&str) + Send + Sync + 'static,
+    {
+        self.on_timeout = Some(Arc::new(on_timeout));
+        self
+    }
+
+    // Returns `None` if the operation didn't finish before the timeout
+    async fn timed<T, Fut>(&self, name: &str, operation: Fut) -> Option<T>
+    where
+        Fut: Future<Output = T>,
+    {
+        pin_mut!(operation);
+        match select(operation, (self.sleeper)(self.timeout)).await {
+            Either::Left((output, _)) => Some(output),
+            Either::Right(_) => {
+                if let Some(on_timeout) = &self.on_timeout {
+                    on_timeout(name);
+                }
+                None
+            }
+        }
+    }
+
+    // Returns a `Timeout` error if the operation didn't finish before the timeout
+    async fn bounded<T, Fut>(&self, name: &str, operation: Fut) -> Result<T>
+    where
+        Fut: Future<Output = Result<T>>,
+    {
+        self.timed(name, operation)
+            .await
+            .unwrap_or_else(|| Err(Box::new(Timeout)))
+    }
+}
+
+#[async_trait::async_trait]
+impl<M: CacheManager> CacheManager for TimeoutManager<M> {
+    async fn get(
+        &self,
+        cache_key: &str,
+    ) -> Result<Option<(HttpResponse, CachePolicy)>> {
+        Ok(self
+            .timed("get", self.inner.get(cache_key))
+            .await
+            .transpose()?
+            .flatten())
+    }
+
+    async fn put(
+        &self,
+        cache_key: String,
+        response: HttpResponse,
+        policy: CachePolicy,
+    ) -> Result<HttpResponse> {
+        match self
+            .timed("put", self.inner.put(cache_key, response.clone(), policy))
+            .await
+        {
+            Some(result) => result,
+            None => Ok(response),
+        }
+    }
+
+    async fn get_many(
+        &self,
+        cache_keys: &[String],
+    ) -> Result<Vec<Option<(HttpResponse, CachePolicy)>>> {
+        match self.timed("get_many", self.inner.get_many(cache_keys)).await {
+            Some(result) => result,
+            None => Ok(cache_keys.iter().map(|_| None).collect()),
+        }
+    }
+
+    fn supports_streaming(&self) -> bool {
+        self.inner.supports_streaming()
+    }
+
+    // The inner manager's `get_or_put` would bound the computation along with the
+    // lookup and the store, so only the lookup and the store are timed here
+    async fn get_or_put<F, Fut>(
+        &self,
+        cache_key: String,
+        compute: F,
+    ) -> Result<HttpResponse>
+    where
+        F: FnOnce() -> Fut + Send,
+        Fut: Future<Output = Result<(HttpResponse, CachePolicy)>> + Send,
+    {
+        if let Some((response, _)) = self.get(&cache_key).await? {
+            return Ok(response);
+        }
+        let (response, policy) = compute().await?;
+        self.put(cache_key, response, policy).await
+    }
+
+    async fn keys(&self) -> Result<Vec<String>> {
+        self.bounded("keys", self.inner.keys()).await
+    }
+
+    async fn delete_matching(
+        &self,
+        predicate: &EntryPredicate,
+    ) -> Result<usize> {
+        self.bounded("delete_matching", self.inner.delete_matching(predicate))
+            .await
+    }
+
+    async fn export(
+        &self,
+        writer: &mut (dyn futures::io::AsyncWrite + Send + Unpin),
+    ) -> Result<()> {
+        self.inner.export(writer).await
+    }
+
+    async fn import(
+        &self,
+        reader: &mut (dyn futures::io::AsyncBufRead + Send + Unpin),
+    ) -> Result<usize> {
+        self.inner.import(reader).await
+    }
+
+    // Falls back to fetching from the origin when it times out
+    async fn revalidate(
+        &self,
+        cache_key: &str,
+        parts: &http::request::Parts,
+    ) -> Result<Option<RevalidationOutcome>> {
+        Ok(self
+            .timed("revalidate", self.inner.revalidate(cache_key, parts))
+            .await
+            .transpose()?
+            .flatten())
+    }
+
+    async fn drain_expired(&self) -> Result<usize> {
+        self.bounded("drain_expired", self.inner.drain_expired()).await
+    }
+
+    async fn touch(&self, cache_key: &str) -> Result<bool> {
+        self.bounded("touch", self.inner.touch(cache_key)).await
+    }
+
+    async fn delete(&self, cache_key: &str) -> Result<()> {
+        self.bounded("delete", self.inner.delete(cache_key)).await
+    }
+}
@@ -0,0 +1,83 @@
+use std::{fmt, future::Future, sync::Arc, time::Duration};
+
+use url::Url;
+
+use crate::{
+    managers::retrying::timer_sleep, CacheEvent, CacheEvents, Result, Sleeper,
+};
+
+/// Periodically revalidates cached responses with the origin, so they're fresh
+/// when they're next requested, see [`crate::HttpCache::refresher`].
+///
+/// The refreshing itself is left to the client, which sends a `GET` request for each
+/// url through the cache with a `Cache-Control: max-age=0` header, making the cache
+/// revalidate the stored response and store the result. Refreshes that fail are
+/// published as [`CacheEvent::RefreshFailed`] and retried on the next interval.
+///
+/// The interval is waited on with a runtime-agnostic timer by default, see
+/// [`Refresher::with_sleeper`]. Without the `futures-timer` feature there is no
+/// default timer, and one must be set before running it.
+#[derive(Clone)]
+pub struct Refresher {
+    // The cache key and url of each response refreshed
+    entries: Vec<(String, Url)>,
+    interval: Duration,
+    sleeper: Sleeper,
+    cache_events: Option<CacheEvents>,
+}
+
+impl fmt::Debug for Refresher {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Refresher")
+            .field("entries", &self.entries)
+            .field("interval", &self.interval)
+            .finish_non_exhaustive()
+    }
+}
+
+impl Refresher {
+    pub(crate) fn new(
+        entries: Vec<(String, Url)>,
+        interval: Duration,
+        cache_events: Option<CacheEvents>,
+    ) -> Self {
+        Self { entries, interval, sleeper: Arc::new(timer_sleep), cache_events }
+    }
+
+    /// Sets the function used to wait for the interval, e.g. `tokio::time::sleep`
+    #[must_use]
+    pub fn with_sleeper<F, Fut>(mut self, sleeper: F) -> Self
+    where
+        F: Fn(Duration) -> Fut + Send + Sync + 'static,
+        Fut: Future<Output = ()> + Send + 'static,
+    {
+        self.sleeper = Arc::new(move |delay| Box::pin(sleeper(delay)));
+        self
+    }
+
+    /// Returns the urls that are refreshed
+    pub fn urls(&self) -> impl Iterator<Item = &Url> {
+        self.entries.iter().map(|(_, url)| url)
+    }
+
+    /// Calls `refresh` with each url every interval, starting one interval from now.
+    /// It runs until it's dropped, so it's usually spawned on the client's runtime.
+    pub async fn run<F, Fut>(self, mut refresh: F)
+    where
+        F: FnMut(Url) -> Fut,
+        Fut: Future<Output = Result<()>>,
+    {
+        loop {
+            (self.sleeper)(self.interval).await;
+            for (cache_key, url) in &self.entries {
+                if refresh(url.clone()).await.is_err() {
+                    if let Some(events) = &self.cache_events {
+                        events.publish(CacheEvent::RefreshFailed(
+                            cache_key.clone(),
+                        ));
+                    }
+                }
+            }
+        }
+    }
+}
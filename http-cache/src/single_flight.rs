@@ -0,0 +1,102 @@
+use std::{
+    collections::HashMap,
+    fmt,
+    sync::{Arc, Mutex, MutexGuard},
+};
+
+use futures::{
+    channel::oneshot::{channel, Receiver, Sender},
+    future::Shared,
+    FutureExt,
+};
+
+// Resolves to whether the leader stored a response the followers can be served
+type Flight = Shared<Receiver<bool>>;
+
+/// The fetches in flight for [`crate::HttpCacheOptions::single_flight`], keyed by cache key.
+/// Clones share their flights, a new value starts without any, so separate caches
+/// never wait on each other's fetches.
+#[derive(Clone, Default)]
+pub struct InFlight {
+    flights: Arc<Mutex<HashMap<String, Flight>>>,
+}
+
+impl fmt::Debug for InFlight {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("InFlight").finish_non_exhaustive()
+    }
+}
+
+impl InFlight {
+    fn lock(&self) -> MutexGuard<'_, HashMap<String, Flight>> {
+        self.flights.lock().unwrap_or_else(|poisoned| poisoned.into_inner())
+    }
+
+    /// Joins the flight for the given cache key, starting one if there is none
+    pub(crate) fn join(&self, cache_key: String) -> Join {
+        let mut flights = self.lock();
+        if let Some(flight) = flights.get(&cache_key) {
+            return Join::Follower(Follower { flight: flight.clone() });
+        }
+        let (tx, rx) = channel();
+        flights.insert(cache_key.clone(), rx.shared());
+        Join::Leader(Leader {
+            in_flight: self.clone(),
+            cache_key,
+            tx: Some(tx),
+        })
+    }
+}
+
+/// The role of a caller that joined a flight
+pub(crate) enum Join {
+    /// This caller performs the fetch
+    Leader(Leader),
+    /// Another caller is already fetching, await its outcome
+    Follower(Follower),
+}
+
+/// Performs the fetch on behalf of every caller in the flight.
+/// The flight ends when the leader is dropped.
+pub(crate) struct Leader {
+    in_flight: InFlight,
+    cache_key: String,
+    tx: Option<Sender<bool>>,
+}
+
+impl Leader {
+    /// Tells the followers whether the response was stored, the ones that can
+    /// use it are then served from the cache and the rest fetch on their own
+    pub(crate) fn complete(mut self, stored: bool) {
+        if let Some(tx) = self.tx.take() {
+            self.finish();
+            let _ = tx.send(stored);
+        }
+    }
+
+    fn finish(&self) {
+        self.in_flight.lock().remove(&self.cache_key);
+    }
+}
+
+impl Drop for Leader {
+    fn drop(&mut self) {
+        // Followers see the dropped sender as canceled and fetch on their own
+        if self.tx.is_some() {
+            self.finish();
+        }
+    }
+}
+
+/// Awaits the outcome of a fetch performed by a [`Leader`]
+pub(crate) struct Follower {
+    flight: Flight,
+}
+
+impl Follower {
+    /// Returns whether the leader stored a response, false if it went away
+    /// before completing the fetch
+    pub(crate) async fn wait(self) -> bool {
+        self.flight.await.unwrap_or(false)
+    }
+}
@@ -1,6 +1,6 @@
 use crate::{
-    error, CacheMode, HitOrMiss, HttpCacheOptions, HttpResponse, HttpVersion,
-    Result,
+    error, CacheMode, ClosureErrorPolicy, HitOrMiss, HttpCacheOptions,
+    HttpResponse, HttpVersion, Result,
 };
 use http::{header::CACHE_CONTROL, StatusCode};
 use http_cache_semantics::CacheOptions;
@@ -32,20 +32,57 @@ fn cache_mode() -> Result<()> {
     Ok(())
 }
 
+#[cfg(feature = "sha2")]
 #[test]
 fn cache_options() -> Result<()> {
     // Testing the Debug, Default and Clone traits for the HttpCacheOptions struct
     let mut opts = HttpCacheOptions::default();
-    assert_eq!(format!("{:?}", opts.clone()), "HttpCacheOptions { cache_options: None, cache_key: \"Fn(&request::Parts) -> String\", cache_mode_fn: \"Fn(&request::Parts) -> CacheMode\", cache_bust: \"Fn(&request::Parts) -> Vec<String>\", cache_status_headers: true }");
+    assert_eq!(format!("{:?}", opts.clone()), "HttpCacheOptions { cache_options: None, cache_key: \"Fn(&request::Parts) -> String\", cache_key_body: \"Fn(&request::Parts, &[u8]) -> Option<String>\", cache_mode_fn: \"Fn(&request::Parts) -> CacheMode\", cache_bust: \"Fn(&request::Parts) -> Vec<String>\", try_cache_key: \"Fn(&request::Parts) -> Result<String>\", try_cache_mode_fn: \"Fn(&request::Parts) -> Result<CacheMode>\", try_cache_bust: \"Fn(&request::Parts) -> Result<Vec<String>>\", closure_error_policy: Fail, cache_status_headers: true, skip_cache_on_cookie: false, respect_request_authorization: false, cache_events: None, stored_header_allowlist: None, single_flight: false, in_flight: InFlight { .. }, via_pseudonym: None, on_cache_decision: \"Fn(&CacheDecision)\", on_stale_served: \"Fn(&Url, StaleReason)\", strip_headers_before_cache: Some([\"connection\", \"keep-alive\", \"proxy-connection\", \"te\", \"trailer\", \"transfer-encoding\", \"upgrade\", \"set-cookie\", \"set-cookie2\"]), shared: false, cache_range_requests: false, normalize_key: false, verify_body_checksum: false, negative_ttl: None, negative_status_codes: {}, cache_key_suffix_fn: \"Fn(&request::Parts) -> String\", pre_key_request_fn: \"Fn(&mut request::Parts)\", only_if_cached_error: false, cache_options_method: false, max_served_staleness: None, max_fresh_lifetime: None, vary_by_request_headers: [], invalidate_on_unsafe_methods: true, follow_content_location: false, max_headers: None, max_header_value_len: None, hash_keys: false, response_header_rewrite_fn: \"Fn(&request::Parts, &mut HttpResponse)\" }");
     opts.cache_options = Some(CacheOptions::default());
-    assert_eq!(format!("{:?}", opts.clone()), "HttpCacheOptions { cache_options: Some(CacheOptions { shared: true, cache_heuristic: 0.1, immutable_min_time_to_live: 86400s, ignore_cargo_cult: false }), cache_key: \"Fn(&request::Parts) -> String\", cache_mode_fn: \"Fn(&request::Parts) -> CacheMode\", cache_bust: \"Fn(&request::Parts) -> Vec<String>\", cache_status_headers: true }");
+    assert_eq!(format!("{:?}", opts.clone()), "HttpCacheOptions { cache_options: Some(CacheOptions { shared: true, cache_heuristic: 0.1, immutable_min_time_to_live: 86400s, ignore_cargo_cult: false }), cache_key: \"Fn(&request::Parts) -> String\", cache_key_body: \"Fn(&request::Parts, &[u8]) -> Option<String>\", cache_mode_fn: \"Fn(&request::Parts) -> CacheMode\", cache_bust: \"Fn(&request::Parts) -> Vec<String>\", try_cache_key: \"Fn(&request::Parts) -> Result<String>\", try_cache_mode_fn: \"Fn(&request::Parts) -> Result<CacheMode>\", try_cache_bust: \"Fn(&request::Parts) -> Result<Vec<String>>\", closure_error_policy: Fail, cache_status_headers: true, skip_cache_on_cookie: false, respect_request_authorization: false, cache_events: None, stored_header_allowlist: None, single_flight: false, in_flight: InFlight { .. }, via_pseudonym: None, on_cache_decision: \"Fn(&CacheDecision)\", on_stale_served: \"Fn(&Url, StaleReason)\", strip_headers_before_cache: Some([\"connection\", \"keep-alive\", \"proxy-connection\", \"te\", \"trailer\", \"transfer-encoding\", \"upgrade\", \"set-cookie\", \"set-cookie2\"]), shared: false, cache_range_requests: false, normalize_key: false, verify_body_checksum: false, negative_ttl: None, negative_status_codes: {}, cache_key_suffix_fn: \"Fn(&request::Parts) -> String\", pre_key_request_fn: \"Fn(&mut request::Parts)\", only_if_cached_error: false, cache_options_method: false, max_served_staleness: None, max_fresh_lifetime: None, vary_by_request_headers: [], invalidate_on_unsafe_methods: true, follow_content_location: false, max_headers: None, max_header_value_len: None, hash_keys: false, response_header_rewrite_fn: \"Fn(&request::Parts, &mut HttpResponse)\" }");
     opts.cache_options = None;
     opts.cache_key = Some(std::sync::Arc::new(|req: &http::request::Parts| {
         format!("{}:{}:{:?}:test", req.method, req.uri, req.version)
     }));
-    assert_eq!(format!("{:?}", opts), "HttpCacheOptions { cache_options: None, cache_key: \"Fn(&request::Parts) -> String\", cache_mode_fn: \"Fn(&request::Parts) -> CacheMode\", cache_bust: \"Fn(&request::Parts) -> Vec<String>\", cache_status_headers: true }");
+    assert_eq!(format!("{:?}", opts), "HttpCacheOptions { cache_options: None, cache_key: \"Fn(&request::Parts) -> String\", cache_key_body: \"Fn(&request::Parts, &[u8]) -> Option<String>\", cache_mode_fn: \"Fn(&request::Parts) -> CacheMode\", cache_bust: \"Fn(&request::Parts) -> Vec<String>\", try_cache_key: \"Fn(&request::Parts) -> Result<String>\", try_cache_mode_fn: \"Fn(&request::Parts) -> Result<CacheMode>\", try_cache_bust: \"Fn(&request::Parts) -> Result<Vec<String>>\", closure_error_policy: Fail, cache_status_headers: true, skip_cache_on_cookie: false, respect_request_authorization: false, cache_events: None, stored_header_allowlist: None, single_flight: false, in_flight: InFlight { .. }, via_pseudonym: None, on_cache_decision: \"Fn(&CacheDecision)\", on_stale_served: \"Fn(&Url, StaleReason)\", strip_headers_before_cache: Some([\"connection\", \"keep-alive\", \"proxy-connection\", \"te\", \"trailer\", \"transfer-encoding\", \"upgrade\", \"set-cookie\", \"set-cookie2\"]), shared: false, cache_range_requests: false, normalize_key: false, verify_body_checksum: false, negative_ttl: None, negative_status_codes: {}, cache_key_suffix_fn: \"Fn(&request::Parts) -> String\", pre_key_request_fn: \"Fn(&mut request::Parts)\", only_if_cached_error: false, cache_options_method: false, max_served_staleness: None, max_fresh_lifetime: None, vary_by_request_headers: [], invalidate_on_unsafe_methods: true, follow_content_location: false, max_headers: None, max_header_value_len: None, hash_keys: false, response_header_rewrite_fn: \"Fn(&request::Parts, &mut HttpResponse)\" }");
     opts.cache_status_headers = false;
-    assert_eq!(format!("{:?}", opts), "HttpCacheOptions { cache_options: None, cache_key: \"Fn(&request::Parts) -> String\", cache_mode_fn: \"Fn(&request::Parts) -> CacheMode\", cache_bust: \"Fn(&request::Parts) -> Vec<String>\", cache_status_headers: false }");
+    assert_eq!(format!("{:?}", opts), "HttpCacheOptions { cache_options: None, cache_key: \"Fn(&request::Parts) -> String\", cache_key_body: \"Fn(&request::Parts, &[u8]) -> Option<String>\", cache_mode_fn: \"Fn(&request::Parts) -> CacheMode\", cache_bust: \"Fn(&request::Parts) -> Vec<String>\", try_cache_key: \"Fn(&request::Parts) -> Result<String>\", try_cache_mode_fn: \"Fn(&request::Parts) -> Result<CacheMode>\", try_cache_bust: \"Fn(&request::Parts) -> Result<Vec<String>>\", closure_error_policy: Fail, cache_status_headers: false, skip_cache_on_cookie: false, respect_request_authorization: false, cache_events: None, stored_header_allowlist: None, single_flight: false, in_flight: InFlight { .. }, via_pseudonym: None, on_cache_decision: \"Fn(&CacheDecision)\", on_stale_served: \"Fn(&Url, StaleReason)\", strip_headers_before_cache: Some([\"connection\", \"keep-alive\", \"proxy-connection\", \"te\", \"trailer\", \"transfer-encoding\", \"upgrade\", \"set-cookie\", \"set-cookie2\"]), shared: false, cache_range_requests: false, normalize_key: false, verify_body_checksum: false, negative_ttl: None, negative_status_codes: {}, cache_key_suffix_fn: \"Fn(&request::Parts) -> String\", pre_key_request_fn: \"Fn(&mut request::Parts)\", only_if_cached_error: false, cache_options_method: false, max_served_staleness: None, max_fresh_lifetime: None, vary_by_request_headers: [], invalidate_on_unsafe_methods: true, follow_content_location: false, max_headers: None, max_header_value_len: None, hash_keys: false, response_header_rewrite_fn: \"Fn(&request::Parts, &mut HttpResponse)\" }");
+    Ok(())
+}
+
+#[test]
+fn cache_options_builder() -> Result<()> {
+    let built = HttpCacheOptions::builder()
+        .cache_options(CacheOptions { shared: false, ..Default::default() })
+        .cache_key(std::sync::Arc::new(|req: &http::request::Parts| {
+            format!("{}:{}:test", req.method, req.uri)
+        }))
+        .closure_error_policy(ClosureErrorPolicy::Bypass)
+        .cache_status_headers(false)
+        .single_flight(true)
+        .via_pseudonym("edge")
+        .build();
+    let literal = HttpCacheOptions {
+        cache_options: Some(CacheOptions {
+            shared: false,
+            ..Default::default()
+        }),
+        cache_key: Some(std::sync::Arc::new(|req: &http::request::Parts| {
+            format!("{}:{}:test", req.method, req.uri)
+        })),
+        closure_error_policy: ClosureErrorPolicy::Bypass,
+        cache_status_headers: false,
+        single_flight: true,
+        via_pseudonym: Some("edge".to_string()),
+        ..Default::default()
+    };
+    assert_eq!(format!("{:?}", built), format!("{:?}", literal));
+    assert!(built.cache_key.is_some());
+    assert!(built.cache_mode_fn.is_none());
+    assert_eq!(
+        format!("{:?}", HttpCacheOptions::builder().build()),
+        format!("{:?}", HttpCacheOptions::default())
+    );
     Ok(())
 }
 
@@ -59,6 +96,15 @@ fn test_errors() -> Result<()> {
     let bh = error::BadHeader::default();
     assert_eq!(format!("{:?}", bh.clone()), "BadHeader",);
     assert_eq!(bh.to_string(), "Error parsing header value".to_string(),);
+    let nc = error::NotCached::default();
+    assert_eq!(format!("{:?}", nc.clone()), "NotCached",);
+    assert_eq!(
+        nc.to_string(),
+        "No cached response for an only-if-cached request".to_string(),
+    );
+    let to = error::Timeout::default();
+    assert_eq!(format!("{:?}", to.clone()), "Timeout",);
+    assert_eq!(to.to_string(), "Cache manager operation timed out".to_string(),);
     Ok(())
 }
 
@@ -94,6 +140,293 @@ fn response_methods_work() -> Result<()> {
     Ok(())
 }
 
+#[test]
+fn warning_without_host() -> Result<()> {
+    let url = Url::from_str("file:///tmp/cached")?;
+    assert!(url.host().is_none());
+    let mut res = HttpResponse {
+        body: TEST_BODY.to_vec(),
+        headers: HashMap::default(),
+        status: 200,
+        url: url.clone(),
+        version: HttpVersion::Http11,
+    };
+    res.add_warning(&url, 111, "Revalidation failed");
+    assert_eq!(res.warning_code(), Some(111));
+    assert!(res
+        .headers
+        .get("warning")
+        .unwrap()
+        .starts_with("111 - \"Revalidation failed\""));
+    Ok(())
+}
+
+#[test]
+fn validators() -> Result<()> {
+    let url = Url::from_str("http://example.com")?;
+    let mut res = HttpResponse {
+        body: TEST_BODY.to_vec(),
+        headers: HashMap::default(),
+        status: 200,
+        url,
+        version: HttpVersion::Http11,
+    };
+    assert!(res.etag().is_none());
+    assert!(res.last_modified().is_none());
+    assert!(!res.matches_validator("*"));
+
+    res.headers.insert("etag".to_string(), "\"abc\"".to_string());
+    assert_eq!(res.etag(), Some("\"abc\""));
+    assert!(res.matches_validator("\"abc\""));
+    assert!(res.matches_validator("W/\"abc\""));
+    assert!(res.matches_validator("\"xyz\", \"abc\""));
+    assert!(res.matches_validator("*"));
+    assert!(!res.matches_validator("\"xyz\""));
+    assert!(!res.matches_validator("\"ab\""));
+
+    res.headers.insert("etag".to_string(), "W/\"abc\"".to_string());
+    assert_eq!(res.etag(), Some("W/\"abc\""));
+    assert!(res.matches_validator("\"abc\""));
+    assert!(res.matches_validator("W/\"abc\""));
+
+    res.headers.insert(
+        "last-modified".to_string(),
+        "Wed, 21 Oct 2015 07:28:00 GMT".to_string(),
+    );
+    assert_eq!(
+        res.last_modified(),
+        Some(
+            std::time::UNIX_EPOCH + std::time::Duration::from_secs(1445412480)
+        )
+    );
+    res.headers.insert("last-modified".to_string(), "yesterday".to_string());
+    assert!(res.last_modified().is_none());
+    Ok(())
+}
+
+#[test]
+fn into_http_response() -> Result<()> {
+    let url = Url::from_str("http://example.com")?;
+    let res = HttpResponse {
+        body: TEST_BODY.to_vec(),
+        headers: HashMap::from([(
+            "cache-control".to_string(),
+            "max-age=60".to_string(),
+        )]),
+        status: 404,
+        url,
+        version: HttpVersion::H2,
+    };
+    let converted = res.clone().into_http_response()?;
+    assert_eq!(converted.status(), 404);
+    assert_eq!(converted.version(), http::Version::HTTP_2);
+    assert_eq!(converted.headers()["cache-control"], "max-age=60");
+    assert_eq!(converted.body(), TEST_BODY);
+    let converted = res.clone().into_http_response_bytes()?;
+    assert_eq!(converted.body().as_ref(), TEST_BODY);
+
+    // Invalid headers are an error rather than being dropped
+    let mut invalid = res;
+    invalid.headers.insert("bad header".to_string(), "value".to_string());
+    assert!(invalid.clone().into_http_response().is_err());
+    assert!(invalid.into_http_response_bytes().is_err());
+    Ok(())
+}
+
+#[test]
+fn from_http_response() -> Result<()> {
+    use bytes::Bytes;
+    use std::convert::TryFrom;
+
+    // Not valid UTF-8
+    let body = Bytes::from_static(&[0, 159, 146, 150, 255]);
+    let url = Url::from_str("http://example.com/image")?;
+    let response = http::Response::builder()
+        .status(203)
+        .version(http::Version::HTTP_2)
+        .header("content-type", "application/octet-stream")
+        .body(body.clone())?;
+    let res = HttpResponse::try_from((response, url.clone()))?;
+    assert_eq!(res.body, body);
+    assert_eq!(res.headers["content-type"], "application/octet-stream");
+    assert_eq!(res.status, 203);
+    assert_eq!(res.url, url);
+    assert_eq!(res.version, HttpVersion::H2);
+
+    let converted = http::Response::<Bytes>::try_from(res)?;
+    assert_eq!(converted.body(), &body);
+    assert_eq!(converted.headers()["content-type"], "application/octet-stream");
+    assert_eq!(converted.status(), 203);
+    assert_eq!(converted.version(), http::Version::HTTP_2);
+
+    // Header values that aren't visible ASCII are an error
+    let response = http::Response::builder()
+        .header("x-binary", http::HeaderValue::from_bytes(&[0xff])?)
+        .body(Bytes::new())?;
+    assert!(HttpResponse::from_http_response(response, url).is_err());
+    Ok(())
+}
+
+#[test]
+fn content_length_matches_body() -> Result<()> {
+    let url = Url::from_str("http://example.com")?;
+    let mut headers = HashMap::new();
+    headers.insert("content-length".to_string(), "100".to_string());
+    let mut res = HttpResponse {
+        body: TEST_BODY.to_vec(),
+        headers,
+        status: 200,
+        url,
+        version: HttpVersion::Http11,
+    };
+    res.update_content_length();
+    assert_eq!(res.headers.get("content-length").unwrap(), "4");
+
+    // Chunked responses and responses without a body are left alone
+    res.headers.insert("content-length".to_string(), "100".to_string());
+    res.headers.insert("transfer-encoding".to_string(), "chunked".to_string());
+    res.update_content_length();
+    assert_eq!(res.headers.get("content-length").unwrap(), "100");
+    res.headers.remove("transfer-encoding");
+    res.body.clear();
+    res.update_content_length();
+    assert_eq!(res.headers.get("content-length").unwrap(), "100");
+    Ok(())
+}
+
+#[test]
+fn cache_control_directives() -> Result<()> {
+    let url = Url::from_str("http://example.com")?;
+    let mut res = HttpResponse {
+        body: TEST_BODY.to_vec(),
+        headers: HashMap::default(),
+        status: 200,
+        url,
+        version: HttpVersion::Http11,
+    };
+    assert!(!res.is_public());
+    assert!(!res.is_immutable());
+    assert!(!res.no_transform());
+    res.headers.insert(
+        CACHE_CONTROL.as_str().to_string(),
+        "Public, max-age=31536000, immutable".to_string(),
+    );
+    assert!(res.is_public());
+    assert!(res.is_immutable());
+    assert!(!res.no_transform());
+    res.headers.insert(
+        CACHE_CONTROL.as_str().to_string(),
+        "max-age=60, no-transform".to_string(),
+    );
+    assert!(res.no_transform());
+    assert!(!res.is_public());
+    assert!(!res.must_understand());
+    res.headers.insert(
+        CACHE_CONTROL.as_str().to_string(),
+        "max-age=60, must-understand, no-store".to_string(),
+    );
+    assert!(res.must_understand());
+    Ok(())
+}
+
+#[test]
+fn qualified_directives() -> Result<()> {
+    let url = Url::from_str("http://example.com")?;
+    let mut res = HttpResponse {
+        body: TEST_BODY.to_vec(),
+        headers: HashMap::from([
+            (
+                CACHE_CONTROL.as_str().to_string(),
+                "max-age=60, no-cache=\"Set-Cookie, X-A\", private=x-b"
+                    .to_string(),
+            ),
+            ("set-cookie".to_string(), "a=1".to_string()),
+            ("x-a".to_string(), "1".to_string()),
+            ("x-b".to_string(), "1".to_string()),
+            ("x-c".to_string(), "1".to_string()),
+        ]),
+        status: 200,
+        url,
+        version: HttpVersion::Http11,
+    };
+    res.apply_qualified_directives();
+    let mut names: Vec<&str> = res.headers.keys().map(String::as_str).collect();
+    names.sort_unstable();
+    assert_eq!(names, ["cache-control", "x-c"]);
+    assert_eq!(res.headers[CACHE_CONTROL.as_str()], "max-age=60");
+
+    // Unqualified directives are left alone
+    res.headers.insert(
+        CACHE_CONTROL.as_str().to_string(),
+        "no-cache, private".to_string(),
+    );
+    res.apply_qualified_directives();
+    assert_eq!(res.headers[CACHE_CONTROL.as_str()], "no-cache, private");
+    Ok(())
+}
+
+#[test]
+fn age_is_part_of_freshness() -> Result<()> {
+    let url = Url::from_str("http://example.com")?;
+    let mut headers = HashMap::new();
+    headers.insert("age".to_string(), "100".to_string());
+    headers
+        .insert(CACHE_CONTROL.as_str().to_string(), "max-age=200".to_string());
+    let res = HttpResponse {
+        body: TEST_BODY.to_vec(),
+        headers,
+        status: 200,
+        url,
+        version: HttpVersion::Http11,
+    };
+    assert_eq!(res.age(), Some(std::time::Duration::from_secs(100)));
+
+    let req = http::Request::get("http://example.com").body(())?;
+    let policy = http_cache_semantics::CachePolicy::new(&req, &res.parts()?);
+    let ttl = policy.time_to_live(std::time::SystemTime::now()).as_secs();
+    assert!((99..=100).contains(&ttl));
+    Ok(())
+}
+
+#[test]
+fn if_modified_since_from_last_modified() -> Result<()> {
+    use http_cache_semantics::{BeforeRequest, CachePolicy};
+
+    const LAST_MODIFIED: &str = "Wed, 21 Oct 2015 07:28:00 GMT";
+    let url = Url::from_str("http://example.com")?;
+    let mut headers = HashMap::new();
+    headers.insert(
+        CACHE_CONTROL.as_str().to_string(),
+        "public, no-cache".to_string(),
+    );
+    headers.insert("last-modified".to_string(), LAST_MODIFIED.to_string());
+    let res = HttpResponse {
+        body: TEST_BODY.to_vec(),
+        headers,
+        status: 200,
+        url,
+        version: HttpVersion::Http11,
+    };
+    assert!(res.etag().is_none());
+
+    // The conditional request for a response without an etag uses its last-modified date
+    let req = http::Request::get("http://example.com").body(())?;
+    let policy = CachePolicy::new(&req, &res.parts()?);
+    let req = http::Request::get("http://example.com").body(())?.into_parts().0;
+    match policy.before_request(&req, std::time::SystemTime::now()) {
+        BeforeRequest::Stale { request, matches } => {
+            assert!(matches);
+            assert_eq!(
+                request.headers.get("if-modified-since").unwrap(),
+                LAST_MODIFIED
+            );
+            assert!(request.headers.get("if-none-match").is_none());
+        }
+        BeforeRequest::Fresh(_) => panic!("expected a conditional request"),
+    }
+    Ok(())
+}
+
 #[test]
 fn version_http() -> Result<()> {
     assert_eq!(format!("{:?}", HttpVersion::Http09), "Http09");
@@ -138,6 +471,38 @@ fn can_convert_versions_from_http() -> Result<()> {
     Ok(())
 }
 
+#[cfg(feature = "bincode")]
+#[test]
+fn serialize_entry() -> Result<()> {
+    use http_cache_semantics::CachePolicy;
+    use std::time::SystemTime;
+
+    let url = Url::parse("http://example.com")?;
+    let http_res = HttpResponse {
+        body: TEST_BODY.to_vec(),
+        headers: HashMap::from([(
+            "cache-control".to_string(),
+            "max-age=60".to_string(),
+        )]),
+        status: 200,
+        url: url.clone(),
+        version: HttpVersion::H2,
+    };
+    let req = http::Request::get("http://example.com").body(())?;
+    let policy = CachePolicy::new(&req, &http_res.parts()?);
+    let bytes = crate::serialize_entry(&http_res, &policy)?;
+    let (response, restored) = crate::deserialize_entry(&bytes)?;
+    assert_eq!(response.body, TEST_BODY);
+    assert_eq!(response.headers, http_res.headers);
+    assert_eq!(response.status, 200);
+    assert_eq!(response.url, url);
+    assert_eq!(response.version, HttpVersion::H2);
+    let now = SystemTime::now();
+    assert_eq!(restored.time_to_live(now), policy.time_to_live(now));
+    assert!(crate::deserialize_entry(b"garbage").is_err());
+    Ok(())
+}
+
 #[cfg(all(test, feature = "with-http-types"))]
 mod with_http_types {
     use super::*;
@@ -172,11 +537,168 @@ mod with_http_types {
     }
 }
 
+#[test]
+fn normalize_key() -> Result<()> {
+    let key = |options: &HttpCacheOptions, uri: &str| -> Result<String> {
+        let (parts, _) = http::Request::get(uri).body(())?.into_parts();
+        options.create_cache_key(&parts, None, None)
+    };
+    let options = HttpCacheOptions::default();
+    assert_ne!(
+        key(&options, "http://example.com/?a=1&b=2")?,
+        key(&options, "http://example.com/?b=2&a=1")?
+    );
+
+    // Query parameter order, host casing and default ports are normalized
+    let options =
+        HttpCacheOptions { normalize_key: true, ..Default::default() };
+    assert_eq!(
+        key(&options, "http://example.com/?a=1&b=2")?,
+        key(&options, "http://example.com/?b=2&a=1")?
+    );
+    assert_eq!(
+        key(&options, "http://EXAMPLE.com:80/path?b=2&a=1")?,
+        "GET:http://example.com/path?a=1&b=2"
+    );
+    assert_ne!(
+        key(&options, "http://example.com/?a=1")?,
+        key(&options, "http://example.com/?a=2")?
+    );
+    Ok(())
+}
+
+#[cfg(feature = "sha2")]
+#[test]
+fn hash_keys() -> Result<()> {
+    let key = |options: &HttpCacheOptions,
+               method: &str,
+               uri: &str|
+     -> Result<String> {
+        let (parts, _) = http::Request::builder()
+            .method(method)
+            .uri(uri)
+            .body(())?
+            .into_parts();
+        options.create_cache_key(&parts, None, None)
+    };
+    let options = HttpCacheOptions { hash_keys: true, ..Default::default() };
+    let long = format!("http://example.com/?q={}", "a".repeat(4096));
+    let hashed = key(&options, "GET", &long)?;
+    assert!(hashed.starts_with("GET:"));
+    assert_eq!(hashed.len(), "GET:".len() + 64);
+
+    // The same url always has the same key, different urls don't share one
+    assert_eq!(hashed, key(&options, "GET", &long)?);
+    assert_ne!(hashed, key(&options, "GET", "http://example.com/?q=a")?);
+    assert!(key(&options, "POST", &long)?.starts_with("POST:"));
+    assert_ne!(
+        hashed,
+        key(&options, "POST", &long)?.replacen("POST", "GET", 1)
+    );
+    Ok(())
+}
+
+#[test]
+fn cache_events() {
+    use crate::{CacheEvent, CacheEvents};
+    use futures::{FutureExt, StreamExt};
+
+    let events = CacheEvents::with_capacity(2);
+    let mut subscriber = events.subscribe();
+    let dropped = events.subscribe();
+    assert_eq!(events.subscriber_count(), 2);
+    drop(dropped);
+    assert_eq!(events.subscriber_count(), 1);
+
+    // A subscriber that falls behind loses the oldest events
+    for key in ["a", "b", "c"] {
+        events.publish(CacheEvent::Hit(key.to_string()));
+    }
+    assert_eq!(
+        subscriber.next().now_or_never(),
+        Some(Some(CacheEvent::Hit("b".to_string())))
+    );
+    assert_eq!(
+        subscriber.next().now_or_never(),
+        Some(Some(CacheEvent::Hit("c".to_string())))
+    );
+    assert!(subscriber.next().now_or_never().is_none());
+
+    // The stream ends once every clone of the channel is dropped
+    let clone = events.clone();
+    drop(events);
+    clone.publish(CacheEvent::Miss("d".to_string()));
+    drop(clone);
+    assert_eq!(
+        subscriber.next().now_or_never(),
+        Some(Some(CacheEvent::Miss("d".to_string())))
+    );
+    assert_eq!(subscriber.next().now_or_never(), Some(None));
+}
+
+#[test]
+fn cache_key_suffix_fn() -> Result<()> {
+    use std::sync::{
+        atomic::{AtomicU32, Ordering},
+        Arc,
+    };
+
+    let version = Arc::new(AtomicU32::new(1));
+    let suffix_version = version.clone();
+    let key = |options: &HttpCacheOptions| -> Result<String> {
+        let (parts, _) =
+            http::Request::get("http://example.com/").body(())?.into_parts();
+        options.create_cache_key(&parts, None, None)
+    };
+    let mut options = HttpCacheOptions {
+        cache_key_suffix_fn: Some(Arc::new(move |_| {
+            format!("v{}", suffix_version.load(Ordering::SeqCst))
+        })),
+        ..Default::default()
+    };
+    assert_eq!(key(&options)?, "GET:http://example.com/:v1");
+
+    // Bumping the suffix moves every request to a new key
+    version.store(2, Ordering::SeqCst);
+    assert_eq!(key(&options)?, "GET:http://example.com/:v2");
+
+    // The suffix is appended to custom keys as well
+    options.cache_key = Some(Arc::new(|parts| parts.uri.path().to_string()));
+    assert_eq!(key(&options)?, "/:v2");
+    Ok(())
+}
+
+// A cache directory under the system temp dir for a single test,
+// removed when it's dropped so it's cleaned up even if the test fails
+#[cfg(feature = "manager-cacache")]
+struct TestDir(std::path::PathBuf);
+
+#[cfg(feature = "manager-cacache")]
+impl TestDir {
+    fn new(name: &str) -> Self {
+        let path = std::env::temp_dir()
+            .join(format!("http-cacache-{name}-{}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&path);
+        Self(path)
+    }
+
+    fn path(&self) -> &std::path::Path {
+        &self.0
+    }
+}
+
+#[cfg(feature = "manager-cacache")]
+impl Drop for TestDir {
+    fn drop(&mut self) {
+        let _ = std::fs::remove_dir_all(&self.0);
+    }
+}
+
 #[cfg(feature = "manager-cacache")]
 mod with_cacache {
 
     use super::*;
-    use crate::{CACacheManager, CacheManager};
+    use crate::{CACacheManager, CacheManager, DeserializeErrorPolicy};
 
     use http_cache_semantics::CachePolicy;
 
@@ -185,44 +707,338 @@ mod with_cacache {
     #[cfg(feature = "cacache-tokio")]
     use tokio::test as async_test;
 
-    #[async_test]
-    async fn cacache() -> Result<()> {
+    // A response for `http://example.com` with the given body and a policy for it
+    fn entry(body: &[u8]) -> Result<(HttpResponse, CachePolicy)> {
         let url = Url::parse("http://example.com")?;
-        let manager = CACacheManager { path: "./http-cacache-test".into() };
-        assert_eq!(
-            &format!("{:?}", manager),
-            "CACacheManager { path: \"./http-cacache-test\" }"
-        );
         let http_res = HttpResponse {
-            body: TEST_BODY.to_vec(),
+            body: body.to_vec(),
             headers: Default::default(),
             status: 200,
-            url: url.clone(),
+            url,
             version: HttpVersion::Http11,
         };
         let req = http::Request::get("http://example.com").body(())?;
-        let res =
-            http::Response::builder().status(200).body(TEST_BODY.to_vec())?;
-        let policy = CachePolicy::new(&req, &res);
+        let res = http::Response::builder().status(200).body(body.to_vec())?;
+        Ok((http_res, CachePolicy::new(&req, &res)))
+    }
+
+    fn manager(dir: &TestDir) -> CACacheManager {
+        CACacheManager { path: dir.path().into(), ..Default::default() }
+    }
+
+    #[async_test]
+    async fn cacache() -> Result<()> {
+        let dir = TestDir::new("test");
+        let manager = manager(&dir);
+        assert_eq!(
+            format!("{:?}", manager),
+            format!("CACacheManager {{ path: {:?}, on_deserialize_error: Error, max_size: None, dedup_by_etag: false }}", dir.path())
+        );
+        let (http_res, policy) = entry(TEST_BODY)?;
+        let cache_key = format!("{}:{}", GET, &http_res.url);
         manager
-            .put(format!("{}:{}", GET, &url), http_res.clone(), policy.clone())
+            .put(cache_key.clone(), http_res.clone(), policy.clone())
             .await?;
-        let data = manager.get(&format!("{}:{}", GET, &url)).await?;
+        let data = manager.get(&cache_key).await?;
         assert!(data.is_some());
         assert_eq!(data.unwrap().0.body, TEST_BODY);
         let clone = manager.clone();
-        let clonedata = clone.get(&format!("{}:{}", GET, &url)).await?;
+        let clonedata = clone.get(&cache_key).await?;
         assert!(clonedata.is_some());
         assert_eq!(clonedata.unwrap().0.body, TEST_BODY);
-        manager.delete(&format!("{}:{}", GET, &url)).await?;
-        let data = manager.get(&format!("{}:{}", GET, &url)).await?;
+        manager.delete(&cache_key).await?;
+        let data = manager.get(&cache_key).await?;
         assert!(data.is_none());
 
-        manager.put(format!("{}:{}", GET, &url), http_res, policy).await?;
+        manager.put(cache_key.clone(), http_res, policy).await?;
         manager.clear().await?;
-        let data = manager.get(&format!("{}:{}", GET, &url)).await?;
+        let data = manager.get(&cache_key).await?;
         assert!(data.is_none());
-        std::fs::remove_dir_all("./http-cacache-test")?;
+        Ok(())
+    }
+
+    #[async_test]
+    async fn cacache_deserialize_error() -> Result<()> {
+        let dir = TestDir::new("deserialize-error-test");
+        let path = dir.path();
+        cacache::write(path, "corrupt", b"garbage".to_vec()).await?;
+
+        // The error is returned by default
+        let manager = manager(&dir);
+        assert!(manager.get("corrupt").await.is_err());
+
+        // The entry is treated as a miss and left in place
+        let manager = CACacheManager {
+            on_deserialize_error: DeserializeErrorPolicy::TreatAsMiss,
+            ..manager
+        };
+        assert!(manager.get("corrupt").await?.is_none());
+        assert!(cacache::metadata(path, "corrupt").await?.is_some());
+
+        // The entry is deleted and treated as a miss
+        let manager = CACacheManager {
+            on_deserialize_error: DeserializeErrorPolicy::DeleteAndMiss,
+            ..manager
+        };
+        assert!(manager.get("corrupt").await?.is_none());
+        assert!(cacache::metadata(path, "corrupt").await?.is_none());
+        Ok(())
+    }
+
+    #[async_test]
+    async fn cacache_get_or_put() -> Result<()> {
+        use std::sync::atomic::{AtomicUsize, Ordering};
+
+        let dir = TestDir::new("get-or-put-test");
+        let manager = manager(&dir);
+        let computed = AtomicUsize::new(0);
+        let computed = &computed;
+        let compute = move || async move {
+            computed.fetch_add(1, Ordering::SeqCst);
+            entry(TEST_BODY)
+        };
+
+        // Computed and stored on a miss, read back on a hit
+        let res = manager.get_or_put("key".into(), compute).await?;
+        assert_eq!(res.body, TEST_BODY);
+        let res = manager.get_or_put("key".into(), compute).await?;
+        assert_eq!(res.body, TEST_BODY);
+        assert_eq!(computed.load(Ordering::SeqCst), 1);
+        Ok(())
+    }
+
+    #[async_test]
+    async fn cacache_touch() -> Result<()> {
+        let dir = TestDir::new("touch-test");
+        let manager = manager(&dir);
+        let (http_res, policy) = entry(TEST_BODY)?;
+        manager.put("present".into(), http_res, policy).await?;
+        assert!(manager.touch("present").await?);
+        assert!(!manager.touch("absent").await?);
+        assert_eq!(manager.get("present").await?.unwrap().0.body, TEST_BODY);
+        Ok(())
+    }
+
+    #[async_test]
+    async fn cacache_max_size() -> Result<()> {
+        let dir = TestDir::new("max-size-test");
+        let manager = CACacheManager::with_max_size(dir.path(), 2500);
+        let (http_res, policy) = entry(&[0; 1000])?;
+        for i in 0..5 {
+            // Distinct bodies so the entries don't share content
+            let mut http_res = http_res.clone();
+            http_res.body[0] = i;
+            manager.put(format!("key-{i}"), http_res, policy.clone()).await?;
+            std::thread::sleep(std::time::Duration::from_millis(5));
+        }
+
+        // The oldest entries were evicted to stay under the limit
+        let mut total = 0;
+        for metadata in cacache::list_sync(dir.path()) {
+            total += metadata?.size;
+        }
+        assert!(total <= 2500);
+        let keys = manager.keys().await?;
+        assert_eq!(keys.len(), 2);
+        assert!(manager.get("key-4").await?.is_some());
+        assert!(manager.get("key-3").await?.is_some());
+        assert!(manager.get("key-0").await?.is_none());
+        manager.clear().await?;
+        Ok(())
+    }
+
+    #[async_test]
+    async fn cacache_max_size_shared_path() -> Result<()> {
+        let dir = TestDir::new("max-size-shared-path-test");
+        let manager = CACacheManager::with_max_size(dir.path(), 2500);
+        let name = dir.path().file_name().unwrap();
+        let other = CACacheManager::with_max_size(
+            dir.path().join("..").join(name),
+            2500,
+        );
+        let (http_res, policy) = entry(&[0; 1000])?;
+        for i in 0..5 {
+            let mut http_res = http_res.clone();
+            http_res.body[0] = i;
+            // A manager spelling the same directory differently shares its usage,
+            // so the entries it writes are counted by the other one
+            let writer = if (1..3).contains(&i) { &other } else { &manager };
+            writer.put(format!("key-{i}"), http_res, policy.clone()).await?;
+            std::thread::sleep(std::time::Duration::from_millis(5));
+        }
+        assert_eq!(manager.keys().await?.len(), 2);
+        assert!(manager.get("key-4").await?.is_some());
+        assert!(manager.get("key-3").await?.is_some());
+        other.clear().await?;
+        Ok(())
+    }
+
+    #[async_test]
+    async fn cacache_max_size_dedup() -> Result<()> {
+        let dir = TestDir::new("max-size-dedup-test");
+        let manager = CACacheManager {
+            dedup_by_etag: true,
+            ..CACacheManager::with_max_size(dir.path(), 1500)
+        };
+        let (http_res, policy) = entry(&[0; 1000])?;
+        for key in ["first", "second"] {
+            let http_res = HttpResponse {
+                headers: HashMap::from([(
+                    "etag".to_string(),
+                    "\"abc\"".to_string(),
+                )]),
+                url: Url::parse(&format!("http://example.com/{key}"))?,
+                ..http_res.clone()
+            };
+            manager.put(key.into(), http_res, policy.clone()).await?;
+        }
+
+        // The shared body is only counted once, so both entries fit
+        assert!(manager.get("first").await?.is_some());
+        assert!(manager.get("second").await?.is_some());
+        manager.clear().await?;
+        Ok(())
+    }
+
+    #[async_test]
+    async fn cacache_dedup_by_etag() -> Result<()> {
+        let dir = TestDir::new("dedup-test");
+        let path = dir.path();
+        let manager = CACacheManager { dedup_by_etag: true, ..manager(&dir) };
+        let (http_res, policy) = entry(TEST_BODY)?;
+        for (key, etag) in
+            [("first", "\"abc\""), ("second", "\"abc\""), ("weak", "W/\"abc\"")]
+        {
+            let http_res = HttpResponse {
+                headers: HashMap::from([(
+                    "etag".to_string(),
+                    etag.to_string(),
+                )]),
+                url: Url::parse(&format!("http://example.com/{key}"))?,
+                ..http_res.clone()
+            };
+            manager.put(key.into(), http_res, policy.clone()).await?;
+        }
+
+        // Both entries with the strong etag refer to the same stored body
+        let first = cacache::metadata(path, "first").await?.unwrap();
+        let second = cacache::metadata(path, "second").await?.unwrap();
+        assert!(first.raw_metadata.is_some());
+        assert_eq!(first.raw_metadata, second.raw_metadata);
+        assert_ne!(first.integrity, second.integrity);
+        let weak = cacache::metadata(path, "weak").await?.unwrap();
+        assert!(weak.raw_metadata.is_none());
+        for key in ["first", "second", "weak"] {
+            let (res, _) = manager.get(key).await?.unwrap();
+            assert_eq!(res.body, TEST_BODY);
+        }
+        Ok(())
+    }
+
+    #[async_test]
+    async fn cacache_keys() -> Result<()> {
+        let dir = TestDir::new("keys-test");
+        let manager = manager(&dir);
+        let (http_res, policy) = entry(TEST_BODY)?;
+        for key in ["first", "second", "third"] {
+            manager.put(key.into(), http_res.clone(), policy.clone()).await?;
+        }
+        let mut keys = manager.keys().await?;
+        keys.sort();
+        assert_eq!(keys, vec!["first", "second", "third"]);
+        assert_eq!(manager.delete_prefix("th").await?, 1);
+        let mut keys = manager.keys().await?;
+        keys.sort();
+        assert_eq!(keys, vec!["first", "second"]);
+        Ok(())
+    }
+
+    #[async_test]
+    async fn cacache_integrity_for() -> Result<()> {
+        let dir = TestDir::new("integrity-test");
+        let manager = manager(&dir);
+        let (http_res, policy) = entry(TEST_BODY)?;
+        assert!(manager.integrity_for("first").await?.is_none());
+        manager.put("first".into(), http_res.clone(), policy.clone()).await?;
+        manager.put("second".into(), http_res, policy).await?;
+        let integrity = manager.integrity_for("first").await?;
+        assert!(integrity.is_some());
+        assert_eq!(manager.integrity_for("first").await?, integrity);
+        assert_eq!(manager.integrity_for("second").await?, integrity);
+        Ok(())
+    }
+
+    #[async_test]
+    async fn cacache_serialized_entry() -> Result<()> {
+        let dir = TestDir::new("serialized-entry-test");
+        let path = dir.path();
+        let manager = manager(&dir);
+        let (http_res, policy) = entry(TEST_BODY)?;
+
+        // A blob written by the manager can be read with `deserialize_entry`
+        manager.put("managed".into(), http_res.clone(), policy.clone()).await?;
+        let blob = cacache::read(path, "managed").await?;
+        let (response, _) = crate::deserialize_entry(&blob)?;
+        assert_eq!(response.body, TEST_BODY);
+        assert_eq!(response.url, http_res.url);
+        assert_eq!(blob, crate::serialize_entry(&http_res, &policy)?);
+
+        // A blob written with `serialize_entry` can be read by the manager
+        let blob = crate::serialize_entry(&http_res, &policy)?;
+        cacache::write(path, "custom", blob).await?;
+        let (response, _) = manager.get("custom").await?.unwrap();
+        assert_eq!(response.body, TEST_BODY);
+        Ok(())
+    }
+
+    #[async_test]
+    async fn cacache_get_many() -> Result<()> {
+        let dir = TestDir::new("get-many-test");
+        let manager = manager(&dir);
+        let (http_res, policy) = entry(TEST_BODY)?;
+        manager.put("first".into(), http_res.clone(), policy.clone()).await?;
+        manager.put("second".into(), http_res, policy).await?;
+        let data = manager
+            .get_many(&[
+                "second".to_string(),
+                "missing".to_string(),
+                "first".to_string(),
+            ])
+            .await?;
+        assert_eq!(data.len(), 3);
+        assert!(data[0].is_some());
+        assert!(data[1].is_none());
+        assert!(data[2].is_some());
+        Ok(())
+    }
+
+    #[cfg(feature = "export")]
+    #[async_test]
+    async fn cacache_export_import() -> Result<()> {
+        let (source_dir, target_dir) =
+            (TestDir::new("export-test"), TestDir::new("import-test"));
+        let (source, target) = (manager(&source_dir), manager(&target_dir));
+        let binary_body = vec![0, 159, 146, 150, 255];
+        let (mut http_res, policy) = entry(&binary_body)?;
+        http_res
+            .headers
+            .insert("content-type".to_string(), "image/png".to_string());
+        source.put("first".into(), http_res.clone(), policy.clone()).await?;
+        source.put("second".into(), http_res.clone(), policy).await?;
+
+        let mut exported = Vec::new();
+        source.export(&mut exported).await?;
+        assert_eq!(exported.iter().filter(|b| **b == b'\n').count(), 2);
+
+        let imported =
+            target.import(&mut futures::io::Cursor::new(exported)).await?;
+        assert_eq!(imported, 2);
+        for key in ["first", "second"] {
+            let (res, _) = target.get(key).await?.unwrap();
+            assert_eq!(res.body, binary_body);
+            assert_eq!(res.headers, http_res.headers);
+            assert_eq!(res.url, http_res.url);
+        }
         Ok(())
     }
 }
@@ -230,7 +1046,7 @@ mod with_cacache {
 #[cfg(feature = "manager-moka")]
 mod with_moka {
     use super::*;
-    use crate::{CacheManager, MokaManager};
+    use crate::{CacheManager, HttpCache, MokaManager};
 
     use http_cache_semantics::CachePolicy;
     use std::sync::Arc;
@@ -273,4 +1089,1444 @@ mod with_moka {
         assert!(data.is_none());
         Ok(())
     }
+    #[async_attributes::test]
+    async fn moka_get_or_put() -> Result<()> {
+        use std::sync::atomic::{AtomicUsize, Ordering};
+
+        let manager = MokaManager::default();
+        let computed = AtomicUsize::new(0);
+        let url = Url::parse("http://example.com")?;
+        let (computed, url) = (&computed, &url);
+        let compute = move || async move {
+            computed.fetch_add(1, Ordering::SeqCst);
+            async_std::task::sleep(std::time::Duration::from_millis(50)).await;
+            let req = http::Request::get(url.as_str()).body(())?;
+            let res = http::Response::builder()
+                .status(200)
+                .body(TEST_BODY.to_vec())?;
+            let http_res = HttpResponse {
+                body: TEST_BODY.to_vec(),
+                headers: Default::default(),
+                status: 200,
+                url: url.clone(),
+                version: HttpVersion::Http11,
+            };
+            Ok((http_res, CachePolicy::new(&req, &res)))
+        };
+
+        // Concurrent callers share a single computation
+        let results = futures::future::join_all(
+            (0..8).map(|_| manager.get_or_put("key".into(), compute)),
+        )
+        .await;
+        for result in results {
+            assert_eq!(result?.body, TEST_BODY);
+        }
+        assert_eq!(computed.load(Ordering::SeqCst), 1);
+        assert!(manager.get("key").await?.is_some());
+
+        // Errors are returned to the callers and nothing is cached
+        let failing = manager
+            .get_or_put("failing".into(), || async {
+                Err::<(HttpResponse, CachePolicy), _>(crate::BoxError::from(
+                    "failed",
+                ))
+            })
+            .await;
+        assert_eq!(failing.unwrap_err().to_string(), "failed");
+        assert!(manager.get("failing").await?.is_none());
+        Ok(())
+    }
+
+    #[async_attributes::test]
+    async fn moka_touch() -> Result<()> {
+        let manager = MokaManager::default();
+        let url = Url::parse("http://example.com")?;
+        let http_res = HttpResponse {
+            body: TEST_BODY.to_vec(),
+            headers: Default::default(),
+            status: 200,
+            url,
+            version: HttpVersion::Http11,
+        };
+        let req = http::Request::get("http://example.com").body(())?;
+        let res =
+            http::Response::builder().status(200).body(TEST_BODY.to_vec())?;
+        let policy = CachePolicy::new(&req, &res);
+        manager.put("present".into(), http_res, policy).await?;
+        assert!(manager.touch("present").await?);
+        assert!(!manager.touch("absent").await?);
+        assert_eq!(manager.get("present").await?.unwrap().0.body, TEST_BODY);
+        Ok(())
+    }
+
+    #[async_attributes::test]
+    async fn moka_policy_ttl() -> Result<()> {
+        use crate::MokaCacheBuilder;
+
+        let url = Url::parse("http://example.com")?;
+        let entry =
+            |cache_control: &str| -> Result<(HttpResponse, CachePolicy)> {
+                let req = http::Request::get(url.as_str()).body(())?;
+                let res = http::Response::builder()
+                    .status(200)
+                    .header(CACHE_CONTROL.as_str(), cache_control)
+                    .body(TEST_BODY.to_vec())?;
+                let http_res = HttpResponse {
+                    body: TEST_BODY.to_vec(),
+                    headers: HashMap::from([(
+                        CACHE_CONTROL.as_str().to_string(),
+                        cache_control.to_string(),
+                    )]),
+                    status: 200,
+                    url: url.clone(),
+                    version: HttpVersion::Http11,
+                };
+                Ok((http_res, CachePolicy::new(&req, &res)))
+            };
+        let manager = MokaManager::with_policy_ttl(MokaCacheBuilder::new(42));
+        let (short, short_policy) = entry("max-age=1")?;
+        manager.put("short".into(), short, short_policy).await?;
+        let (long, long_policy) = entry("max-age=3600")?;
+        manager.put("long".into(), long, long_policy).await?;
+        assert!(manager.get("short").await?.is_some());
+
+        // The short lived entry is evicted by moka once it's no longer fresh
+        std::thread::sleep(std::time::Duration::from_millis(1100));
+        manager.cache.run_pending_tasks().await;
+        assert!(manager.get("short").await?.is_none());
+        assert!(manager.get("long").await?.is_some());
+        Ok(())
+    }
+
+    #[async_attributes::test]
+    async fn moka_inspect() -> Result<()> {
+        let url = Url::parse("http://example.com")?;
+        let http_res = HttpResponse {
+            body: TEST_BODY.to_vec(),
+            headers: Default::default(),
+            status: 200,
+            url: url.clone(),
+            version: HttpVersion::Http11,
+        };
+        let req = http::Request::get("http://example.com").body(())?;
+        let res =
+            http::Response::builder().status(200).body(TEST_BODY.to_vec())?;
+        let policy = CachePolicy::new(&req, &res);
+        let cache = HttpCache {
+            mode: CacheMode::Default,
+            manager: MokaManager::default(),
+            options: HttpCacheOptions::default(),
+        };
+        let key = format!("{}:{}", GET, &url);
+        cache.manager.put(key.clone(), http_res, policy).await?;
+        let inspected = cache.inspect(&key).await?.unwrap();
+        assert_eq!(inspected.body, TEST_BODY);
+        assert!(inspected.headers.is_empty());
+        assert!(cache
+            .inspect("GET:http://example.com/absent")
+            .await?
+            .is_none());
+        Ok(())
+    }
+
+    #[async_attributes::test]
+    async fn moka_drain_expired() -> Result<()> {
+        let url = Url::parse("http://example.com")?;
+        let entry =
+            |cache_control: &str| -> Result<(HttpResponse, CachePolicy)> {
+                let req = http::Request::get(url.as_str()).body(())?;
+                let res = http::Response::builder()
+                    .status(200)
+                    .header(CACHE_CONTROL.as_str(), cache_control)
+                    .body(TEST_BODY.to_vec())?;
+                let http_res = HttpResponse {
+                    body: TEST_BODY.to_vec(),
+                    headers: HashMap::from([(
+                        CACHE_CONTROL.as_str().to_string(),
+                        cache_control.to_string(),
+                    )]),
+                    status: 200,
+                    url: url.clone(),
+                    version: HttpVersion::Http11,
+                };
+                Ok((http_res, CachePolicy::new(&req, &res)))
+            };
+        let manager = MokaManager::default();
+        let (fresh, fresh_policy) = entry("max-age=3600")?;
+        manager.put("fresh".into(), fresh, fresh_policy).await?;
+        let (expired, expired_policy) = entry("max-age=0")?;
+        manager.put("expired".into(), expired, expired_policy).await?;
+        let (revalidating, revalidating_policy) =
+            entry("max-age=0, stale-while-revalidate=3600")?;
+        manager
+            .put("revalidating".into(), revalidating, revalidating_policy)
+            .await?;
+
+        // Stale entries within their stale-while-revalidate window are kept
+        assert_eq!(manager.drain_expired().await?, 1);
+        assert!(manager.get("fresh").await?.is_some());
+        assert!(manager.get("expired").await?.is_none());
+        assert!(manager.get("revalidating").await?.is_some());
+        Ok(())
+    }
+
+    #[async_attributes::test]
+    async fn moka_eviction_listener() -> Result<()> {
+        use crate::{MokaCacheBuilder, MokaRemovalCause};
+        use std::sync::Mutex;
+
+        let evicted = Arc::new(Mutex::new(Vec::new()));
+        let listener_evicted = evicted.clone();
+        let manager = MokaManager::with_eviction_listener(
+            MokaCacheBuilder::new(1),
+            move |key, cause| {
+                listener_evicted.lock().unwrap().push((key.to_string(), cause))
+            },
+        );
+        let url = Url::parse("http://example.com")?;
+        let http_res = HttpResponse {
+            body: TEST_BODY.to_vec(),
+            headers: Default::default(),
+            status: 200,
+            url: url.clone(),
+            version: HttpVersion::Http11,
+        };
+        let req = http::Request::get("http://example.com").body(())?;
+        let res =
+            http::Response::builder().status(200).body(TEST_BODY.to_vec())?;
+        let policy = CachePolicy::new(&req, &res);
+        manager.put("first".into(), http_res.clone(), policy.clone()).await?;
+        manager.put("second".into(), http_res, policy).await?;
+
+        // Inserting past capacity evicts one of the entries
+        let evicted = evicted.lock().unwrap().clone();
+        assert_eq!(evicted.len(), 1);
+        let (key, cause) = &evicted[0];
+        assert_eq!(*cause, MokaRemovalCause::Size);
+        assert!(manager.get(key).await?.is_none());
+        Ok(())
+    }
+
+    #[async_attributes::test]
+    async fn moka_trim_to() -> Result<()> {
+        let manager = MokaManager::new(crate::MokaCache::new(10));
+        let url = Url::parse("http://example.com")?;
+        let http_res = HttpResponse {
+            body: TEST_BODY.to_vec(),
+            headers: Default::default(),
+            status: 200,
+            url: url.clone(),
+            version: HttpVersion::Http11,
+        };
+        let req = http::Request::get("http://example.com").body(())?;
+        let res =
+            http::Response::builder().status(200).body(TEST_BODY.to_vec())?;
+        let policy = CachePolicy::new(&req, &res);
+        for i in 0..10 {
+            manager
+                .put(format!("key-{i}"), http_res.clone(), policy.clone())
+                .await?;
+        }
+        assert_eq!(manager.cache.entry_count(), 10);
+
+        // Trimming to half of the capacity evicts half of the entries
+        manager.trim_to(0.5).await?;
+        assert_eq!(manager.cache.entry_count(), 5);
+
+        // Trimming to zero empties the cache
+        manager.trim_to(0.0).await?;
+        assert_eq!(manager.cache.entry_count(), 0);
+        Ok(())
+    }
+
+    #[async_attributes::test]
+    async fn moka_keys() -> Result<()> {
+        let manager = MokaManager::default();
+        let url = Url::parse("http://example.com")?;
+        let http_res = HttpResponse {
+            body: TEST_BODY.to_vec(),
+            headers: Default::default(),
+            status: 200,
+            url,
+            version: HttpVersion::Http11,
+        };
+        let req = http::Request::get("http://example.com").body(())?;
+        let res =
+            http::Response::builder().status(200).body(TEST_BODY.to_vec())?;
+        let policy = CachePolicy::new(&req, &res);
+        for key in ["first", "second", "third"] {
+            manager.put(key.into(), http_res.clone(), policy.clone()).await?;
+        }
+        let mut keys = manager.keys().await?;
+        keys.sort();
+        assert_eq!(keys, vec!["first", "second", "third"]);
+        Ok(())
+    }
+
+    #[async_attributes::test]
+    async fn moka_delete_matching() -> Result<()> {
+        let manager = MokaManager::default();
+        let url = Url::parse("http://example.com")?;
+        let http_res = HttpResponse {
+            body: TEST_BODY.to_vec(),
+            headers: Default::default(),
+            status: 200,
+            url: url.clone(),
+            version: HttpVersion::Http11,
+        };
+        let req = http::Request::get("http://example.com").body(())?;
+        let res =
+            http::Response::builder().status(200).body(TEST_BODY.to_vec())?;
+        let policy = CachePolicy::new(&req, &res);
+        manager.put("ok".into(), http_res.clone(), policy.clone()).await?;
+        for key in ["error-1", "error-2"] {
+            let error_res = HttpResponse { status: 500, ..http_res.clone() };
+            manager.put(key.into(), error_res, policy.clone()).await?;
+        }
+
+        // Only the entries matching the predicate are removed
+        let removed = manager
+            .delete_matching(&|_key: &str, res: &HttpResponse| {
+                res.status == 500
+            })
+            .await?;
+        assert_eq!(removed, 2);
+        assert!(manager.get("ok").await?.is_some());
+        assert!(manager.get("error-1").await?.is_none());
+        assert!(manager.get("error-2").await?.is_none());
+
+        // The cache key is passed to the predicate
+        let removed = manager
+            .delete_matching(&|key: &str, _res: &HttpResponse| key == "ok")
+            .await?;
+        assert_eq!(removed, 1);
+        assert!(manager.get("ok").await?.is_none());
+        Ok(())
+    }
+
+    #[cfg(feature = "export")]
+    #[async_attributes::test]
+    async fn moka_export_import() -> Result<()> {
+        let url = Url::parse("http://example.com")?;
+        let source = MokaManager::default();
+        let target = MokaManager::default();
+        let http_res = HttpResponse {
+            body: TEST_BODY.to_vec(),
+            headers: Default::default(),
+            status: 200,
+            url: url.clone(),
+            version: HttpVersion::Http11,
+        };
+        let req = http::Request::get("http://example.com").body(())?;
+        let res =
+            http::Response::builder().status(200).body(TEST_BODY.to_vec())?;
+        let policy = CachePolicy::new(&req, &res);
+        source.put(format!("{}:{}", GET, &url), http_res, policy).await?;
+
+        let mut exported = Vec::new();
+        source.export(&mut exported).await?;
+        let imported =
+            target.import(&mut futures::io::Cursor::new(exported)).await?;
+        assert_eq!(imported, 1);
+        let data = target.get(&format!("{}:{}", GET, &url)).await?;
+        assert_eq!(data.unwrap().0.body, TEST_BODY);
+        Ok(())
+    }
+}
+
+#[cfg(feature = "manager-memory")]
+mod with_memory {
+    use super::*;
+    use crate::{CacheManager, MemoryManager};
+
+    use http_cache_semantics::CachePolicy;
+
+    #[async_attributes::test]
+    async fn memory() -> Result<()> {
+        let manager = MemoryManager::new();
+        assert_eq!(format!("{:?}", manager), "MemoryManager { .. }");
+        let url = Url::parse("http://example.com")?;
+        let key = format!("{}:{}", GET, &url);
+        let http_res = HttpResponse {
+            body: TEST_BODY.to_vec(),
+            headers: Default::default(),
+            status: 200,
+            url,
+            version: HttpVersion::Http11,
+        };
+        let req = http::Request::get("http://example.com").body(())?;
+        let res =
+            http::Response::builder().status(200).body(TEST_BODY.to_vec())?;
+        let policy = CachePolicy::new(&req, &res);
+        manager.put(key.clone(), http_res.clone(), policy.clone()).await?;
+        let data = manager.get(&key).await?;
+        assert_eq!(data.unwrap().0.body, TEST_BODY);
+
+        // Clones share the same entries
+        let clone = manager.clone();
+        assert_eq!(clone.keys().await?, vec![key.clone()]);
+        clone.delete(&key).await?;
+        assert!(manager.get(&key).await?.is_none());
+
+        manager.put(key.clone(), http_res, policy).await?;
+        manager.clear();
+        assert!(manager.get(&key).await?.is_none());
+        Ok(())
+    }
+
+    #[async_attributes::test]
+    async fn memory_get_many() -> Result<()> {
+        let manager = MemoryManager::new();
+        let url = Url::parse("http://example.com")?;
+        let http_res = HttpResponse {
+            body: TEST_BODY.to_vec(),
+            headers: Default::default(),
+            status: 200,
+            url,
+            version: HttpVersion::Http11,
+        };
+        let req = http::Request::get("http://example.com").body(())?;
+        let res =
+            http::Response::builder().status(200).body(TEST_BODY.to_vec())?;
+        let policy = CachePolicy::new(&req, &res);
+        manager.put("first".into(), http_res.clone(), policy.clone()).await?;
+        manager.put("second".into(), http_res, policy).await?;
+        let data = manager
+            .get_many(&[
+                "second".to_string(),
+                "missing".to_string(),
+                "first".to_string(),
+            ])
+            .await?;
+        assert_eq!(data.len(), 3);
+        assert!(data[0].is_some());
+        assert!(data[1].is_none());
+        assert!(data[2].is_some());
+        Ok(())
+    }
+
+    #[async_attributes::test]
+    async fn memory_get_or_put() -> Result<()> {
+        use std::sync::atomic::{AtomicUsize, Ordering};
+
+        let manager = MemoryManager::new();
+        let computed = AtomicUsize::new(0);
+        let url = Url::parse("http://example.com")?;
+        let (computed, url) = (&computed, &url);
+        let compute = move || async move {
+            computed.fetch_add(1, Ordering::SeqCst);
+            async_std::task::sleep(std::time::Duration::from_millis(50)).await;
+            let req = http::Request::get(url.as_str()).body(())?;
+            let res = http::Response::builder()
+                .status(200)
+                .body(TEST_BODY.to_vec())?;
+            let http_res = HttpResponse {
+                body: TEST_BODY.to_vec(),
+                headers: Default::default(),
+                status: 200,
+                url: url.clone(),
+                version: HttpVersion::Http11,
+            };
+            Ok((http_res, CachePolicy::new(&req, &res)))
+        };
+
+        // Concurrent callers share a single computation
+        let results = futures::future::join_all(
+            (0..8).map(|_| manager.get_or_put("key".into(), compute)),
+        )
+        .await;
+        for result in results {
+            assert_eq!(result?.body, TEST_BODY);
+        }
+        assert_eq!(computed.load(Ordering::SeqCst), 1);
+        assert!(manager.get("key").await?.is_some());
+
+        // Errors are returned to the callers and nothing is cached
+        let failing = manager
+            .get_or_put("failing".into(), || async {
+                Err::<(HttpResponse, CachePolicy), _>(crate::BoxError::from(
+                    "failed",
+                ))
+            })
+            .await;
+        assert_eq!(failing.unwrap_err().to_string(), "failed");
+        assert!(manager.get("failing").await?.is_none());
+        Ok(())
+    }
+
+    #[async_attributes::test]
+    async fn memory_get_or_put_per_key() -> Result<()> {
+        use futures::{channel::oneshot, FutureExt};
+
+        let manager = MemoryManager::new();
+        let url = Url::parse("http://example.com")?;
+        let http_res = HttpResponse {
+            body: TEST_BODY.to_vec(),
+            headers: Default::default(),
+            status: 200,
+            url,
+            version: HttpVersion::Http11,
+        };
+        let req = http::Request::get("http://example.com").body(())?;
+        let res =
+            http::Response::builder().status(200).body(TEST_BODY.to_vec())?;
+        let policy = CachePolicy::new(&req, &res);
+        manager.put("hit".into(), http_res.clone(), policy.clone()).await?;
+
+        // A computation that stays pending until it's released
+        let (release, released) = oneshot::channel::<()>();
+        let entry = (http_res.clone(), policy.clone());
+        let pending = manager.get_or_put("pending".into(), || async move {
+            released.await?;
+            Ok(entry)
+        });
+        futures::pin_mut!(pending);
+        assert!(pending.as_mut().now_or_never().is_none());
+
+        // A hit doesn't wait on it
+        let hit = manager
+            .get_or_put("hit".into(), || async {
+                unreachable!("the entry is cached")
+            })
+            .now_or_never();
+        assert_eq!(hit.unwrap()?.body, TEST_BODY);
+
+        // Nor does a computation for another key
+        let entry = (http_res, policy);
+        let other = manager
+            .get_or_put("other".into(), || async move { Ok(entry) })
+            .now_or_never();
+        assert_eq!(other.unwrap()?.body, TEST_BODY);
+
+        release.send(()).unwrap();
+        assert_eq!(pending.await?.body, TEST_BODY);
+        assert!(manager.get("pending").await?.is_some());
+        Ok(())
+    }
+
+    #[async_attributes::test]
+    async fn memory_drain_expired() -> Result<()> {
+        let url = Url::parse("http://example.com")?;
+        let manager = MemoryManager::new();
+        for (key, cache_control) in
+            [("fresh", "max-age=3600"), ("expired", "max-age=0")]
+        {
+            let req = http::Request::get(url.as_str()).body(())?;
+            let res = http::Response::builder()
+                .status(200)
+                .header(CACHE_CONTROL.as_str(), cache_control)
+                .body(TEST_BODY.to_vec())?;
+            let http_res = HttpResponse {
+                body: TEST_BODY.to_vec(),
+                headers: HashMap::from([(
+                    CACHE_CONTROL.as_str().to_string(),
+                    cache_control.to_string(),
+                )]),
+                status: 200,
+                url: url.clone(),
+                version: HttpVersion::Http11,
+            };
+            let policy = CachePolicy::new(&req, &res);
+            manager.put(key.into(), http_res, policy).await?;
+        }
+        assert_eq!(manager.drain_expired().await?, 1);
+        assert!(manager.get("fresh").await?.is_some());
+        assert!(manager.get("expired").await?.is_none());
+        Ok(())
+    }
+}
+
+#[cfg(feature = "json-body-key")]
+mod with_json_body_key {
+    use super::*;
+    use crate::json_fields_key;
+
+    #[test]
+    fn json_fields_key_ignores_unlisted_fields() -> Result<()> {
+        let key_body = json_fields_key(&["/query", "/variables/id"]);
+        let parts = http::Request::post("http://example.com/graphql")
+            .body(())?
+            .into_parts()
+            .0;
+        let first = key_body(
+            &parts,
+            br#"{"query":"a","variables":{"id":1},"requestId":"one"}"#,
+        );
+        let second = key_body(
+            &parts,
+            br#"{"requestId":"two","variables":{"id":1},"query":"a"}"#,
+        );
+        let third = key_body(
+            &parts,
+            br#"{"query":"b","variables":{"id":1},"requestId":"one"}"#,
+        );
+        assert!(first.is_some());
+        assert_eq!(first, second);
+        assert_ne!(first, third);
+        // Non JSON bodies are hashed in full
+        assert_ne!(key_body(&parts, b"a"), key_body(&parts, b"b"));
+        Ok(())
+    }
+}
+
+#[cfg(all(feature = "manager-cacache", feature = "manager-moka"))]
+mod with_size_routing {
+    use super::*;
+    use crate::{
+        CACacheManager, CacheManager, EntryPredicate, HttpCache, MokaManager,
+        RevalidationOutcome, SizeRoutingManager,
+    };
+
+    use http_cache_semantics::CachePolicy;
+
+    #[async_attributes::test]
+    async fn size_routing() -> Result<()> {
+        let url = Url::parse("http://example.com")?;
+        let dir = TestDir::new("size-routing-test");
+        let manager = SizeRoutingManager::new(
+            MokaManager::default(),
+            CACacheManager { path: dir.path().into(), ..Default::default() },
+            8,
+        );
+        let small_res = HttpResponse {
+            body: TEST_BODY.to_vec(),
+            headers: Default::default(),
+            status: 200,
+            url: url.clone(),
+            version: HttpVersion::Http11,
+        };
+        let large_res =
+            HttpResponse { body: vec![b'x'; 1024], ..small_res.clone() };
+        let req = http::Request::get("http://example.com").body(())?;
+        let res =
+            http::Response::builder().status(200).body(TEST_BODY.to_vec())?;
+        let policy = CachePolicy::new(&req, &res);
+        manager.put("small".into(), small_res, policy.clone()).await?;
+        manager.put("large".into(), large_res.clone(), policy.clone()).await?;
+
+        // Each response is stored by the matching manager
+        assert!(manager.small.get("small").await?.is_some());
+        assert!(manager.large.get("small").await?.is_none());
+        assert!(manager.small.get("large").await?.is_none());
+        assert!(manager.large.get("large").await?.is_some());
+        assert_eq!(manager.get("small").await?.unwrap().0.body, TEST_BODY);
+        assert_eq!(manager.get("large").await?.unwrap().0.body.len(), 1024);
+
+        // A batched get reads from both managers
+        let data = manager
+            .get_many(&[
+                "large".to_string(),
+                "missing".to_string(),
+                "small".to_string(),
+            ])
+            .await?;
+        assert_eq!(data[0].as_ref().unwrap().0.body.len(), 1024);
+        assert!(data[1].is_none());
+        assert_eq!(data[2].as_ref().unwrap().0.body, TEST_BODY);
+
+        // A computed response is routed by its size
+        let computed = (large_res.clone(), policy.clone());
+        manager
+            .get_or_put("computed".into(), || async move { Ok(computed) })
+            .await?;
+        assert!(manager.large.get("computed").await?.is_some());
+
+        // A response that shrinks moves to the small manager
+        let shrunk = HttpResponse { body: TEST_BODY.to_vec(), ..large_res };
+        manager.put("large".into(), shrunk, policy).await?;
+        assert!(manager.small.get("large").await?.is_some());
+        assert!(manager.large.get("large").await?.is_none());
+
+        manager.delete("large").await?;
+        assert!(manager.get("large").await?.is_none());
+        Ok(())
+    }
+
+    // Answers every revalidation of an entry it holds
+    #[derive(Debug, Clone, Default)]
+    struct Revalidating(MokaManager);
+
+    #[async_trait::async_trait]
+    impl CacheManager for Revalidating {
+        async fn get(
+            &self,
+            cache_key: &str,
+        ) -> Result<Option<(HttpResponse, CachePolicy)>> {
+            self.0.get(cache_key).await
+        }
+
+        async fn put(
+            &self,
+            cache_key: String,
+            res: HttpResponse,
+            policy: CachePolicy,
+        ) -> Result<HttpResponse> {
+            self.0.put(cache_key, res, policy).await
+        }
+
+        async fn delete_matching(
+            &self,
+            predicate: &EntryPredicate,
+        ) -> Result<usize> {
+            self.0.delete_matching(predicate).await
+        }
+
+        async fn revalidate(
+            &self,
+            cache_key: &str,
+            _parts: &http::request::Parts,
+        ) -> Result<Option<RevalidationOutcome>> {
+            Ok(self
+                .0
+                .get(cache_key)
+                .await?
+                .map(|_| RevalidationOutcome::NotModified(HashMap::new())))
+        }
+
+        async fn delete(&self, cache_key: &str) -> Result<()> {
+            self.0.delete(cache_key).await
+        }
+    }
+
+    #[async_attributes::test]
+    async fn size_routing_revalidate_and_delete_matching() -> Result<()> {
+        let url = Url::parse("http://example.com")?;
+        let manager = SizeRoutingManager::new(
+            MokaManager::default(),
+            Revalidating::default(),
+            8,
+        );
+        let req = http::Request::get("http://example.com").body(())?;
+        let res =
+            http::Response::builder().status(200).body(TEST_BODY.to_vec())?;
+        let policy = CachePolicy::new(&req, &res);
+        let small_res = HttpResponse {
+            body: TEST_BODY.to_vec(),
+            headers: Default::default(),
+            status: 200,
+            url,
+            version: HttpVersion::Http11,
+        };
+        let large_res =
+            HttpResponse { body: vec![0; 1024], ..small_res.clone() };
+        manager.put("small".into(), small_res, policy.clone()).await?;
+        manager.put("large".into(), large_res, policy).await?;
+
+        // Revalidation reaches the manager holding the entry
+        let (parts, _) = req.into_parts();
+        assert!(manager.revalidate("large", &parts).await?.is_some());
+
+        // Entries in both managers are matched
+        assert_eq!(manager.delete_matching(&|_, _| true).await?, 2);
+        assert!(manager.get("small").await?.is_none());
+        assert!(manager.get("large").await?.is_none());
+        Ok(())
+    }
+
+    #[cfg(feature = "export")]
+    #[async_attributes::test]
+    async fn size_routing_export_import() -> Result<()> {
+        let url = Url::parse("http://example.com")?;
+        let req = http::Request::get("http://example.com").body(())?;
+        let res =
+            http::Response::builder().status(200).body(TEST_BODY.to_vec())?;
+        let policy = CachePolicy::new(&req, &res);
+        let small_res = HttpResponse {
+            body: TEST_BODY.to_vec(),
+            headers: Default::default(),
+            status: 200,
+            url,
+            version: HttpVersion::Http11,
+        };
+        let large_res =
+            HttpResponse { body: vec![0; 1024], ..small_res.clone() };
+        let source = SizeRoutingManager::new(
+            MokaManager::default(),
+            MokaManager::default(),
+            8,
+        );
+        source.put("small".into(), small_res, policy.clone()).await?;
+        source.put("large".into(), large_res, policy).await?;
+        let mut exported = Vec::new();
+        source.export(&mut exported).await?;
+
+        // Imported entries are routed by their size
+        let target = SizeRoutingManager::new(
+            MokaManager::default(),
+            MokaManager::default(),
+            8,
+        );
+        let imported =
+            target.import(&mut futures::io::Cursor::new(exported)).await?;
+        assert_eq!(imported, 2);
+        assert!(target.small.get("small").await?.is_some());
+        assert!(target.large.get("small").await?.is_none());
+        assert!(target.large.get("large").await?.is_some());
+        assert!(target.small.get("large").await?.is_none());
+        Ok(())
+    }
+
+    #[test]
+    fn supports_streaming() {
+        let manager = SizeRoutingManager::new(
+            MokaManager::default(),
+            CACacheManager::default(),
+            8,
+        );
+        assert!(!manager.small.supports_streaming());
+        assert!(!manager.large.supports_streaming());
+        assert!(!manager.supports_streaming());
+        let cache = HttpCache {
+            mode: CacheMode::Default,
+            manager,
+            options: Default::default(),
+        };
+        assert!(!cache.supports_streaming());
+    }
+}
+
+#[cfg(all(feature = "encryption", feature = "manager-moka"))]
+mod with_encryption {
+    use super::*;
+    use crate::{
+        CacheManager, EncryptedManager, MokaCacheBuilder, MokaManager,
+    };
+
+    use http_cache_semantics::CachePolicy;
+
+    const KEY: [u8; 32] = [7; 32];
+
+    #[async_attributes::test]
+    async fn encrypted() -> Result<()> {
+        let url = Url::parse("http://example.com")?;
+        let manager = EncryptedManager::new(MokaManager::default(), KEY);
+        assert!(!format!("{:?}", manager).contains("cipher"));
+        let mut headers = HashMap::new();
+        headers.insert("x-secret".to_string(), "hunter2".to_string());
+        let http_res = HttpResponse {
+            body: TEST_BODY.to_vec(),
+            headers,
+            status: 200,
+            url: url.clone(),
+            version: HttpVersion::Http11,
+        };
+        let req = http::Request::get("http://example.com").body(())?;
+        let res = http::Response::builder()
+            .status(200)
+            .header(CACHE_CONTROL, "max-age=86400, public")
+            .body(TEST_BODY.to_vec())?;
+        let policy = CachePolicy::new(&req, &res);
+        let cache_key = format!("{}:{}", GET, &url);
+        manager.put(cache_key.clone(), http_res.clone(), policy).await?;
+
+        // The inner manager only holds ciphertext
+        let (sealed, _) = manager.inner.get(&cache_key).await?.unwrap();
+        assert!(!sealed.headers.contains_key("x-secret"));
+        assert_eq!(sealed.headers.len(), 1);
+        assert!(!sealed.body.windows(TEST_BODY.len()).any(|w| w == TEST_BODY));
+        assert!(!sealed.body.windows(7).any(|w| w == b"hunter2"));
+        assert!(!sealed.body.windows(7).any(|w| w == b"max-age"));
+
+        // Round trip decryption restores the response and policy
+        let (decrypted, policy) = manager.get(&cache_key).await?.unwrap();
+        assert_eq!(decrypted.body, http_res.body);
+        assert_eq!(decrypted.headers, http_res.headers);
+        assert!(!policy.is_stale(std::time::SystemTime::now()));
+
+        // Entries can't be read with another key or under another cache key
+        let other = EncryptedManager::new(manager.inner.clone(), [8; 32]);
+        assert!(other.get(&cache_key).await.is_err());
+        manager.inner.put("moved".into(), sealed, policy.clone()).await?;
+        assert!(manager.get("moved").await.is_err());
+
+        // Batched gets decrypt every entry found
+        let data = manager
+            .get_many(&[cache_key.clone(), "missing".to_string()])
+            .await?;
+        assert_eq!(data[0].as_ref().unwrap().0.headers, http_res.headers);
+        assert!(data[1].is_none());
+
+        // A computed response is stored encrypted and returned decrypted
+        let computed = (http_res.clone(), policy.clone());
+        let res = manager
+            .get_or_put("computed".into(), || async move { Ok(computed) })
+            .await?;
+        assert_eq!(res.body, TEST_BODY);
+        let (sealed, _) = manager.inner.get("computed").await?.unwrap();
+        assert!(!sealed.body.windows(7).any(|w| w == b"hunter2"));
+        assert_eq!(manager.get("computed").await?.unwrap().0.body, TEST_BODY);
+        assert!(
+            manager.supports_streaming() == manager.inner.supports_streaming()
+        );
+
+        manager.delete(&cache_key).await?;
+        assert!(manager.get(&cache_key).await?.is_none());
+        Ok(())
+    }
+
+    #[cfg(feature = "export")]
+    #[async_attributes::test]
+    async fn encrypted_export_import() -> Result<()> {
+        let url = Url::parse("http://example.com")?;
+        let req = http::Request::get("http://example.com").body(())?;
+        let res =
+            http::Response::builder().status(200).body(TEST_BODY.to_vec())?;
+        let http_res = HttpResponse {
+            body: TEST_BODY.to_vec(),
+            headers: Default::default(),
+            status: 200,
+            url,
+            version: HttpVersion::Http11,
+        };
+        let source = EncryptedManager::new(MokaManager::default(), KEY);
+        source
+            .put("key".into(), http_res, CachePolicy::new(&req, &res))
+            .await?;
+        let mut exported = Vec::new();
+        source.export(&mut exported).await?;
+        assert!(!exported.windows(TEST_BODY.len()).any(|w| w == TEST_BODY));
+
+        // The sealed entries can be read by a manager with the same key
+        let target = EncryptedManager::new(MokaManager::default(), KEY);
+        let imported =
+            target.import(&mut futures::io::Cursor::new(exported)).await?;
+        assert_eq!(imported, 1);
+        assert_eq!(target.get("key").await?.unwrap().0.body, TEST_BODY);
+        Ok(())
+    }
+
+    #[async_attributes::test]
+    async fn encrypted_policy_ttl() -> Result<()> {
+        let url = Url::parse("http://example.com")?;
+        let entry =
+            |cache_control: &str| -> Result<(HttpResponse, CachePolicy)> {
+                let req = http::Request::get(url.as_str()).body(())?;
+                let res = http::Response::builder()
+                    .status(200)
+                    .header(CACHE_CONTROL.as_str(), cache_control)
+                    .body(TEST_BODY.to_vec())?;
+                let http_res = HttpResponse {
+                    body: TEST_BODY.to_vec(),
+                    headers: HashMap::from([(
+                        CACHE_CONTROL.as_str().to_string(),
+                        cache_control.to_string(),
+                    )]),
+                    status: 200,
+                    url: url.clone(),
+                    version: HttpVersion::Http11,
+                };
+                Ok((http_res, CachePolicy::new(&req, &res)))
+            };
+        let manager = EncryptedManager::new(
+            MokaManager::with_policy_ttl(MokaCacheBuilder::new(42)),
+            KEY,
+        );
+        let (fresh, fresh_policy) = entry("max-age=3600")?;
+        manager.put("fresh".into(), fresh, fresh_policy).await?;
+        let (expired, expired_policy) = entry("max-age=0")?;
+        manager.put("expired".into(), expired, expired_policy).await?;
+
+        // The inner manager doesn't expire the sealed entries on its own
+        manager.inner.cache.run_pending_tasks().await;
+        assert!(manager.get("fresh").await?.is_some());
+        assert!(manager.get("expired").await?.is_some());
+
+        // They're drained by their own policy
+        assert_eq!(manager.drain_expired().await?, 1);
+        assert!(manager.get("fresh").await?.is_some());
+        assert!(manager.get("expired").await?.is_none());
+        Ok(())
+    }
+}
+
+#[cfg(feature = "futures-timer")]
+mod with_retrying {
+    use super::*;
+    use crate::{CacheManager, RetryingManager, Unsupported};
+
+    use futures::io::AsyncWriteExt;
+    use http_cache_semantics::CachePolicy;
+    use std::{
+        sync::{
+            atomic::{AtomicUsize, Ordering},
+            Arc, Mutex,
+        },
+        time::Duration,
+    };
+
+    // Fails the first `failures` operations with the given error
+    #[derive(Debug, Clone)]
+    struct FlakyManager {
+        failures: usize,
+        attempts: Arc<AtomicUsize>,
+        unsupported: bool,
+    }
+
+    impl FlakyManager {
+        fn new(failures: usize) -> Self {
+            Self { failures, attempts: Arc::default(), unsupported: false }
+        }
+
+        fn attempt(&self) -> Result<()> {
+            if self.attempts.fetch_add(1, Ordering::SeqCst) < self.failures {
+                if self.unsupported {
+                    return Err(Box::new(Unsupported));
+                }
+                return Err("connection reset".into());
+            }
+            Ok(())
+        }
+    }
+
+    #[async_trait::async_trait]
+    impl CacheManager for FlakyManager {
+        async fn get(
+            &self,
+            _cache_key: &str,
+        ) -> Result<Option<(HttpResponse, CachePolicy)>> {
+            self.attempt()?;
+            Ok(None)
+        }
+
+        async fn put(
+            &self,
+            _cache_key: String,
+            res: HttpResponse,
+            _policy: CachePolicy,
+        ) -> Result<HttpResponse> {
+            self.attempt()?;
+            Ok(res)
+        }
+
+        async fn delete(&self, _cache_key: &str) -> Result<()> {
+            self.attempt()
+        }
+
+        async fn export(
+            &self,
+            writer: &mut (dyn futures::io::AsyncWrite + Send + Unpin),
+        ) -> Result<()> {
+            self.attempt()?;
+            writer.write_all(b"flaky\n").await?;
+            Ok(())
+        }
+
+        async fn import(
+            &self,
+            _reader: &mut (dyn futures::io::AsyncBufRead + Send + Unpin),
+        ) -> Result<usize> {
+            self.attempt()?;
+            Ok(1)
+        }
+    }
+
+    #[async_attributes::test]
+    async fn retrying_export_import() -> Result<()> {
+        // Forwarded to the inner manager whether or not the `export` feature is enabled
+        let manager = RetryingManager::new(FlakyManager::new(0));
+        let mut exported = Vec::new();
+        manager.export(&mut exported).await?;
+        assert_eq!(exported, b"flaky\n");
+        let imported =
+            manager.import(&mut futures::io::Cursor::new(exported)).await?;
+        assert_eq!(imported, 1);
+        Ok(())
+    }
+
+    #[async_attributes::test]
+    async fn retrying() -> Result<()> {
+        let delays = Arc::new(Mutex::new(Vec::new()));
+        let sleeper_delays = delays.clone();
+        let flaky = FlakyManager::new(2);
+        let manager =
+            RetryingManager::new(flaky.clone()).with_sleeper(move |delay| {
+                sleeper_delays.lock().unwrap().push(delay);
+                async {}
+            });
+        assert!(manager.get("key").await?.is_none());
+        assert_eq!(flaky.attempts.load(Ordering::SeqCst), 3);
+        assert_eq!(
+            *delays.lock().unwrap(),
+            [Duration::from_millis(50), Duration::from_millis(100)]
+        );
+
+        // The error is returned once the retries run out
+        let flaky = FlakyManager::new(2);
+        let manager = RetryingManager::new(flaky.clone())
+            .with_max_retries(1)
+            .with_backoff(Duration::from_millis(1));
+        assert!(manager.delete("key").await.is_err());
+        assert_eq!(flaky.attempts.load(Ordering::SeqCst), 2);
+
+        // Errors that aren't retryable are returned straight away
+        let flaky = FlakyManager { unsupported: true, ..FlakyManager::new(2) };
+        let manager = RetryingManager::new(flaky.clone())
+            .with_retry_if(|e| !e.is::<Unsupported>());
+        assert!(manager.get("key").await.is_err());
+        assert_eq!(flaky.attempts.load(Ordering::SeqCst), 1);
+
+        // Puts are retried with the same response
+        let flaky = FlakyManager::new(1);
+        let manager = RetryingManager::new(flaky.clone())
+            .with_backoff(Duration::from_millis(1));
+        let url = Url::parse("http://example.com")?;
+        let req = http::Request::get("http://example.com").body(())?;
+        let res =
+            http::Response::builder().status(200).body(TEST_BODY.to_vec())?;
+        let http_res = HttpResponse {
+            body: TEST_BODY.to_vec(),
+            headers: Default::default(),
+            status: 200,
+            url,
+            version: HttpVersion::Http11,
+        };
+        let policy = CachePolicy::new(&req, &res);
+        let stored =
+            manager.put("key".into(), http_res.clone(), policy.clone()).await?;
+        assert_eq!(stored.body, TEST_BODY);
+        assert_eq!(flaky.attempts.load(Ordering::SeqCst), 2);
+
+        // Batched gets are retried
+        let flaky = FlakyManager::new(1);
+        let manager = RetryingManager::new(flaky.clone())
+            .with_backoff(Duration::from_millis(1));
+        let data =
+            manager.get_many(&["a".to_string(), "b".to_string()]).await?;
+        assert!(data.iter().all(Option::is_none));
+        assert_eq!(flaky.attempts.load(Ordering::SeqCst), 3);
+
+        // The lookup and the store are retried, the computation runs once
+        let flaky = FlakyManager::new(1);
+        let manager = RetryingManager::new(flaky.clone())
+            .with_backoff(Duration::from_millis(1));
+        let computed = Arc::new(AtomicUsize::new(0));
+        let counter = computed.clone();
+        let stored = manager
+            .get_or_put("key".into(), || async move {
+                counter.fetch_add(1, Ordering::SeqCst);
+                Ok((http_res, policy))
+            })
+            .await?;
+        assert_eq!(stored.body, TEST_BODY);
+        assert_eq!(computed.load(Ordering::SeqCst), 1);
+        assert_eq!(flaky.attempts.load(Ordering::SeqCst), 3);
+        Ok(())
+    }
+}
+
+#[cfg(feature = "futures-timer")]
+mod with_timeout {
+    use super::*;
+    use crate::{CacheManager, EntryPredicate, Timeout, TimeoutManager};
+
+    use http_cache_semantics::CachePolicy;
+    use std::{
+        sync::{Arc, Mutex},
+        time::Duration,
+    };
+
+    // Never finishes an operation
+    #[derive(Debug, Clone)]
+    struct SlowManager;
+
+    #[async_trait::async_trait]
+    impl CacheManager for SlowManager {
+        async fn get(
+            &self,
+            _cache_key: &str,
+        ) -> Result<Option<(HttpResponse, CachePolicy)>> {
+            futures::future::pending().await
+        }
+
+        async fn put(
+            &self,
+            _cache_key: String,
+            _res: HttpResponse,
+            _policy: CachePolicy,
+        ) -> Result<HttpResponse> {
+            futures::future::pending().await
+        }
+
+        async fn keys(&self) -> Result<Vec<String>> {
+            futures::future::pending().await
+        }
+
+        async fn delete_matching(
+            &self,
+            _predicate: &EntryPredicate,
+        ) -> Result<usize> {
+            futures::future::pending().await
+        }
+
+        async fn drain_expired(&self) -> Result<usize> {
+            futures::future::pending().await
+        }
+
+        async fn touch(&self, _cache_key: &str) -> Result<bool> {
+            futures::future::pending().await
+        }
+
+        async fn delete(&self, _cache_key: &str) -> Result<()> {
+            futures::future::pending().await
+        }
+    }
+
+    #[async_attributes::test]
+    async fn timeout() -> Result<()> {
+        let timed_out = Arc::new(Mutex::new(Vec::new()));
+        let recorded = timed_out.clone();
+        let manager =
+            TimeoutManager::new(SlowManager, Duration::from_millis(10))
+                .with_timeout_listener(move |name| {
+                    recorded.lock().unwrap().push(name.to_string())
+                });
+        // A get that times out is a miss
+        assert!(manager.get("key").await?.is_none());
+
+        // A put that times out still hands back the response
+        let url = Url::parse("http://example.com")?;
+        let req = http::Request::get("http://example.com").body(())?;
+        let res =
+            http::Response::builder().status(200).body(TEST_BODY.to_vec())?;
+        let http_res = HttpResponse {
+            body: TEST_BODY.to_vec(),
+            headers: Default::default(),
+            status: 200,
+            url,
+            version: HttpVersion::Http11,
+        };
+        let stored = manager
+            .put("key".into(), http_res.clone(), CachePolicy::new(&req, &res))
+            .await?;
+        assert_eq!(stored.body, TEST_BODY);
+
+        // A batched get that times out misses every key
+        let data = manager
+            .get_many(&["first".to_string(), "second".to_string()])
+            .await?;
+        assert!(data.iter().all(Option::is_none));
+
+        // The computed response is returned when the lookup and store time out
+        let computed = (http_res, CachePolicy::new(&req, &res));
+        let stored = manager
+            .get_or_put("key".into(), || async move { Ok(computed) })
+            .await?;
+        assert_eq!(stored.body, TEST_BODY);
+
+        // A delete that times out is an error
+        let manager = manager.with_sleeper(|_| async {});
+        let err = manager.delete("key").await.unwrap_err();
+        assert!(err.is::<Timeout>());
+
+        // As are the operations without a result to fall back on
+        assert!(manager.keys().await.unwrap_err().is::<Timeout>());
+        assert!(manager
+            .delete_matching(&|_, _| true)
+            .await
+            .unwrap_err()
+            .is::<Timeout>());
+        assert!(manager.drain_expired().await.unwrap_err().is::<Timeout>());
+        assert!(manager.touch("key").await.unwrap_err().is::<Timeout>());
+
+        // Every timeout is reported, including the ones that are swallowed
+        assert_eq!(
+            *timed_out.lock().unwrap(),
+            [
+                "get",
+                "put",
+                "get_many",
+                "get",
+                "put",
+                "delete",
+                "keys",
+                "delete_matching",
+                "drain_expired",
+                "touch"
+            ]
+        );
+        Ok(())
+    }
+}
+
+#[cfg(feature = "manager-memory")]
+mod with_namespaced {
+    use super::*;
+    use crate::{CacheManager, MemoryManager, NamespacedManager};
+
+    use http_cache_semantics::CachePolicy;
+
+    #[async_attributes::test]
+    async fn namespaced() -> Result<()> {
+        // Both namespaces share the same store
+        let store = MemoryManager::new();
+        let first = NamespacedManager::new(store.clone(), "first");
+        let second = NamespacedManager::new(store.clone(), "second");
+        assert_eq!(
+            format!("{:?}", first),
+            "NamespacedManager { inner: MemoryManager { .. }, namespace: \"first\" }"
+        );
+        let url = Url::parse("http://example.com")?;
+        let http_res = HttpResponse {
+            body: TEST_BODY.to_vec(),
+            headers: Default::default(),
+            status: 200,
+            url: url.clone(),
+            version: HttpVersion::Http11,
+        };
+        let req = http::Request::get("http://example.com").body(())?;
+        let res =
+            http::Response::builder().status(200).body(TEST_BODY.to_vec())?;
+        let policy = CachePolicy::new(&req, &res);
+        let key = format!("{}:{}", GET, &url);
+        first.put(key.clone(), http_res.clone(), policy.clone()).await?;
+
+        // Entries are only visible in their own namespace
+        assert!(first.get(&key).await?.is_some());
+        assert!(second.get(&key).await?.is_none());
+        assert_eq!(first.keys().await?, vec![key.clone()]);
+        assert!(second.keys().await?.is_empty());
+        assert_eq!(store.keys().await?, vec![format!("first:{key}")]);
+
+        // Deleting in one namespace leaves the other alone
+        second.put(key.clone(), http_res, policy).await?;
+        assert_eq!(second.delete_prefix("GET:").await?, 1);
+        assert!(first.get(&key).await?.is_some());
+        assert_eq!(first.delete_matching(&|_, _| true).await?, 1);
+        assert!(store.keys().await?.is_empty());
+        Ok(())
+    }
+
+    #[async_attributes::test]
+    async fn namespaced_get_many_and_get_or_put() -> Result<()> {
+        let store = MemoryManager::new();
+        let first = NamespacedManager::new(store.clone(), "first");
+        let second = NamespacedManager::new(store.clone(), "second");
+        assert!(!first.supports_streaming());
+        let url = Url::parse("http://example.com")?;
+        let http_res = HttpResponse {
+            body: TEST_BODY.to_vec(),
+            headers: Default::default(),
+            status: 200,
+            url,
+            version: HttpVersion::Http11,
+        };
+        let req = http::Request::get("http://example.com").body(())?;
+        let res =
+            http::Response::builder().status(200).body(TEST_BODY.to_vec())?;
+        let policy = CachePolicy::new(&req, &res);
+
+        // The computed entry is stored under the namespace
+        let computed = (http_res.clone(), policy.clone());
+        let res = first
+            .get_or_put("key".into(), || async move { Ok(computed) })
+            .await?;
+        assert_eq!(res.body, TEST_BODY);
+        assert_eq!(store.keys().await?, vec!["first:key".to_string()]);
+
+        // Only the namespace's own entries are read
+        second.put("other".into(), http_res, policy).await?;
+        let data =
+            first.get_many(&["key".to_string(), "other".to_string()]).await?;
+        assert!(data[0].is_some());
+        assert!(data[1].is_none());
+        Ok(())
+    }
+}
+
+#[cfg(feature = "manager-memory")]
+mod with_refresher {
+    use super::*;
+    use crate::{CacheEvent, CacheEvents, HttpCache, MemoryManager};
+
+    use futures::{FutureExt, StreamExt};
+    use std::{
+        sync::{
+            atomic::{AtomicBool, Ordering},
+            Arc, Mutex,
+        },
+        time::Duration,
+    };
+
+    fn cache(options: HttpCacheOptions) -> HttpCache<MemoryManager> {
+        HttpCache {
+            mode: CacheMode::Default,
+            manager: MemoryManager::new(),
+            options,
+        }
+    }
+
+    #[test]
+    fn refresher() -> Result<()> {
+        let events = CacheEvents::default();
+        let mut failed = events.subscribe();
+        let cache = cache(HttpCacheOptions {
+            cache_events: Some(events),
+            ..Default::default()
+        });
+        let keys = vec![
+            "GET:http://example.com/a".to_string(),
+            "GET:http://example.com/b".to_string(),
+        ];
+        let refresher =
+            cache.refresher_for_keys(&keys, Duration::from_secs(60))?;
+
+        // Only the first interval passes, the refresher waits on the second one
+        let slept = Arc::new(AtomicBool::new(false));
+        let refresher = refresher.with_sleeper(move |_| {
+            let waited = slept.swap(true, Ordering::SeqCst);
+            async move {
+                if waited {
+                    futures::future::pending::<()>().await;
+                }
+            }
+        });
+        let refreshed = Arc::new(Mutex::new(Vec::new()));
+        let recorded = refreshed.clone();
+        let run = refresher.run(move |url: Url| {
+            recorded.lock().unwrap().push(url.to_string());
+            let ok = url.path() == "/a";
+            async move {
+                let result: Result<()> =
+                    if ok { Ok(()) } else { Err(Box::new(error::BadHeader)) };
+                result
+            }
+        });
+        assert!(run.now_or_never().is_none());
+        assert_eq!(
+            *refreshed.lock().unwrap(),
+            ["http://example.com/a", "http://example.com/b"]
+        );
+
+        // The failed refresh is reported under its key
+        assert_eq!(
+            failed.next().now_or_never(),
+            Some(Some(CacheEvent::RefreshFailed(keys[1].clone())))
+        );
+        assert!(failed.next().now_or_never().is_none());
+        Ok(())
+    }
+
+    #[cfg(feature = "sha2")]
+    #[test]
+    fn refresher_keys() -> Result<()> {
+        let cache =
+            cache(HttpCacheOptions { hash_keys: true, ..Default::default() });
+        let interval = Duration::from_secs(60);
+
+        // Keys that aren't in the default form are reported, not skipped
+        let keys = vec![
+            "GET:http://example.com/".to_string(),
+            "custom-key".to_string(),
+        ];
+        let err = cache.refresher_for_keys(&keys, interval).unwrap_err();
+        let err = err.downcast_ref::<crate::UnrefreshableKey>().unwrap();
+        assert_eq!(err.0, "custom-key");
+
+        // Refreshing by url works with hashed keys
+        let url = Url::parse("http://example.com/")?;
+        let refresher = cache.refresher(vec![url.clone()], interval)?;
+        assert_eq!(refresher.urls().collect::<Vec<_>>(), [&url]);
+        Ok(())
+    }
 }